@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The number of lines of the build log reported when a build fails.
+pub const LOG_TAIL_LINES_COUNT: usize = 20;
+
+/// The output of a command whose output was streamed to the terminal and to a log file.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct LoggedOutput {
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub log_path: path::PathBuf,
+    /// The last lines of the output.
+    pub tail: Vec<String>,
+}
+impl LoggedOutput {
+    /// Gets the error describing a failed build, with the module which failed to build
+    /// and the end of the build log.
+    pub fn get_build_error(&self) -> String {
+        let mut error = match get_failing_module(&self.tail) {
+            Some(module_name) => format!("Could not build module {}.", module_name),
+            None => "Could not run flatpak build.".to_string(),
+        };
+        error.push_str(&format!(
+            " Last lines of the build log at {}:\n{}",
+            self.log_path.display(),
+            self.tail.join("\n")
+        ));
+        error
+    }
+}
+
+/// Gets the path of a new log file in the logs directory.
+pub fn get_new_log_path(logs_dir: &path::Path) -> Result<path::PathBuf, String> {
+    if let Err(e) = fs::create_dir_all(logs_dir) {
+        return Err(format!(
+            "Could not create logs directory {}: {}",
+            logs_dir.display(),
+            e
+        ));
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut log_path = logs_dir.join(format!("build-{}.log", timestamp));
+    let mut log_index = 1;
+    while log_path.exists() {
+        log_path = logs_dir.join(format!("build-{}-{}.log", timestamp, log_index));
+        log_index += 1;
+    }
+    Ok(log_path)
+}
+
+/// Runs a command, streaming its output to the terminal while appending it to a log file.
+pub fn run_logged_command(command: &mut Command, log_path: &path::Path) -> Result<LoggedOutput, String> {
+    let log_file = match fs::OpenOptions::new().create(true).append(true).open(log_path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Could not create log file {}: {}", log_path.display(), e)),
+    };
+    let log_file = Arc::new(Mutex::new(log_file));
+    let tail: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
+
+    let mut child = match command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not run {:?}: {}", command.get_program(), e)),
+    };
+    let stdout_thread = stream_output(child.stdout.take(), false, log_file.clone(), tail.clone());
+    let stderr_thread = stream_output(child.stderr.take(), true, log_file.clone(), tail.clone());
+    let status = match child.wait() {
+        Ok(s) => s,
+        Err(e) => return Err(e.to_string()),
+    };
+    for output_thread in vec![stdout_thread, stderr_thread].into_iter().flatten() {
+        let _ = output_thread.join();
+    }
+
+    let tail = tail.lock().unwrap().iter().cloned().collect();
+    Ok(LoggedOutput {
+        success: status.success(),
+        exit_code: status.code(),
+        log_path: log_path.to_path_buf(),
+        tail,
+    })
+}
+
+/// Appends a line to a log file, for example to describe the next command.
+pub fn write_log_line(log_path: &path::Path, line: &str) -> Result<(), String> {
+    let mut log_file = match fs::OpenOptions::new().create(true).append(true).open(log_path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Could not open log file {}: {}", log_path.display(), e)),
+    };
+    match writeln!(log_file, "{}", line) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!(
+            "Could not write to log file {}: {}",
+            log_path.display(),
+            e
+        )),
+    }
+}
+
+fn stream_output<R: Read + Send + 'static>(
+    output: Option<R>,
+    is_stderr: bool,
+    log_file: Arc<Mutex<fs::File>>,
+    tail: Arc<Mutex<VecDeque<String>>>,
+) -> Option<thread::JoinHandle<()>> {
+    let output = output?;
+    Some(thread::spawn(move || {
+        // The output is not always valid UTF-8, for example with compiler output, and the pipe
+        // has to be drained until the end so that the command is not killed by a SIGPIPE.
+        let mut reader = BufReader::new(output);
+        let mut line_bytes: Vec<u8> = vec![];
+        loop {
+            line_bytes.clear();
+            match reader.read_until(b'\n', &mut line_bytes) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+            if line_bytes.last() == Some(&b'\n') {
+                line_bytes.pop();
+            }
+            let line = String::from_utf8_lossy(&line_bytes).to_string();
+            if is_stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+            if let Ok(mut log_file) = log_file.lock() {
+                let _ = writeln!(log_file, "{}", line);
+            }
+            if let Ok(mut tail) = tail.lock() {
+                if tail.len() == LOG_TAIL_LINES_COUNT {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        }
+    }))
+}
+
+/// Finds the module which failed to build from the output of flatpak-builder. The errors
+/// usually name the module, otherwise the module being built is the one which failed.
+pub fn get_failing_module(log_lines: &[String]) -> Option<String> {
+    for line in log_lines.iter().rev() {
+        if let Some(module_error) = line.split("module ").nth(1) {
+            if line.starts_with("Error:") || line.contains("Failed") {
+                if let Some((module_name, _)) = module_error.split_once(':') {
+                    return Some(module_name.trim().to_string());
+                }
+            }
+        }
+    }
+    for line in log_lines.iter().rev() {
+        if let Some(building_module) = line.strip_prefix("Building module ") {
+            return building_module.split(' ').next().map(|m| m.to_string());
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_failing_module() {
+        let log_lines: Vec<String> = [
+            "Building module libfoo in /app/.flatpak-builder/build/libfoo-1",
+            "========================================================================",
+            "Running: make",
+            "make: *** [Makefile:12: all] Error 2",
+            "Error: module libfoo: Child process exited with code 2",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+        assert_eq!(get_failing_module(&log_lines), Some("libfoo".to_string()));
+        assert_eq!(get_failing_module(&log_lines[0..4]), Some("libfoo".to_string()));
+        assert_eq!(get_failing_module(&log_lines[1..4]), None);
+    }
+
+    #[test]
+    pub fn test_run_logged_command() {
+        let logs_dir = std::env::temp_dir().join(format!("fpm-test-logs-{}", std::process::id()));
+        let log_path = get_new_log_path(&logs_dir).unwrap();
+        fs::write(&log_path, "").unwrap();
+        assert_ne!(get_new_log_path(&logs_dir).unwrap(), log_path);
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("echo 'Building module foo in /tmp'; printf 'caf\\351\\n' >&2; echo failed >&2; exit 1");
+
+        let output = run_logged_command(&mut command, &log_path).unwrap();
+        let log_content = fs::read_to_string(&log_path).unwrap();
+        fs::remove_dir_all(&logs_dir).unwrap();
+
+        assert!(!output.success);
+        assert_eq!(output.exit_code, Some(1));
+        assert!(log_content.contains("Building module foo in /tmp\n"));
+        // The lines which are not valid UTF-8 do not stop the output.
+        assert!(log_content.contains("caf\u{FFFD}\n"));
+        assert!(log_content.contains("failed\n"));
+        assert_eq!(output.tail.len(), 3);
+        assert!(output
+            .get_build_error()
+            .starts_with("Could not build module foo."));
+    }
+}
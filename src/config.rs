@@ -8,6 +8,12 @@ use serde::{Deserialize, Serialize};
 // about ignoring an additional directory.
 pub const DEFAULT_CACHE_DIR: &str = ".git/";
 pub const DEFAULT_CONFIG_FILE_NAME: &str = ".fpm-config.yaml";
+pub const DEFAULT_WORKSPACES_DIR_NAME: &str = "fpm-workspaces";
+// The workspace used when building a manifest outside of a workspace.
+pub const DEFAULT_WORKSPACE_NAME: &str = "default";
+
+// The number of builds kept in the history of each workspace.
+pub const MAX_BUILD_RECORDS: usize = 50;
 
 #[derive(Deserialize, Serialize, Debug, Default)]
 #[serde(default)]
@@ -15,10 +21,113 @@ pub struct WorkspaceConfig {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub current_workspace: Option<String>,
 
+    /// The last build, of any workspace.
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_build: Option<String>,
+    pub last_build: Option<BuildRecord>,
 
     pub workspaces: BTreeMap<String, String>,
+
+    /// The builds of each workspace, from the oldest to the most recent.
+    pub builds: BTreeMap<String, Vec<BuildRecord>>,
+}
+impl WorkspaceConfig {
+    pub fn get_current_workspace_name(&self) -> &str {
+        match &self.current_workspace {
+            Some(w) => w,
+            None => DEFAULT_WORKSPACE_NAME,
+        }
+    }
+
+    pub fn add_build_record(&mut self, workspace_name: &str, build_record: BuildRecord) {
+        let builds = self.builds.entry(workspace_name.to_string()).or_default();
+        builds.push(build_record.clone());
+        if builds.len() > MAX_BUILD_RECORDS {
+            builds.drain(0..builds.len() - MAX_BUILD_RECORDS);
+        }
+        self.last_build = Some(build_record);
+    }
+
+    pub fn get_builds(&self, workspace_name: &str) -> &[BuildRecord] {
+        match self.builds.get(workspace_name) {
+            Some(b) => b,
+            None => &[],
+        }
+    }
+
+    pub fn get_last_successful_build(&self, workspace_name: &str) -> Option<&BuildRecord> {
+        self.get_builds(workspace_name).iter().rev().find(|b| b.success)
+    }
+}
+
+/// A build of a workspace.
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct BuildRecord {
+    /// The start of the build, in seconds since the Unix epoch.
+    pub timestamp: u64,
+
+    pub manifest_path: String,
+
+    /// The checksum of the manifest and of the module files it includes.
+    pub manifest_sha256: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_head: Option<String>,
+
+    pub duration_ms: u64,
+
+    pub success: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<String>,
+}
+
+/// Gets the directory in which the files specific to a workspace, like the build logs,
+/// are stored.
+pub fn get_workspace_cache_dir(workspace_name: &str) -> path::PathBuf {
+    path::Path::new(DEFAULT_CACHE_DIR)
+        .join(DEFAULT_WORKSPACES_DIR_NAME)
+        .join(workspace_name)
+}
+
+pub fn get_workspace_logs_dir(workspace_name: &str) -> path::PathBuf {
+    get_workspace_cache_dir(workspace_name).join("logs")
+}
+
+/// Gets the directory in which flatpak-builder builds a workspace, so that each workspace
+/// keeps its own build when switching between workspaces.
+pub fn get_workspace_build_dir(workspace_name: &str) -> path::PathBuf {
+    get_workspace_cache_dir(workspace_name).join("build")
+}
+
+/// Gets the directory passed to flatpak-builder with `--state-dir`, in which the downloads
+/// and the build cache of a workspace are stored.
+pub fn get_workspace_state_dir(workspace_name: &str) -> path::PathBuf {
+    get_workspace_cache_dir(workspace_name).join("state")
+}
+
+/// Gets the directory in which the main module of a workspace is built by `make --dev`.
+pub fn get_workspace_dev_build_dir(workspace_name: &str) -> path::PathBuf {
+    get_workspace_cache_dir(workspace_name).join("dev-build")
+}
+
+/// Gets the names of the workspaces which have a cache directory.
+pub fn get_cached_workspace_names() -> Vec<String> {
+    let workspaces_dir = path::Path::new(DEFAULT_CACHE_DIR).join(DEFAULT_WORKSPACES_DIR_NAME);
+    let dir_entries = match fs::read_dir(workspaces_dir) {
+        Ok(e) => e,
+        Err(_) => return vec![],
+    };
+    let mut workspace_names: Vec<String> = dir_entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.file_name().to_str().map(|n| n.to_string()))
+        .collect();
+    workspace_names.sort();
+    workspace_names
 }
 
 pub fn write_config(config: &WorkspaceConfig) -> Result<WorkspaceConfig, String> {
@@ -88,9 +197,10 @@ pub fn get_manifest_path() -> Result<String, String> {
     let workspace_name = match &config.current_workspace {
         Some(w) => w,
         None => {
-            return Err(format!(
+            return Err(
                 "Not currently in a workspace. Use `ls` to list the available workspaces and manifests."
-            ))
+                    .to_string(),
+            )
         }
     };
 
@@ -101,25 +211,42 @@ pub fn get_manifest_path() -> Result<String, String> {
         ));
     }
 
-    return Ok(config.workspaces.get(workspace_name).unwrap().to_string());
-}
-
-pub fn get_manifest() -> Result<flatpak_rs::application::FlatpakApplication, String> {
-    let path = match get_manifest_path() {
-        Ok(p) => p,
-        Err(e) => return Err(e),
-    };
-    log::debug!("Loading manifest at {}.", &path);
-
-    flatpak_rs::application::FlatpakApplication::load_from_file(path.to_string())
+    Ok(config.workspaces.get(workspace_name).unwrap().to_string())
 }
 
 pub fn read_or_init_config() -> Result<WorkspaceConfig, String> {
     match read_config() {
         Ok(config) => Ok(config),
         Err(_) => match write_config(&WorkspaceConfig::default()) {
-            Ok(c) => return Ok(c),
-            Err(e) => return Err(e),
+            Ok(c) => Ok(c),
+            Err(e) => Err(e),
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_add_build_record() {
+        let mut config = WorkspaceConfig::default();
+        for timestamp in 0..MAX_BUILD_RECORDS + 5 {
+            config.add_build_record(
+                "default",
+                BuildRecord {
+                    timestamp: timestamp as u64,
+                    success: timestamp % 2 == 0,
+                    ..Default::default()
+                },
+            );
+        }
+        let builds = config.get_builds("default");
+        assert_eq!(builds.len(), MAX_BUILD_RECORDS);
+        assert_eq!(builds[0].timestamp, 5);
+        assert_eq!(config.last_build.as_ref().unwrap().timestamp, 54);
+        assert_eq!(config.get_last_successful_build("default").unwrap().timestamp, 54);
+        assert!(config.get_builds("other").is_empty());
+        assert!(config.get_last_successful_build("other").is_none());
+    }
+}
@@ -0,0 +1,681 @@
+//! Incremental builds of the main module of a Flatpak manifest, from the working tree.
+//!
+//! flatpak-builder rebuilds the last module of a manifest from scratch every time it
+//! changes. During development, the dependencies are instead built once with flatpak-builder,
+//! stopping before the main module, and the main module is then built directly from the
+//! working tree with `flatpak build`, in the build directory of the workspace. The build
+//! system is only configured again when the build files changed.
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path;
+use std::process::Command;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule, FlatpakModuleItem};
+use flatpak_rs::source::{FlatpakSourceItem, FlatpakSourceType};
+use sha2::{Digest, Sha256};
+
+use crate::build_log::LoggedOutput;
+
+pub const DEFAULT_PREFIX: &str = "/app";
+
+// The files in which the checksums of the last successful steps are stored, in the cache
+// directory of the workspace.
+const DEPENDENCIES_CHECKSUM_FILE_NAME: &str = "dev-dependencies.sha256";
+const CONFIGURE_CHECKSUM_FILE_NAME: &str = "dev-configure.sha256";
+
+// The files which require the build system to be configured again when they change.
+const BUILD_FILE_NAMES: &[&str] = &[
+    "meson.build",
+    "meson_options.txt",
+    "meson.options",
+    "CMakeLists.txt",
+    "configure.ac",
+    "configure.in",
+    "Makefile.am",
+    "autogen.sh",
+];
+const BUILD_FILE_EXTENSIONS: &[&str] = &["cmake", "pro", "pri"];
+
+/// The shell commands building a module, with the directories they are run in.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct DevBuildSteps {
+    pub configure: Vec<(path::PathBuf, String)>,
+    pub build: Vec<(path::PathBuf, String)>,
+    pub install: Vec<(path::PathBuf, String)>,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DevBuild {
+    /// The main module of the manifest.
+    pub module: FlatpakModule,
+    manifest_sha256: String,
+    prefix: String,
+    env: Vec<String>,
+    build_args: Vec<String>,
+    /// The directory containing the sources of the main module in the working tree.
+    source_dir: path::PathBuf,
+    /// The directory initialized by flatpak-builder, in which the module is installed.
+    build_dir: path::PathBuf,
+    /// The directory in which the module is built, for the out-of-tree builds.
+    module_build_dir: path::PathBuf,
+    cache_dir: path::PathBuf,
+    logs_dir: path::PathBuf,
+}
+impl DevBuild {
+    pub fn new(
+        flatpak_application: &FlatpakApplication,
+        manifest_path: &str,
+        workspace_name: &str,
+    ) -> Result<DevBuild, String> {
+        let current_dir = match env::current_dir() {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Could not get the current directory: {}", e)),
+        };
+        let manifest_dir = current_dir.join(
+            path::Path::new(manifest_path)
+                .parent()
+                .unwrap_or(path::Path::new("./")),
+        );
+        let (module, module_dir) = get_main_module(flatpak_application, &manifest_dir)?;
+
+        let build_options: Vec<&FlatpakBuildOptions> =
+            vec![&flatpak_application.build_options, &module.build_options]
+                .into_iter()
+                .flatten()
+                .collect();
+        let prefix = build_options
+            .iter()
+            .rev()
+            .map(|o| o.prefix.to_string())
+            .find(|p| !p.is_empty())
+            .unwrap_or(DEFAULT_PREFIX.to_string());
+
+        let mut env = get_flatpak_builder_env(&flatpak_application.get_id(), &get_default_arch(), &prefix);
+        env.extend(get_build_env(&build_options, &prefix));
+
+        let cache_dir = current_dir.join(crate::config::get_workspace_cache_dir(workspace_name));
+        Ok(DevBuild {
+            manifest_sha256: crate::utils::get_manifest_sha256(manifest_path)?,
+            env,
+            build_args: build_options.iter().flat_map(|o| o.build_args.clone()).collect(),
+            source_dir: get_module_source_dir(&module, &module_dir),
+            build_dir: current_dir.join(crate::config::get_workspace_build_dir(workspace_name)),
+            module_build_dir: current_dir.join(crate::config::get_workspace_dev_build_dir(workspace_name)),
+            logs_dir: current_dir.join(crate::config::get_workspace_logs_dir(workspace_name)),
+            cache_dir,
+            prefix,
+            module,
+        })
+    }
+
+    /// Whether the dependencies have to be built (again) with flatpak-builder, because they
+    /// were never built or because the manifest changed since they were built.
+    pub fn needs_dependencies_build(&self) -> bool {
+        if !self.build_dir.join("files").is_dir() {
+            return true;
+        }
+        read_checksum(&self.cache_dir.join(DEPENDENCIES_CHECKSUM_FILE_NAME))
+            != Some(self.manifest_sha256.to_string())
+    }
+
+    /// Records that the dependencies were built. The main module has to be configured again,
+    /// since it is now installed in a new build directory.
+    pub fn set_dependencies_built(&self) -> Result<(), String> {
+        remove_checksum(&self.cache_dir.join(CONFIGURE_CHECKSUM_FILE_NAME))?;
+        write_checksum(
+            &self.cache_dir.join(DEPENDENCIES_CHECKSUM_FILE_NAME),
+            &self.manifest_sha256,
+        )
+    }
+
+    /// Builds and installs the main module in the build directory, configuring the build
+    /// system only when the build files changed.
+    pub fn build(&self) -> Result<LoggedOutput, String> {
+        let configure_checksum_path = self.cache_dir.join(CONFIGURE_CHECKSUM_FILE_NAME);
+        if !self.module_build_dir.is_dir() {
+            remove_checksum(&configure_checksum_path)?;
+        }
+        if let Err(e) = fs::create_dir_all(&self.module_build_dir) {
+            return Err(format!(
+                "Could not create build directory {}: {}",
+                self.module_build_dir.display(),
+                e
+            ));
+        }
+        let log_path = crate::build_log::get_new_log_path(&self.logs_dir)?;
+        log::info!("Writing the build log to {}.", log_path.display());
+        crate::build_log::write_log_line(
+            &log_path,
+            &format!(
+                "Building module {} in {}",
+                self.module.name,
+                self.source_dir.display()
+            ),
+        )?;
+
+        let mut output = LoggedOutput {
+            success: true,
+            exit_code: Some(0),
+            log_path: log_path.to_path_buf(),
+            tail: vec![],
+        };
+
+        let is_configured = self.module_build_dir.join("meson-private").is_dir();
+        let steps = get_build_steps(
+            &self.module,
+            &self.prefix,
+            &self.source_dir,
+            &self.module_build_dir,
+            is_configured,
+        );
+        let configure_sha256 = get_configure_sha256(&self.module, &self.prefix, &self.source_dir)?;
+        if read_checksum(&configure_checksum_path) == Some(configure_sha256.to_string()) {
+            log::info!("The build files did not change, skipping the configure step.");
+        } else {
+            remove_checksum(&configure_checksum_path)?;
+            for (working_dir, command) in &steps.configure {
+                output = self.run_command(working_dir, command, &log_path)?;
+                if !output.success {
+                    return Ok(self.get_failed_output(output));
+                }
+            }
+            write_checksum(&configure_checksum_path, &configure_sha256)?;
+        }
+
+        for (working_dir, command) in steps.build.iter().chain(steps.install.iter()) {
+            output = self.run_command(working_dir, command, &log_path)?;
+            if !output.success {
+                return Ok(self.get_failed_output(output));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Adds the module being built to the output of a failed command, so that the build
+    /// error names it like for the builds made with flatpak-builder.
+    fn get_failed_output(&self, mut output: LoggedOutput) -> LoggedOutput {
+        output
+            .tail
+            .insert(0, format!("Building module {}", self.module.name));
+        output
+    }
+
+    fn run_command(
+        &self,
+        working_dir: &path::Path,
+        command: &str,
+        log_path: &path::Path,
+    ) -> Result<LoggedOutput, String> {
+        println!("Running: {}", command);
+        crate::build_log::write_log_line(log_path, &format!("Running: {}", command))?;
+
+        let mut flatpak_command = Command::new("flatpak");
+        flatpak_command
+            .arg("build")
+            .arg("--die-with-parent")
+            .arg("--nofilesystem=host")
+            .arg(format!("--filesystem={}", self.source_dir.display()))
+            .arg(format!("--filesystem={}", self.module_build_dir.display()))
+            .arg(format!("--build-dir={}", working_dir.display()));
+        for env_var in &self.env {
+            flatpak_command.arg(format!("--env={}", env_var));
+        }
+        flatpak_command.args(&self.build_args);
+        flatpak_command
+            .arg(&self.build_dir)
+            .arg("sh")
+            .arg("-c")
+            .arg(command);
+
+        crate::build_log::run_logged_command(&mut flatpak_command, log_path)
+    }
+}
+
+/// Gets the main module of a manifest, which is the last module, with the directory of the
+/// file it was loaded from.
+pub fn get_main_module(
+    flatpak_application: &FlatpakApplication,
+    manifest_dir: &path::Path,
+) -> Result<(FlatpakModule, path::PathBuf), String> {
+    match flatpak_application.modules.last() {
+        Some(FlatpakModuleItem::Description(m)) => Ok((m.clone(), manifest_dir.to_path_buf())),
+        Some(FlatpakModuleItem::Path(p)) => {
+            let module_path = manifest_dir.join(p);
+            let module = FlatpakModule::load_from_file(module_path.to_str().unwrap_or("").to_string())?;
+            Ok((module, module_path.parent().unwrap_or(manifest_dir).to_path_buf()))
+        }
+        None => Err("The Flatpak manifest does not have any module.".to_string()),
+    }
+}
+
+/// Gets the directory containing the sources of a module in the working tree, which is
+/// the path of its first `dir` source, or the current directory.
+pub fn get_module_source_dir(module: &FlatpakModule, module_dir: &path::Path) -> path::PathBuf {
+    let mut source_dir = module_dir.to_path_buf();
+    let dir_source_path = module.sources.iter().find_map(|s| match s {
+        FlatpakSourceItem::Description(s) if s.get_type() == Some(FlatpakSourceType::Dir) => s.path.clone(),
+        _ => None,
+    });
+    match dir_source_path {
+        Some(p) => source_dir = source_dir.join(p),
+        None => {
+            if let Ok(current_dir) = env::current_dir() {
+                source_dir = current_dir;
+            }
+        }
+    }
+    if !module.subdir.is_empty() {
+        source_dir = source_dir.join(&module.subdir);
+    }
+    fs::canonicalize(&source_dir).unwrap_or(source_dir)
+}
+
+/// Gets the architecture flatpak builds for by default.
+pub fn get_default_arch() -> String {
+    if let Ok(output) = Command::new("flatpak").arg("--default-arch").output() {
+        let arch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !arch.is_empty() {
+            return arch;
+        }
+    }
+    match env::consts::ARCH {
+        "x86" => "i386".to_string(),
+        arch => arch.to_string(),
+    }
+}
+
+/// Gets the variables flatpak-builder defines in the environment of every module build.
+pub fn get_flatpak_builder_env(app_id: &str, arch: &str, prefix: &str) -> Vec<String> {
+    let n_jobs = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    vec![
+        format!("FLATPAK_ID={}", app_id),
+        format!("FLATPAK_ARCH={}", arch),
+        format!("FLATPAK_DEST={}", prefix),
+        format!("FLATPAK_BUILDER_N_JOBS={}", n_jobs),
+    ]
+}
+
+/// Gets the environment of the build from the build options of the application and of the
+/// module, in that order. The per-architecture options are not supported.
+pub fn get_build_env(build_options: &[&FlatpakBuildOptions], prefix: &str) -> Vec<String> {
+    let mut flags: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut paths: BTreeMap<&str, (Vec<&str>, Vec<&str>)> = BTreeMap::new();
+    let mut env_vars: BTreeMap<String, String> = BTreeMap::new();
+    for options in build_options {
+        for (flag_name, flag_value) in [
+            ("CFLAGS", &options.cflags),
+            ("CPPFLAGS", &options.cppflags),
+            ("CXXFLAGS", &options.cxxflags),
+            ("LDFLAGS", &options.ldflags),
+        ] {
+            if !flag_value.is_empty() {
+                flags.entry(flag_name).or_default().push(flag_value);
+            }
+        }
+        for (path_name, prepended_path, appended_path) in [
+            ("PATH", &options.prepend_path, &options.append_path),
+            (
+                "LD_LIBRARY_PATH",
+                &options.prepend_ld_library_path,
+                &options.append_ld_library_path,
+            ),
+            (
+                "PKG_CONFIG_PATH",
+                &options.prepend_pkg_config_path,
+                &options.append_pkg_config_path,
+            ),
+        ] {
+            let (prepended_paths, appended_paths) = paths.entry(path_name).or_default();
+            if !prepended_path.is_empty() {
+                prepended_paths.insert(0, prepended_path);
+            }
+            if !appended_path.is_empty() {
+                appended_paths.push(appended_path);
+            }
+        }
+        match &options.env {
+            FlatpakBuildOptionsEnv::Dict(env) => env_vars.extend(env.clone()),
+            FlatpakBuildOptionsEnv::Array(env) => {
+                for env_var in env {
+                    if let Some((name, value)) = env_var.split_once('=') {
+                        env_vars.insert(name.to_string(), value.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut build_env: Vec<String> = vec![];
+    for (flag_name, flag_values) in flags {
+        build_env.push(format!("{}={}", flag_name, flag_values.join(" ")));
+    }
+    let default_paths: BTreeMap<&str, String> = BTreeMap::from([
+        ("PATH", format!("{}/bin:/usr/bin", prefix)),
+        ("LD_LIBRARY_PATH", format!("{}/lib", prefix)),
+        (
+            "PKG_CONFIG_PATH",
+            format!(
+                "{0}/lib/pkgconfig:{0}/share/pkgconfig:/usr/lib/pkgconfig:/usr/share/pkgconfig",
+                prefix
+            ),
+        ),
+    ]);
+    for (path_name, (prepended_paths, appended_paths)) in paths {
+        if prepended_paths.is_empty() && appended_paths.is_empty() {
+            continue;
+        }
+        let mut path_value: Vec<&str> = prepended_paths;
+        path_value.push(default_paths.get(path_name).unwrap());
+        path_value.extend(appended_paths);
+        build_env.push(format!("{}={}", path_name, path_value.join(":")));
+    }
+    for (name, value) in env_vars {
+        build_env.retain(|e| !e.starts_with(&format!("{}=", name)));
+        build_env.push(format!("{}={}", name, value));
+    }
+    build_env
+}
+
+/// Gets the commands configuring, building and installing a module with its build system,
+/// like flatpak-builder would. All the builds except the simple and qmake ones are made out of
+/// tree, so that they don't leave build files in the working tree. The autotools projects which
+/// don't support building out of tree, or which were already configured in the working tree,
+/// cannot be built this way.
+pub fn get_build_steps(
+    module: &FlatpakModule,
+    prefix: &str,
+    source_dir: &path::Path,
+    module_build_dir: &path::Path,
+    is_configured: bool,
+) -> DevBuildSteps {
+    let config_opts = get_shell_args(&module.config_opts);
+    let make_args = get_shell_args(&module.make_args);
+    let make_install_args = get_shell_args(&module.make_install_args);
+    let install_rule = if module.install_rule.is_empty() {
+        "install"
+    } else {
+        &module.install_rule
+    };
+    let source_dir_arg = shell_quote(&source_dir.display().to_string());
+    let source_dir = source_dir.to_path_buf();
+    let module_build_dir = module_build_dir.to_path_buf();
+
+    let mut steps = DevBuildSteps::default();
+    let make_tool = match module.get_buildsystem().as_deref() {
+        Some(flatpak_rs::build_system::SIMPLE) => {
+            for build_command in &module.build_commands {
+                steps.build.push((source_dir.clone(), build_command.to_string()));
+            }
+            None
+        }
+        Some(flatpak_rs::build_system::MESON) => {
+            steps.configure.push((
+                source_dir.clone(),
+                format!(
+                    "meson setup{} --prefix={} --libdir=lib {} {}{}",
+                    if is_configured { " --reconfigure" } else { "" },
+                    prefix,
+                    shell_quote(&module_build_dir.display().to_string()),
+                    source_dir_arg,
+                    config_opts
+                ),
+            ));
+            Some(("ninja", module_build_dir))
+        }
+        Some(flatpak_rs::build_system::CMAKE) | Some(flatpak_rs::build_system::CMAKE_NINJA) => {
+            let (generator, make_tool) = match module.get_buildsystem().as_deref() {
+                Some(flatpak_rs::build_system::CMAKE_NINJA) => ("Ninja", "ninja"),
+                _ => ("Unix Makefiles", "make"),
+            };
+            steps.configure.push((
+                module_build_dir.clone(),
+                format!(
+                    "cmake -G '{}' -DCMAKE_INSTALL_PREFIX:PATH={} -DCMAKE_INSTALL_LIBDIR:PATH=lib {}{}",
+                    generator, prefix, source_dir_arg, config_opts
+                ),
+            ));
+            Some((make_tool, module_build_dir))
+        }
+        Some(flatpak_rs::build_system::QMAKE) => {
+            steps.configure.push((
+                source_dir.clone(),
+                format!("qmake PREFIX={}{}", prefix, config_opts),
+            ));
+            Some(("make", source_dir.clone()))
+        }
+        _ => {
+            if !source_dir.join("configure").is_file() && !module.no_autogen.unwrap_or(false) {
+                steps
+                    .configure
+                    .push((source_dir.clone(), "NOCONFIGURE=1 ./autogen.sh".to_string()));
+            }
+            steps.configure.push((
+                module_build_dir.clone(),
+                format!("{}/configure --prefix={}{}", source_dir_arg, prefix, config_opts),
+            ));
+            Some(("make", module_build_dir))
+        }
+    };
+
+    if let Some((make_tool, build_dir)) = make_tool {
+        steps
+            .build
+            .push((build_dir.clone(), format!("{}{}", make_tool, make_args)));
+        if !module.no_make_install.unwrap_or(false) {
+            steps.install.push((
+                build_dir,
+                format!("{} {}{}", make_tool, install_rule, make_install_args),
+            ));
+        }
+    }
+    for post_install_command in &module.post_install {
+        steps
+            .install
+            .push((source_dir.clone(), post_install_command.to_string()));
+    }
+    steps
+}
+
+/// Computes a checksum of everything the configure step depends on: the module, the
+/// prefix and the build files of the working tree.
+fn get_configure_sha256(
+    module: &FlatpakModule,
+    prefix: &str,
+    source_dir: &path::Path,
+) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    match serde_json::to_string(module) {
+        Ok(m) => hasher.update(m.as_bytes()),
+        Err(e) => return Err(format!("Could not serialize module {}: {}", module.name, e)),
+    };
+    hasher.update(prefix.as_bytes());
+
+    let mut build_file_paths: Vec<path::PathBuf> = vec![];
+    get_build_file_paths(source_dir, &mut build_file_paths);
+    build_file_paths.sort();
+    for build_file_path in build_file_paths {
+        hasher.update(build_file_path.to_str().unwrap_or("").as_bytes());
+        if let Ok(build_file_content) = fs::read(&build_file_path) {
+            hasher.update(&build_file_content);
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Finds the build files of a directory, ignoring the hidden directories.
+fn get_build_file_paths(dir: &path::Path, build_file_paths: &mut Vec<path::PathBuf>) {
+    let dir_entries = match fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(_) => return,
+    };
+    for dir_entry in dir_entries.filter_map(|e| e.ok()) {
+        let entry_path = dir_entry.path();
+        let file_name = dir_entry.file_name().to_str().unwrap_or("").to_string();
+        if entry_path.is_dir() {
+            if !file_name.starts_with('.') {
+                get_build_file_paths(&entry_path, build_file_paths);
+            }
+            continue;
+        }
+        let extension = entry_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if BUILD_FILE_NAMES.contains(&file_name.as_str()) || BUILD_FILE_EXTENSIONS.contains(&extension) {
+            build_file_paths.push(entry_path);
+        }
+    }
+}
+
+fn get_shell_args(args: &[String]) -> String {
+    args.iter().map(|a| format!(" {}", shell_quote(a))).collect()
+}
+
+fn shell_quote(arg: &str) -> String {
+    let is_safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=:,./@%".contains(c));
+    if is_safe {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
+
+fn read_checksum(checksum_path: &path::Path) -> Option<String> {
+    fs::read_to_string(checksum_path)
+        .ok()
+        .map(|c| c.trim().to_string())
+}
+
+fn write_checksum(checksum_path: &path::Path, checksum: &str) -> Result<(), String> {
+    match fs::write(checksum_path, checksum) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write {}: {}", checksum_path.display(), e)),
+    }
+}
+
+/// Forgets the steps of the last dev build of a workspace. This has to be called before
+/// flatpak-builder cleans the build directory of the workspace, since the dependencies and the
+/// main module installed in it by a dev build are then removed.
+pub fn reset_dev_build(workspace_name: &str) -> Result<(), String> {
+    let cache_dir = crate::config::get_workspace_cache_dir(workspace_name);
+    remove_checksum(&cache_dir.join(DEPENDENCIES_CHECKSUM_FILE_NAME))?;
+    remove_checksum(&cache_dir.join(CONFIGURE_CHECKSUM_FILE_NAME))
+}
+
+fn remove_checksum(checksum_path: &path::Path) -> Result<(), String> {
+    if !checksum_path.is_file() {
+        return Ok(());
+    }
+    match fs::remove_file(checksum_path) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not remove {}: {}", checksum_path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flatpak_rs::format::FlatpakManifestFormat;
+
+    #[test]
+    pub fn test_get_build_steps() {
+        let module = FlatpakModule::parse(
+            FlatpakManifestFormat::YAML,
+            r#"
+            name: app
+            buildsystem: meson
+            config-opts:
+              - -Dprofile=development
+              - -Dname=My App
+            post-install:
+              - install -Dm644 data/app.svg /app/share/icons/app.svg
+            sources:
+              - type: dir
+                path: ./
+            "#,
+        )
+        .unwrap();
+        let source_dir = path::Path::new("/src/app");
+        let module_build_dir = path::Path::new("/src/app/.git/fpm-workspaces/default/dev-build");
+
+        let steps = get_build_steps(&module, "/app", source_dir, module_build_dir, false);
+        assert_eq!(steps.configure.len(), 1);
+        assert_eq!(
+            steps.configure[0].1,
+            "meson setup --prefix=/app --libdir=lib /src/app/.git/fpm-workspaces/default/dev-build /src/app -Dprofile=development '-Dname=My App'"
+        );
+        assert_eq!(
+            steps.build[0],
+            (module_build_dir.to_path_buf(), "ninja".to_string())
+        );
+        assert_eq!(steps.install.len(), 2);
+        assert_eq!(steps.install[0].1, "ninja install");
+        assert_eq!(steps.install[1].0, source_dir.to_path_buf());
+
+        let steps = get_build_steps(&module, "/app", source_dir, module_build_dir, true);
+        assert!(steps.configure[0].1.starts_with("meson setup --reconfigure "));
+
+        // The autotools builds are made out of tree, even without builddir.
+        let module = FlatpakModule::parse(
+            FlatpakManifestFormat::YAML,
+            r#"
+            name: app
+            no-autogen: true
+            sources:
+              - type: dir
+                path: ./
+            "#,
+        )
+        .unwrap();
+        let steps = get_build_steps(&module, "/app", source_dir, module_build_dir, false);
+        assert_eq!(
+            steps.configure,
+            vec![(
+                module_build_dir.to_path_buf(),
+                "/src/app/configure --prefix=/app".to_string()
+            )]
+        );
+        assert_eq!(steps.build[0].0, module_build_dir.to_path_buf());
+    }
+
+    #[test]
+    pub fn test_get_flatpak_builder_env() {
+        let build_env = get_flatpak_builder_env("org.example.App", "x86_64", "/app");
+        assert_eq!(build_env[0], "FLATPAK_ID=org.example.App");
+        assert_eq!(build_env[1], "FLATPAK_ARCH=x86_64");
+        assert_eq!(build_env[2], "FLATPAK_DEST=/app");
+        assert!(build_env[3].starts_with("FLATPAK_BUILDER_N_JOBS="));
+    }
+
+    #[test]
+    pub fn test_get_build_env() {
+        let app_build_options = FlatpakBuildOptions {
+            cflags: "-O2".to_string(),
+            append_path: "/usr/lib/sdk/rust-stable/bin".to_string(),
+            ..Default::default()
+        };
+        let mut env = BTreeMap::new();
+        env.insert("CFLAGS".to_string(), "-O0".to_string());
+        env.insert("RUST_BACKTRACE".to_string(), "1".to_string());
+        let module_build_options = FlatpakBuildOptions {
+            ldflags: "-lm".to_string(),
+            env: FlatpakBuildOptionsEnv::Dict(env),
+            ..Default::default()
+        };
+
+        let build_env = get_build_env(&[&app_build_options, &module_build_options], "/app");
+        assert_eq!(
+            build_env,
+            vec![
+                "LDFLAGS=-lm",
+                "PATH=/app/bin:/usr/bin:/usr/lib/sdk/rust-stable/bin",
+                "CFLAGS=-O0",
+                "RUST_BACKTRACE=1",
+            ]
+        );
+    }
+}
@@ -0,0 +1,555 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fs;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const RUBYGEMS_URL: &str = "https://rubygems.org";
+pub const DEFAULT_MODULE_NAME: &str = "ruby-gems";
+pub const DEFAULT_DESTINATION: &str = "gems";
+pub const DEFAULT_GIT_DESTINATION: &str = "gems-git";
+
+/// Main structure for a Bundler lockfile (`Gemfile.lock`).
+/// See https://bundler.io/guides/gemfile_lock.html
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GemfileLock {
+    pub sources: Vec<GemfileLockSource>,
+    pub platforms: Vec<String>,
+    /// The sha256 checksums of the gems, indexed by `name (version)`.
+    pub checksums: BTreeMap<String, String>,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub enum GemfileLockSourceType {
+    #[default]
+    Gem,
+    Git,
+    Path,
+}
+
+/// A `GEM`, `GIT` or `PATH` section of the lockfile.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GemfileLockSource {
+    pub r#type: GemfileLockSourceType,
+    pub remote: String,
+    pub revision: Option<String>,
+    pub specs: Vec<GemfileLockSpec>,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GemfileLockSpec {
+    pub name: String,
+    pub version: String,
+    /// The platform of the gem, when it is not a pure Ruby gem.
+    pub platform: Option<String>,
+    pub dependencies: Vec<String>,
+}
+impl GemfileLockSpec {
+    /// Parses a spec from the `name (version)` or `name (version-platform)` format.
+    pub fn parse(spec: &str) -> Option<GemfileLockSpec> {
+        let (name, version) = spec.trim().split_once(' ')?;
+        let version = version.trim_start_matches('(').trim_end_matches(')');
+        // Gem versions cannot contain a dash, so the rest of the version is the platform.
+        let (version, platform) = match version.split_once('-') {
+            Some((v, p)) => (v, Some(p.to_string())),
+            None => (version, None),
+        };
+        Some(GemfileLockSpec {
+            name: name.to_string(),
+            version: version.to_string(),
+            platform,
+            ..Default::default()
+        })
+    }
+
+    pub fn get_full_version(&self) -> String {
+        match &self.platform {
+            Some(p) => format!("{}-{}", self.version, p),
+            None => self.version.to_string(),
+        }
+    }
+
+    pub fn get_gem_filename(&self) -> String {
+        format!("{}-{}.gem", self.name, self.get_full_version())
+    }
+
+    /// Gets the flatpak architecture a platform-specific gem can be installed on. The gems
+    /// built for other operating systems, or for the musl libc, cannot be used in a flatpak.
+    pub fn get_flatpak_arch(&self) -> Option<String> {
+        let (cpu, os) = self.platform.as_ref()?.split_once('-')?;
+        if os != "linux" && os != "linux-gnu" {
+            return None;
+        }
+        match cpu {
+            "x86_64" => Some("x86_64".to_string()),
+            "aarch64" | "arm64" => Some("aarch64".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Gets the flatpak architectures a gem can be installed on, or `None` if the gem
+    /// can be installed on all of them.
+    pub fn get_arches(&self) -> Option<Vec<String>> {
+        self.platform.as_ref()?;
+        Some(self.get_flatpak_arch().into_iter().collect())
+    }
+}
+
+impl GemfileLock {
+    pub fn file_path_matches(path: &str) -> bool {
+        path.ends_with("Gemfile.lock") || path.ends_with("gems.locked")
+    }
+
+    pub fn parse(lockfile_content: &str) -> Result<GemfileLock, String> {
+        let mut gemfile_lock = GemfileLock::default();
+        let mut current_section = "";
+
+        for (line_number, line) in lockfile_content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if !line.starts_with(' ') {
+                current_section = line.trim();
+                match current_section {
+                    "GEM" | "GIT" | "PATH" => gemfile_lock.sources.push(GemfileLockSource {
+                        r#type: match current_section {
+                            "GIT" => GemfileLockSourceType::Git,
+                            "PATH" => GemfileLockSourceType::Path,
+                            _ => GemfileLockSourceType::Gem,
+                        },
+                        ..Default::default()
+                    }),
+                    _ => {}
+                }
+                continue;
+            }
+
+            let indentation = line.len() - line.trim_start().len();
+            let line = line.trim();
+            match current_section {
+                "GEM" | "GIT" | "PATH" => {
+                    let source = match gemfile_lock.sources.last_mut() {
+                        Some(s) => s,
+                        None => return Err(format!("Invalid Gemfile.lock at line {}.", line_number + 1)),
+                    };
+                    match indentation {
+                        2 => {
+                            if let Some(remote) = line.strip_prefix("remote: ") {
+                                source.remote = remote.to_string();
+                            }
+                            if let Some(revision) = line.strip_prefix("revision: ") {
+                                source.revision = Some(revision.to_string());
+                            }
+                        }
+                        4 => match GemfileLockSpec::parse(line) {
+                            Some(s) => source.specs.push(s),
+                            None => {
+                                return Err(format!("Invalid gem spec {} at line {}.", line, line_number + 1))
+                            }
+                        },
+                        6 => {
+                            let dependency_name = line.split(' ').next().unwrap_or(line);
+                            if let Some(spec) = source.specs.last_mut() {
+                                spec.dependencies.push(dependency_name.to_string());
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                "PLATFORMS" => gemfile_lock.platforms.push(line.to_string()),
+                "CHECKSUMS" => {
+                    // Gems without a checksum only have the `name (version)` part.
+                    if let Some((spec, checksum)) = line.rsplit_once(' ') {
+                        if let Some(sha256) = checksum.strip_prefix("sha256=") {
+                            gemfile_lock
+                                .checksums
+                                .insert(spec.to_string(), sha256.to_string());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(gemfile_lock)
+    }
+
+    pub fn get_checksum(&self, spec: &GemfileLockSpec) -> Option<&String> {
+        self.checksums
+            .get(&format!("{} ({})", spec.name, spec.get_full_version()))
+    }
+
+    /// Gets the specs to install, with their source. When a gem is available for the ruby
+    /// platform, the platform-specific variants are not used, since they can't be installed
+    /// on all the architectures. The variants for platforms without a flatpak architecture
+    /// are never used.
+    pub fn get_specs(&self) -> Vec<(&GemfileLockSource, &GemfileLockSpec)> {
+        let mut pure_gems: BTreeSet<&str> = BTreeSet::new();
+        for source in &self.sources {
+            for spec in &source.specs {
+                if spec.platform.is_none() {
+                    pure_gems.insert(&spec.name);
+                }
+            }
+        }
+
+        let mut specs: Vec<(&GemfileLockSource, &GemfileLockSpec)> = vec![];
+        for source in &self.sources {
+            if source.r#type == GemfileLockSourceType::Path {
+                continue;
+            }
+            for spec in &source.specs {
+                if spec.platform.is_some() && pure_gems.contains(spec.name.as_str()) {
+                    continue;
+                }
+                if spec.platform.is_some() && spec.get_flatpak_arch().is_none() {
+                    log::info!("Ignoring {} for platform {}.", spec.name, spec.get_full_version());
+                    continue;
+                }
+                specs.push((source, spec));
+            }
+        }
+        specs
+    }
+
+    /// Gets the gems which are not installed by the generated module: the gems from a local
+    /// path, and the gems only available for platforms without a flatpak architecture.
+    pub fn get_unsupported_gems(&self) -> Vec<String> {
+        let mut unsupported_gems: Vec<String> = self
+            .sources
+            .iter()
+            .filter(|s| s.r#type == GemfileLockSourceType::Path)
+            .flat_map(|s| {
+                s.specs
+                    .iter()
+                    .map(move |spec| format!("{} ({}, path {})", spec.name, spec.get_full_version(), s.remote))
+            })
+            .collect();
+
+        let installed_gems: BTreeSet<&str> = self.get_specs().iter().map(|(_, s)| s.name.as_str()).collect();
+        for source in &self.sources {
+            for spec in &source.specs {
+                if source.r#type != GemfileLockSourceType::Path && !installed_gems.contains(spec.name.as_str())
+                {
+                    unsupported_gems.push(format!("{} ({})", spec.name, spec.get_full_version()));
+                }
+            }
+        }
+        unsupported_gems
+    }
+
+    /// Gets the names of the gems in the order they need to be installed, the dependencies
+    /// of a gem being installed before the gem itself.
+    pub fn get_install_order(&self) -> Vec<String> {
+        let mut dependencies: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for (_, spec) in self.get_specs() {
+            let gem_dependencies = dependencies.entry(&spec.name).or_default();
+            gem_dependencies.extend(spec.dependencies.iter().map(|d| d.as_str()));
+        }
+
+        let mut install_order: Vec<String> = vec![];
+        let mut visited_gems: BTreeSet<&str> = BTreeSet::new();
+        for gem_name in dependencies.keys() {
+            let mut next_gems: Vec<(&str, bool)> = vec![(gem_name, false)];
+            while let Some((gem_name, dependencies_installed)) = next_gems.pop() {
+                if dependencies_installed {
+                    install_order.push(gem_name.to_string());
+                    continue;
+                }
+                if !visited_gems.insert(gem_name) {
+                    continue;
+                }
+                next_gems.push((gem_name, true));
+                for dependency_name in dependencies.get(gem_name).unwrap_or(&vec![]) {
+                    if dependencies.contains_key(dependency_name) {
+                        next_gems.push((dependency_name, false));
+                    }
+                }
+            }
+        }
+        install_order
+    }
+}
+
+/// Gets the sha256 checksum of a gem from the rubygems.org API, for lockfiles generated
+/// before Bundler added the `CHECKSUMS` section.
+pub fn get_rubygems_checksum(spec: &GemfileLockSpec) -> Result<String, String> {
+    let mut version_url = format!(
+        "{}/api/v2/rubygems/{}/versions/{}.json",
+        RUBYGEMS_URL, spec.name, spec.version
+    );
+    if let Some(platform) = &spec.platform {
+        version_url = format!("{}?platform={}", version_url, platform);
+    }
+    let version_metadata = crate::utils::fetch_json(&version_url)?;
+    match version_metadata["sha"].as_str() {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!("No checksum for gem {}.", spec.get_gem_filename())),
+    }
+}
+
+fn get_git_dest(remote: &str) -> String {
+    let repository_name = remote.trim_end_matches('/').trim_end_matches(".git");
+    let repository_name = repository_name.rsplit('/').next().unwrap_or(repository_name);
+    format!("{}/{}", DEFAULT_GIT_DESTINATION, repository_name)
+}
+
+pub fn get_bundler_module(gemfile_lock: &GemfileLock) -> Result<FlatpakModule, String> {
+    let mut bundler_module = FlatpakModule {
+        name: DEFAULT_MODULE_NAME.to_string(),
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        ..Default::default()
+    };
+
+    let mut gem_install_commands: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut git_dests: BTreeSet<String> = BTreeSet::new();
+    for (source, spec) in gemfile_lock.get_specs() {
+        let install_commands = gem_install_commands.entry(spec.name.to_string()).or_default();
+        let gem_path = format!("{}/{}", DEFAULT_DESTINATION, spec.get_gem_filename());
+
+        if source.r#type == GemfileLockSourceType::Git {
+            let revision = match &source.revision {
+                Some(r) => r,
+                None => return Err(format!("No revision for the git source {}.", source.remote)),
+            };
+            let git_dest = get_git_dest(&source.remote);
+            if git_dests.insert(git_dest.to_string()) {
+                bundler_module
+                    .sources
+                    .push(FlatpakSourceItem::Description(FlatpakSource {
+                        r#type: Some(FlatpakSourceType::Git),
+                        url: Some(source.remote.to_string()),
+                        commit: Some(revision.to_string()),
+                        dest: Some(git_dest.to_string()),
+                        ..Default::default()
+                    }));
+            }
+            // The gemspec is not always at the root of the repository.
+            install_commands.push(format!(
+                "gemspec=$(find {} -name {}.gemspec | head -n 1) && (cd $(dirname $gemspec) && gem build $(basename $gemspec) -o $OLDPWD/{})",
+                git_dest, spec.name, gem_path
+            ));
+        } else {
+            let sha256 = match gemfile_lock.get_checksum(spec) {
+                Some(c) => c.to_string(),
+                None => get_rubygems_checksum(spec)?,
+            };
+            bundler_module
+                .sources
+                .push(FlatpakSourceItem::Description(FlatpakSource {
+                    r#type: Some(FlatpakSourceType::File),
+                    url: Some(format!(
+                        "{}/gems/{}",
+                        source.remote.trim_end_matches('/'),
+                        spec.get_gem_filename()
+                    )),
+                    sha256: Some(sha256),
+                    dest: Some(DEFAULT_DESTINATION.to_string()),
+                    only_arches: spec.get_arches(),
+                    ..Default::default()
+                }));
+        }
+
+        let install_command = format!(
+            "gem install --local --ignore-dependencies --no-document --install-dir ${{FLATPAK_DEST}}/lib/ruby/gems --bindir ${{FLATPAK_DEST}}/bin {}",
+            gem_path
+        );
+        if spec.platform.is_some() {
+            // Platform-specific gems are only downloaded on their architecture.
+            install_commands.push(format!("if [ -f {} ]; then {}; fi", gem_path, install_command));
+        } else {
+            install_commands.push(install_command);
+        }
+    }
+
+    for gem_name in gemfile_lock.get_install_order() {
+        if let Some(install_commands) = gem_install_commands.remove(&gem_name) {
+            bundler_module.build_commands.extend(install_commands);
+        }
+    }
+    Ok(bundler_module)
+}
+
+pub struct BundlerImporter {}
+impl Importer for BundlerImporter {
+    fn get_name(&self) -> String {
+        "bundler".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        GemfileLock::file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        let gemfile_lock_content = match fs::read_to_string(manifest_path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read Gemfile.lock at {}: {}", manifest_path, e)),
+        };
+        let gemfile_lock = GemfileLock::parse(&gemfile_lock_content)?;
+        let mut import_result = ImportResult::new(get_bundler_module(&gemfile_lock)?);
+        import_result.unsupported_entries = gemfile_lock.get_unsupported_gems();
+        Ok(import_result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GEMFILE_LOCK: &str = r###"
+GIT
+  remote: https://github.com/louib/my-gem.git
+  revision: 5e2b1ea1bd6c2d1ea42f7e3a5b0d8e3e4c7fda12
+  branch: main
+  specs:
+    my-gem (0.2.0)
+      rake (>= 12)
+
+PATH
+  remote: .
+  specs:
+    my-app (0.1.0)
+      nokogiri (~> 1.13)
+
+GEM
+  remote: https://rubygems.org/
+  specs:
+    mini_portile2 (2.8.0)
+    nokogiri (1.13.0)
+      mini_portile2 (~> 2.8.0)
+      racc (~> 1.4)
+    nokogiri (1.13.0-x86_64-linux)
+      racc (~> 1.4)
+    racc (1.6.0)
+    rake (13.0.6)
+    sqlite3 (1.6.0-aarch64-linux)
+    sqlite3 (1.6.0-x86_64-linux)
+
+PLATFORMS
+  ruby
+  x86_64-linux
+
+DEPENDENCIES
+  my-app!
+  my-gem!
+  rake
+
+CHECKSUMS
+  mini_portile2 (2.8.0) sha256=1e06b286ff19b73cfc9193cb3dd2bd80416f8262443564b25b23baea74a05765
+  nokogiri (1.13.0) sha256=b5c4fa1c1ab1ba4bc12f4fa7e8f2d4bf2bcbf1ce9d6a5f6dd2b4c1b7b0d5c9e61
+  nokogiri (1.13.0-x86_64-linux) sha256=85f15ba5b5fe8a1bbd7cba4ea3b2f6d5d8b8e4c8b8e4f0b7c7c7b7c7c7c7c7c7
+  racc (1.6.0) sha256=2dede3b136eeabf0f0b0f9f3dea7b4f8c1c0c2cf0d4a6a2a5b2a7a0c5f3c9e4a
+  rake (13.0.6) sha256=5ce4bf5037b4196c24ac62834d8db1ce175470391026bd9e557d669beeb19097
+  sqlite3 (1.6.0-aarch64-linux) sha256=d2b8d2b1f1c7e2b7d2e5a1c6b1f8d3a7c2b4e8f1a6d3c9b7e2a5f8c1d4b7e0a3
+  sqlite3 (1.6.0-x86_64-linux) sha256=f1c7e2b7d2e5a1c6b1f8d3a7c2b4e8f1a6d3c9b7e2a5f8c1d4b7e0a3d2b8d2b1
+
+BUNDLED WITH
+   2.5.0
+"###;
+
+    #[test]
+    pub fn test_parse_gemfile_lock() {
+        let gemfile_lock = GemfileLock::parse(GEMFILE_LOCK).unwrap();
+        assert_eq!(gemfile_lock.sources.len(), 3);
+        assert_eq!(gemfile_lock.sources[0].r#type, GemfileLockSourceType::Git);
+        assert_eq!(
+            gemfile_lock.sources[0].revision,
+            Some("5e2b1ea1bd6c2d1ea42f7e3a5b0d8e3e4c7fda12".to_string())
+        );
+        assert_eq!(gemfile_lock.sources[2].specs.len(), 7);
+        assert_eq!(
+            gemfile_lock.sources[2].specs[1].dependencies,
+            vec!["mini_portile2", "racc"]
+        );
+        assert_eq!(
+            gemfile_lock.sources[2].specs[2].platform,
+            Some("x86_64-linux".to_string())
+        );
+        assert_eq!(gemfile_lock.platforms, vec!["ruby", "x86_64-linux"]);
+        assert_eq!(gemfile_lock.checksums.len(), 7);
+    }
+
+    #[test]
+    pub fn test_get_install_order() {
+        let gemfile_lock = GemfileLock::parse(GEMFILE_LOCK).unwrap();
+        // The platform-specific variant of nokogiri and the local gems are not installed.
+        assert_eq!(gemfile_lock.get_specs().len(), 7);
+        assert_eq!(
+            gemfile_lock.get_unsupported_gems(),
+            vec!["my-app (0.1.0, path .)"]
+        );
+
+        let install_order = gemfile_lock.get_install_order();
+        assert_eq!(install_order.len(), 6);
+        let get_position = |gem_name: &str| install_order.iter().position(|g| g == gem_name).unwrap();
+        assert!(get_position("mini_portile2") < get_position("nokogiri"));
+        assert!(get_position("racc") < get_position("nokogiri"));
+        assert!(get_position("rake") < get_position("my-gem"));
+    }
+
+    #[test]
+    pub fn test_get_bundler_module() {
+        let gemfile_lock = GemfileLock::parse(GEMFILE_LOCK).unwrap();
+        let bundler_module = get_bundler_module(&gemfile_lock).unwrap();
+        // One git source and 6 gems.
+        assert_eq!(bundler_module.sources.len(), 7);
+        assert_eq!(bundler_module.build_commands.len(), 8);
+
+        let sqlite3_source = bundler_module
+            .sources
+            .iter()
+            .find_map(|s| match s {
+                FlatpakSourceItem::Description(s)
+                    if s.url
+                        .as_ref()
+                        .unwrap()
+                        .ends_with("sqlite3-1.6.0-aarch64-linux.gem") =>
+                {
+                    Some(s)
+                }
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(sqlite3_source.only_arches, Some(vec!["aarch64".to_string()]));
+    }
+
+    #[test]
+    pub fn test_skip_unsupported_platforms() {
+        let gemfile_lock = GemfileLock::parse(
+            r###"
+GEM
+  remote: https://rubygems.org/
+  specs:
+    ffi (1.16.0-arm64-darwin)
+    ffi (1.16.0-x86_64-linux-musl)
+    ffi (1.16.0-x86_64-linux-gnu)
+    mac-only (1.0.0-x86_64-darwin)
+
+PLATFORMS
+  arm64-darwin
+  x86_64-linux-gnu
+  x86_64-linux-musl
+"###,
+        )
+        .unwrap();
+        let specs = gemfile_lock.get_specs();
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].1.get_arches(), Some(vec!["x86_64".to_string()]));
+        assert_eq!(
+            gemfile_lock.get_unsupported_gems(),
+            vec!["mac-only (1.0.0-x86_64-darwin)"]
+        );
+    }
+}
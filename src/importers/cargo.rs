@@ -1,12 +1,395 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path;
+
 use flatpak_rs::archive::FlatpakArchiveType;
 use flatpak_rs::build_system::FlatpakBuildSystem;
-use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule};
 use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
 
 use serde::{Deserialize, Serialize};
 
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
 pub const CRATES_IO_URL: &str = "https://static.crates.io/crates";
+pub const CRATES_IO_INDEX_URL: &str = "https://index.crates.io";
+pub const CRATES_IO_SOURCES: [&str; 2] = [
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
 pub const DEFAULT_DESTINATION: &str = "cargo/vendor";
+pub const DEFAULT_GIT_DESTINATION: &str = "cargo/git";
+pub const VENDORED_SOURCES_NAME: &str = "vendored-sources";
+pub const DEFAULT_CARGO_HOME: &str = "cargo";
+pub const GIT_CRATE_VENDOR_SCRIPT_PATH: &str = "cargo/vendor-git-crate.py";
+/// The script copying a crate from a checkout of a git repository to the vendor directory, called
+/// with the checkout, the crate name and the vendor directory. The crate is located from the
+/// `[package]` section of the manifests, and the fields inherited from the workspace are replaced
+/// with their values, like `cargo package` does, since the workspace is not vendored.
+/// `tomllib` requires Python 3.11, which is in the Freedesktop SDK since 23.08.
+pub const GIT_CRATE_VENDOR_SCRIPT: [&str; 108] = [
+    "import json, os, re, shutil, sys, tomllib",
+    "checkout_dir, crate_name, vendor_dir = sys.argv[1:4]",
+    "def load(manifest_path):",
+    "    with open(manifest_path, \"rb\") as manifest_file:",
+    "        return tomllib.load(manifest_file)",
+    "manifest_paths = []",
+    "for root, dirs, files in os.walk(checkout_dir):",
+    "    dirs[:] = [d for d in dirs if d != \"target\" and not d.startswith(\".\")]",
+    "    if \"Cargo.toml\" in files:",
+    "        manifest_paths.append(os.path.join(root, \"Cargo.toml\"))",
+    "manifest_paths.sort(key=lambda p: (p.count(os.sep), p))",
+    "crate_dir = None",
+    "for manifest_path in manifest_paths:",
+    "    try:",
+    "        manifest = load(manifest_path)",
+    "    except (OSError, tomllib.TOMLDecodeError):",
+    "        continue",
+    "    if manifest.get(\"package\", {}).get(\"name\") == crate_name:",
+    "        crate_dir = os.path.dirname(manifest_path)",
+    "        break",
+    "if crate_dir is None:",
+    "    sys.exit(\"Could not find crate %s in %s.\" % (crate_name, checkout_dir))",
+    "shutil.copytree(crate_dir, vendor_dir, dirs_exist_ok=True, ignore=lambda d, names: [n for n in names if n == \".git\" or (d == crate_dir and n == \"target\")])",
+    "package = manifest[\"package\"]",
+    "def get_workspace(directory):",
+    "    workspace_manifest_path = os.path.join(directory, \"Cargo.toml\")",
+    "    if os.path.isfile(workspace_manifest_path):",
+    "        return load(workspace_manifest_path).get(\"workspace\")",
+    "    return None",
+    "workspace_dir = crate_dir",
+    "if isinstance(package.get(\"workspace\"), str):",
+    "    workspace_dir = os.path.normpath(os.path.join(crate_dir, package[\"workspace\"]))",
+    "workspace = get_workspace(workspace_dir)",
+    "while workspace is None and os.path.normpath(workspace_dir) != os.path.normpath(checkout_dir):",
+    "    workspace_dir = os.path.dirname(workspace_dir)",
+    "    workspace = get_workspace(workspace_dir)",
+    "workspace = workspace or {}",
+    "def is_inherited(value):",
+    "    return isinstance(value, dict) and value.get(\"workspace\") is True",
+    "for key, value in list(package.items()):",
+    "    if not is_inherited(value):",
+    "        continue",
+    "    value = workspace.get(\"package\", {})[key]",
+    "    if key in (\"readme\", \"license-file\") and isinstance(value, str):",
+    "        shutil.copy(os.path.join(workspace_dir, value), os.path.join(vendor_dir, os.path.basename(value)))",
+    "        value = os.path.basename(value)",
+    "    package[key] = value",
+    "package.pop(\"workspace\", None)",
+    "manifest.pop(\"workspace\", None)",
+    "if is_inherited(manifest.get(\"lints\")):",
+    "    manifest[\"lints\"] = workspace.get(\"lints\", {})",
+    "def normalize_dependencies(dependencies, is_dev):",
+    "    for name, dependency in list(dependencies.items()):",
+    "        if is_inherited(dependency):",
+    "            inherited = workspace.get(\"dependencies\", {})[name]",
+    "            inherited = dict(inherited) if isinstance(inherited, dict) else {\"version\": inherited}",
+    "            if \"path\" in inherited:",
+    "                inherited[\"path\"] = os.path.relpath(os.path.join(workspace_dir, inherited[\"path\"]), crate_dir)",
+    "            features = inherited.get(\"features\", [])",
+    "            features = features + [f for f in dependency.get(\"features\", []) if f not in features]",
+    "            if features:",
+    "                inherited[\"features\"] = features",
+    "            if dependency.get(\"optional\"):",
+    "                inherited[\"optional\"] = True",
+    "            dependency = inherited",
+    "        if isinstance(dependency, dict) and \"path\" in dependency:",
+    "            if \"version\" not in dependency and is_dev:",
+    "                del dependencies[name]",
+    "                continue",
+    "            if \"version\" in dependency:",
+    "                dependency = {k: v for k, v in dependency.items() if k != \"path\"}",
+    "        dependencies[name] = dependency",
+    "dependency_tables = [manifest] + list(manifest.get(\"target\", {}).values())",
+    "for table in dependency_tables:",
+    "    for key in (\"dependencies\", \"dev-dependencies\", \"dev_dependencies\", \"build-dependencies\", \"build_dependencies\"):",
+    "        if isinstance(table.get(key), dict):",
+    "            normalize_dependencies(table[key], key.startswith(\"dev\"))",
+    "def format_key(key):",
+    "    return key if re.fullmatch(r\"[A-Za-z0-9_-]+\", key) else json.dumps(key, ensure_ascii=False)",
+    "def format_value(value):",
+    "    if isinstance(value, bool):",
+    "        return \"true\" if value else \"false\"",
+    "    if isinstance(value, str):",
+    "        return json.dumps(value, ensure_ascii=False)",
+    "    if isinstance(value, list):",
+    "        return \"[\" + \", \".join(format_value(v) for v in value) + \"]\"",
+    "    if isinstance(value, dict):",
+    "        return \"{\" + \", \".join(format_key(k) + \" = \" + format_value(v) for k, v in value.items()) + \"}\"",
+    "    return str(value)",
+    "def is_table_array(value):",
+    "    return isinstance(value, list) and len(value) > 0 and all(isinstance(v, dict) for v in value)",
+    "def write_entries(lines, keys, table):",
+    "    for key, value in table.items():",
+    "        if not isinstance(value, dict) and not is_table_array(value):",
+    "            lines.append(format_key(key) + \" = \" + format_value(value))",
+    "    for key, value in table.items():",
+    "        header = \".\".join(format_key(k) for k in keys + [key])",
+    "        if isinstance(value, dict):",
+    "            lines.append(\"[\" + header + \"]\")",
+    "            write_entries(lines, keys + [key], value)",
+    "        elif is_table_array(value):",
+    "            for item in value:",
+    "                lines.append(\"[[\" + header + \"]]\")",
+    "                write_entries(lines, keys + [key], item)",
+    "lines = []",
+    "write_entries(lines, [], manifest)",
+    "with open(os.path.join(vendor_dir, \"Cargo.toml\"), \"w\") as manifest_file:",
+    "    manifest_file.write(\"\\n\".join(lines) + \"\\n\")",
+];
+pub const RUST_SDK_EXTENSION_PATH: &str = "/usr/lib/sdk/rust-stable/bin";
+/// Placeholder used to enable all the features of a package.
+const ALL_FEATURES: &str = "*";
+
+/// Main structure for a Cargo manifest (`Cargo.toml`).
+/// See https://doc.rust-lang.org/cargo/reference/manifest.html
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(default)]
+pub struct CargoManifest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<CargoManifestPackage>,
+
+    pub bin: Vec<CargoManifestTarget>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<CargoManifestWorkspace>,
+
+    pub features: BTreeMap<String, Vec<String>>,
+
+    pub dependencies: BTreeMap<String, toml::Value>,
+
+    #[serde(rename = "build-dependencies")]
+    pub build_dependencies: BTreeMap<String, toml::Value>,
+
+    /// The platform-specific dependencies, indexed by target triple or `cfg` expression.
+    pub target: BTreeMap<String, CargoManifestPlatform>,
+}
+impl CargoManifest {
+    pub fn file_path_matches(path: &str) -> bool {
+        path.ends_with("Cargo.toml")
+    }
+
+    pub fn parse(manifest_content: &str) -> Result<CargoManifest, String> {
+        match toml::from_str(manifest_content) {
+            Ok(m) => Ok(m),
+            Err(e) => Err(format!("Failed to parse the Cargo.toml manifest: {}.", e)),
+        }
+    }
+
+    pub fn load_from_file(manifest_path: &path::Path) -> Result<CargoManifest, String> {
+        let manifest_content = match fs::read_to_string(manifest_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read Cargo.toml file at {}: {}",
+                    manifest_path.display(),
+                    e
+                ))
+            }
+        };
+        CargoManifest::parse(&manifest_content)
+    }
+
+    /// Features can be declared explicitly, or implicitly with optional dependencies.
+    pub fn has_feature(&self, feature_name: &str) -> bool {
+        if feature_name == "default" || self.features.contains_key(feature_name) {
+            return true;
+        }
+        match self.dependencies.get(feature_name) {
+            Some(toml::Value::Table(dependency)) => dependency
+                .get("optional")
+                .and_then(|o| o.as_bool())
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Gets the names of the binaries of the package, including the binaries
+    /// discovered automatically by cargo. Like with cargo, a binary is not discovered
+    /// when a `[[bin]]` target has the same name or the same path. The binaries requiring
+    /// features that are not enabled are ignored.
+    /// See https://doc.rust-lang.org/cargo/guide/project-layout.html
+    pub fn get_binaries(&self, manifest_dir: &path::Path, features: &[String]) -> Vec<String> {
+        let package = match &self.package {
+            Some(p) => p,
+            None => return vec![],
+        };
+
+        let mut enabled_features: Vec<String> = features.to_vec();
+        if let Some(default_features) = self.features.get("default") {
+            enabled_features.extend(default_features.iter().cloned());
+        }
+
+        let mut discovered_binaries: Vec<(String, path::PathBuf)> = vec![];
+        if manifest_dir.join("src/main.rs").is_file() {
+            discovered_binaries.push((package.name.to_string(), manifest_dir.join("src/main.rs")));
+        }
+        if let Ok(bin_dir_entries) = fs::read_dir(manifest_dir.join("src/bin")) {
+            for bin_dir_entry in bin_dir_entries.flatten() {
+                let bin_path = bin_dir_entry.path();
+                let binary = if bin_path.is_file() && bin_path.extension().unwrap_or_default() == "rs" {
+                    bin_path.file_stem().map(|n| (n, bin_path.to_path_buf()))
+                } else if bin_path.join("main.rs").is_file() {
+                    bin_path.file_name().map(|n| (n, bin_path.join("main.rs")))
+                } else {
+                    None
+                };
+                if let Some((binary_name, binary_path)) = binary {
+                    discovered_binaries.push((binary_name.to_str().unwrap_or("").to_string(), binary_path));
+                }
+            }
+        }
+
+        let mut binaries: Vec<String> = vec![];
+        let mut target_paths: Vec<path::PathBuf> = vec![];
+        for target in &self.bin {
+            if target.name.is_empty() {
+                continue;
+            }
+            // The targets without a path use the path of the binary discovered with their name.
+            let target_path = match &target.path {
+                Some(p) => Some(manifest_dir.join(p)),
+                None => discovered_binaries
+                    .iter()
+                    .find(|(n, _)| n == &target.name)
+                    .map(|(_, p)| p.to_path_buf()),
+            };
+            target_paths.extend(target_path);
+            if let Some(missing_feature) = target
+                .required_features
+                .iter()
+                .find(|f| !enabled_features.contains(f))
+            {
+                log::info!(
+                    "Ignoring binary {} which requires feature {}.",
+                    target.name,
+                    missing_feature
+                );
+                continue;
+            }
+            binaries.push(target.name.to_string());
+        }
+
+        if package.autobins != Some(false) {
+            for (binary_name, binary_path) in discovered_binaries {
+                let is_target =
+                    self.bin.iter().any(|t| t.name == binary_name) || target_paths.contains(&binary_path);
+                if !is_target {
+                    binaries.push(binary_name);
+                }
+            }
+        }
+
+        binaries.sort();
+        binaries.dedup();
+        binaries
+    }
+
+    /// Gets the metadata used to resolve the dependencies of the package. The dev-dependencies
+    /// are not needed to build the package, so they are ignored.
+    pub fn get_metadata(&self, workspace_dependencies: &BTreeMap<String, toml::Value>) -> CargoPackageMetadata {
+        let mut dependencies: Vec<CargoDependency> = vec![];
+        let mut platforms: Vec<(Option<&String>, &CargoManifestPlatform)> = vec![];
+        let default_platform = CargoManifestPlatform {
+            dependencies: self.dependencies.clone(),
+            build_dependencies: self.build_dependencies.clone(),
+        };
+        platforms.push((None, &default_platform));
+        for (target, platform) in &self.target {
+            platforms.push((Some(target), platform));
+        }
+        for (target, platform) in platforms {
+            for (kind, platform_dependencies) in [
+                ("normal", &platform.dependencies),
+                ("build", &platform.build_dependencies),
+            ] {
+                for (name, value) in platform_dependencies {
+                    let mut dependency = CargoDependency::from_manifest(name, value, workspace_dependencies);
+                    dependency.kind = Some(kind.to_string());
+                    dependency.target = target.cloned();
+                    dependencies.push(dependency);
+                }
+            }
+        }
+        CargoPackageMetadata {
+            features: self.features.clone(),
+            dependencies,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct CargoManifestPlatform {
+    pub dependencies: BTreeMap<String, toml::Value>,
+
+    pub build_dependencies: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(default)]
+pub struct CargoManifestPackage {
+    pub name: String,
+
+    /// The version can be inherited from the workspace, so it is not always a string.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<toml::Value>,
+
+    /// The path of the workspace root, when it cannot be inferred.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autobins: Option<bool>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct CargoManifestTarget {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    pub required_features: Vec<String>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct CargoManifestWorkspace {
+    /// The members of the workspace, which can be glob patterns.
+    pub members: Vec<String>,
+
+    pub exclude: Vec<String>,
+
+    /// The dependencies inherited by the members with `workspace = true`.
+    pub dependencies: BTreeMap<String, toml::Value>,
+}
 
 #[derive(Clone)]
 #[derive(Deserialize)]
@@ -34,68 +417,1469 @@ pub struct CargoLockPackage {
 }
 impl CargoLockPackage {
     pub fn get_sources(&self) -> Vec<FlatpakSource> {
-        if self.source.len() == 0 {
+        if self.source.is_empty() {
             log::debug!("Cargo package {} has no source. ignoring.", &self.name);
             return vec![];
         }
-        if self.checksum.len() == 0 {
+
+        if self.source.starts_with("git+") {
+            let git_source = match CargoGitSource::parse(&self.source) {
+                Ok(s) => s,
+                Err(e) => {
+                    log::warn!("Invalid git source for Cargo package {}: {}", &self.name, e);
+                    return vec![];
+                }
+            };
+            return vec![self.get_git_copy_source(&git_source)];
+        }
+
+        if self.checksum.is_empty() {
             // FIXME should we maybe raise an error here?
             log::debug!("Cargo package {} has no checksum. ignoring.", &self.name);
             return vec![];
         }
 
-        if self.source.starts_with("git+") {
-            // FIXME ignoring for now.
-            return vec![];
+        let package_source = FlatpakSource {
+            r#type: Some(FlatpakSourceType::Archive),
+            archive_type: Some(FlatpakArchiveType::TarGzip),
+            url: Some(format!(
+                "{}/{}/{}-{}.crate",
+                CRATES_IO_URL, self.name, self.name, self.version
+            )),
+            sha256: Some(self.checksum.clone()),
+            dest: Some(self.get_vendor_dir()),
+            ..Default::default()
+        };
+
+        vec![package_source]
+    }
+
+    /// Gets the content of the `.cargo-checksum.json` file cargo expects in the vendored
+    /// crate directory. The checksums of the individual files are not required, and
+    /// the crates from git repositories do not have a package checksum.
+    pub fn get_checksum_file_content(&self) -> Option<String> {
+        if self.source.is_empty() {
+            return None;
         }
+        let package_checksum = if self.source.starts_with("git+") {
+            serde_json::Value::Null
+        } else if self.checksum.is_empty() {
+            return None;
+        } else {
+            serde_json::Value::String(self.checksum.to_string())
+        };
+        Some(serde_json::json!({"files": {}, "package": package_checksum}).to_string())
+    }
 
-        let mut package_source = FlatpakSource::default();
-        package_source.r#type = Some(FlatpakSourceType::Archive);
-        package_source.archive_type = Some(FlatpakArchiveType::TarGzip);
-        package_source.url = Some(format!(
-            "{}/{}/{}-{}.crate",
-            CRATES_IO_URL, self.name, self.name, self.version
-        ));
-        package_source.sha256 = Some(self.checksum.clone());
-        package_source.dest = Some(format!("{}/{}-{}", DEFAULT_DESTINATION, self.name, self.version));
+    pub fn is_from_crates_io(&self) -> bool {
+        CRATES_IO_SOURCES.contains(&self.source.as_str()) && !self.checksum.is_empty()
+    }
 
-        vec![package_source]
+    pub fn get_vendor_dir(&self) -> String {
+        format!("{}/{}-{}", DEFAULT_DESTINATION, self.name, self.version)
+    }
+
+    /// The crates from a git repository can be anywhere in the repository, so they are located
+    /// during the build, once the repository is checked out, and copied to the vendor directory.
+    fn get_git_copy_source(&self, git_source: &CargoGitSource) -> FlatpakSource {
+        FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(vec![format!(
+                "python3 {} {} {} {}",
+                GIT_CRATE_VENDOR_SCRIPT_PATH,
+                git_source.get_dest(),
+                self.name,
+                self.get_vendor_dir()
+            )]),
+            ..Default::default()
+        }
     }
 }
 
-pub fn get_cargo_module(cargo_lock_manifest: &str) -> FlatpakModule {
-    let mut cargo_module = FlatpakModule::default();
-    cargo_module.name = "cargo_sources".to_string();
-    cargo_module.buildsystem = Some(FlatpakBuildSystem::Simple);
-    cargo_module
-        .build_commands
-        .push("cargo --offline fetch --manifest-path Cargo.toml --verbose".to_string());
-    cargo_module
-        .build_commands
-        .push("cargo --offline build --release --verbose".to_string());
-    cargo_module
-        .build_commands
-        .push("install -Dm755 ./target/debug/".to_string());
-    let mut current_cargo_project_source: FlatpakSource = FlatpakSource::default();
-    current_cargo_project_source.r#type = Some(FlatpakSourceType::Git);
-    current_cargo_project_source.path = Some("./".to_string());
+/// A git source from a Cargo.lock file, for example
+/// `git+https://github.com/louib/flatpak-rs?branch=main#2d9cba64`.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub struct CargoGitSource {
+    pub url: String,
+    pub branch: Option<String>,
+    pub tag: Option<String>,
+    pub rev: Option<String>,
+    pub commit: String,
+}
+impl CargoGitSource {
+    pub fn parse(source: &str) -> Result<CargoGitSource, String> {
+        let source = match source.strip_prefix("git+") {
+            Some(s) => s,
+            None => return Err(format!("{} is not a git source.", source)),
+        };
+        let (source, commit) = match source.split_once('#') {
+            Some((s, c)) => (s, c),
+            None => return Err(format!("Git source {} is not pinned to a commit.", source)),
+        };
+        let (url, query) = match source.split_once('?') {
+            Some((u, q)) => (u, q),
+            None => (source, ""),
+        };
+
+        let mut git_source = CargoGitSource {
+            url: url.to_string(),
+            commit: commit.to_string(),
+            ..Default::default()
+        };
+        for query_param in query.split('&') {
+            match query_param.split_once('=') {
+                Some(("branch", b)) => git_source.branch = Some(b.to_string()),
+                Some(("tag", t)) => git_source.tag = Some(t.to_string()),
+                Some(("rev", r)) => git_source.rev = Some(r.to_string()),
+                _ => {}
+            }
+        }
+        Ok(git_source)
+    }
+
+    /// Gets the identifier of the source, as expected in the source replacement
+    /// sections of the Cargo config. This is the source without the commit.
+    pub fn get_source_id(&self) -> String {
+        let mut source_id = format!("git+{}", self.url);
+        if let Some(branch) = &self.branch {
+            source_id.push_str(&format!("?branch={}", branch));
+        } else if let Some(tag) = &self.tag {
+            source_id.push_str(&format!("?tag={}", tag));
+        } else if let Some(rev) = &self.rev {
+            source_id.push_str(&format!("?rev={}", rev));
+        }
+        source_id
+    }
+
+    /// Gets the directory in which the repository is checked out.
+    pub fn get_repo_name(&self) -> String {
+        self.url
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .rsplit('/')
+            .next()
+            .unwrap_or("")
+            .to_string()
+    }
+
+    pub fn get_dest(&self) -> String {
+        let short_commit: String = self.commit.chars().take(7).collect();
+        format!(
+            "{}/{}-{}",
+            DEFAULT_GIT_DESTINATION,
+            self.get_repo_name(),
+            short_commit
+        )
+    }
+
+    pub fn get_flatpak_source(&self) -> FlatpakSource {
+        FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            url: Some(self.url.to_string()),
+            commit: Some(self.commit.to_string()),
+            dest: Some(self.get_dest()),
+            ..Default::default()
+        }
+    }
+}
+
+impl CargoLock {
+    pub fn parse(cargo_lock_manifest: &str) -> Result<CargoLock, String> {
+        match toml::from_str(cargo_lock_manifest) {
+            Ok(c) => Ok(c),
+            Err(e) => Err(format!("Failed to parse the Cargo.lock manifest: {}.", e)),
+        }
+    }
+
+    /// Gets the git repositories used by the packages, without duplicates.
+    pub fn get_git_sources(&self) -> Vec<CargoGitSource> {
+        let mut git_sources: Vec<CargoGitSource> = vec![];
+        for cargo_package in &self.package {
+            if !cargo_package.source.starts_with("git+") {
+                continue;
+            }
+            let git_source = match CargoGitSource::parse(&cargo_package.source) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            if !git_sources.contains(&git_source) {
+                git_sources.push(git_source);
+            }
+        }
+        git_sources
+    }
+}
+
+/// A dependency of a package, as found in the registry index or in a Cargo.toml manifest.
+/// See https://doc.rust-lang.org/cargo/reference/registry-index.html#json-schema
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(default)]
+pub struct CargoDependency {
+    /// The name of the dependency in the package, which differs from the name of
+    /// the crate when the dependency is renamed.
+    pub name: String,
+
+    pub req: String,
+
+    pub features: Vec<String>,
+
+    pub optional: bool,
+
+    pub default_features: bool,
+
+    /// The target triple or `cfg` expression of a platform-specific dependency.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+
+    /// Either `normal`, `build` or `dev`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+
+    /// The name of the crate, when the dependency is renamed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+}
+impl CargoDependency {
+    pub fn from_manifest(
+        name: &str,
+        value: &toml::Value,
+        workspace_dependencies: &BTreeMap<String, toml::Value>,
+    ) -> CargoDependency {
+        let mut dependency = CargoDependency {
+            name: name.to_string(),
+            default_features: true,
+            ..Default::default()
+        };
+        let mut tables: Vec<&toml::value::Table> = vec![];
+        match value {
+            toml::Value::String(req) => dependency.req = req.to_string(),
+            toml::Value::Table(table) => {
+                // The inherited dependencies can only add features and make the dependency optional.
+                if table.get("workspace").and_then(|w| w.as_bool()) == Some(true) {
+                    match workspace_dependencies.get(name) {
+                        Some(toml::Value::String(req)) => dependency.req = req.to_string(),
+                        Some(toml::Value::Table(workspace_table)) => tables.push(workspace_table),
+                        _ => log::warn!("Dependency {} is not defined in the workspace.", name),
+                    }
+                }
+                tables.push(table);
+            }
+            _ => {}
+        }
+        for table in tables {
+            if let Some(req) = table.get("version").and_then(|v| v.as_str()) {
+                dependency.req = req.to_string();
+            }
+            if let Some(package) = table.get("package").and_then(|p| p.as_str()) {
+                dependency.package = Some(package.to_string());
+            }
+            if let Some(optional) = table.get("optional").and_then(|o| o.as_bool()) {
+                dependency.optional = optional;
+            }
+            let default_features = table
+                .get("default-features")
+                .or_else(|| table.get("default_features"))
+                .and_then(|d| d.as_bool());
+            if let Some(default_features) = default_features {
+                dependency.default_features = default_features;
+            }
+            if let Some(toml::Value::Array(features)) = table.get("features") {
+                for feature in features.iter().filter_map(|f| f.as_str()) {
+                    dependency.features.push(feature.to_string());
+                }
+            }
+        }
+        dependency
+    }
+
+    pub fn get_crate_name(&self) -> &str {
+        match &self.package {
+            Some(p) => p,
+            None => &self.name,
+        }
+    }
+
+    pub fn is_dev_dependency(&self) -> bool {
+        self.kind.as_deref() == Some("dev")
+    }
+}
+
+/// A version of a crate in the registry index.
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(default)]
+pub struct CargoIndexEntry {
+    pub name: String,
+
+    pub vers: String,
+
+    pub deps: Vec<CargoDependency>,
+
+    pub features: BTreeMap<String, Vec<String>>,
+
+    /// The features using the newer syntax (`dep:` and `?/`) are stored separately.
+    pub features2: BTreeMap<String, Vec<String>>,
+}
+impl CargoIndexEntry {
+    /// Gets the path of the crate's file in the sparse index.
+    /// See https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files
+    pub fn get_index_path(crate_name: &str) -> String {
+        let crate_name = crate_name.to_lowercase();
+        match crate_name.len() {
+            1 => format!("1/{}", crate_name),
+            2 => format!("2/{}", crate_name),
+            3 => format!("3/{}/{}", &crate_name[0..1], crate_name),
+            _ => format!("{}/{}/{}", &crate_name[0..2], &crate_name[2..4], crate_name),
+        }
+    }
+
+    /// Gets the entry of a crate version from the crates.io index. The index files are cached,
+    /// and only fetched again when the version is not found in the cached file.
+    pub fn fetch(crate_name: &str, version: &str) -> Result<CargoIndexEntry, String> {
+        let index_path = CargoIndexEntry::get_index_path(crate_name);
+        let cache_path = get_index_cache_dir().map(|d| d.join(&index_path));
+        if let Some(index_content) = cache_path.as_ref().and_then(|p| fs::read_to_string(p).ok()) {
+            if let Some(index_entry) = CargoIndexEntry::find_version(&index_content, crate_name, version)? {
+                return Ok(index_entry);
+            }
+        }
+
+        let index_url = format!("{}/{}", CRATES_IO_INDEX_URL, index_path);
+        let index_content = crate::utils::fetch(&index_url)?;
+        if let Some(cache_path) = &cache_path {
+            let is_cached = cache_path.parent().is_some_and(|d| fs::create_dir_all(d).is_ok())
+                && fs::write(cache_path, &index_content).is_ok();
+            if !is_cached {
+                log::debug!("Could not cache the index file at {}.", cache_path.display());
+            }
+        }
+        match CargoIndexEntry::find_version(&index_content, crate_name, version)? {
+            Some(index_entry) => Ok(index_entry),
+            None => Err(format!(
+                "Could not find version {} of crate {} in the index.",
+                version, crate_name
+            )),
+        }
+    }
+
+    fn find_version(
+        index_content: &str,
+        crate_name: &str,
+        version: &str,
+    ) -> Result<Option<CargoIndexEntry>, String> {
+        for line in index_content.lines() {
+            let index_entry: CargoIndexEntry = match serde_json::from_str(line) {
+                Ok(e) => e,
+                Err(e) => return Err(format!("Invalid index entry for crate {}: {}", crate_name, e)),
+            };
+            if index_entry.vers == version {
+                return Ok(Some(index_entry));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn get_metadata(&self) -> CargoPackageMetadata {
+        let mut features = self.features.clone();
+        features.extend(self.features2.clone());
+        CargoPackageMetadata {
+            features,
+            dependencies: self
+                .deps
+                .iter()
+                .filter(|d| !d.is_dev_dependency())
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Gets the directory in which the files of the crates.io index are cached.
+fn get_index_cache_dir() -> Option<path::PathBuf> {
+    let cache_dir = match env::var("XDG_CACHE_HOME") {
+        Ok(d) if !d.is_empty() => path::PathBuf::from(d),
+        _ => path::PathBuf::from(env::var("HOME").ok()?).join(".cache"),
+    };
+    Some(cache_dir.join("fpm").join("cargo-index"))
+}
+
+/// Gets the directory in which cargo stores its registry and git checkouts on the host.
+fn get_cargo_home() -> Option<path::PathBuf> {
+    match env::var("CARGO_HOME") {
+        Ok(h) => Some(path::PathBuf::from(h)),
+        Err(_) => env::var("HOME").ok().map(|h| path::Path::new(&h).join(".cargo")),
+    }
+}
+
+/// Gets the directories in which the sources of the crates.io packages may already have been
+/// extracted, by `cargo vendor` in the workspace or by cargo in its registry.
+fn get_local_crate_dirs(workspace_root_dir: &path::Path) -> Vec<path::PathBuf> {
+    let mut local_crate_dirs: Vec<path::PathBuf> = vec![workspace_root_dir.join("vendor")];
+    if let Some(Ok(registry_dirs)) = get_cargo_home().map(|h| fs::read_dir(h.join("registry").join("src"))) {
+        let mut registry_dirs: Vec<path::PathBuf> = registry_dirs.flatten().map(|d| d.path()).collect();
+        registry_dirs.sort();
+        local_crate_dirs.extend(registry_dirs);
+    }
+    local_crate_dirs
+}
+
+/// Gets the metadata of a crates.io package from its manifest, when its sources were already
+/// extracted in one of the local crate directories.
+fn get_local_metadata(
+    cargo_package: &CargoLockPackage,
+    local_crate_dirs: &[path::PathBuf],
+) -> Option<CargoPackageMetadata> {
+    for local_crate_dir in local_crate_dirs {
+        for crate_dir_name in [
+            format!("{}-{}", cargo_package.name, cargo_package.version),
+            cargo_package.name.to_string(),
+        ] {
+            let manifest_path = local_crate_dir.join(crate_dir_name).join("Cargo.toml");
+            if !manifest_path.is_file() {
+                continue;
+            }
+            let manifest = match CargoManifest::load_from_file(&manifest_path) {
+                Ok(m) => m,
+                Err(e) => {
+                    log::debug!("{}", e);
+                    continue;
+                }
+            };
+            if manifest.package.as_ref().is_some_and(|p| {
+                p.version.as_ref().and_then(|v| v.as_str()) == Some(cargo_package.version.as_str())
+            }) {
+                return Some(manifest.get_metadata(&BTreeMap::new()));
+            }
+        }
+    }
+    None
+}
+
+/// The features and dependencies of a package, used to resolve the dependency graph.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct CargoPackageMetadata {
+    pub features: BTreeMap<String, Vec<String>>,
+    pub dependencies: Vec<CargoDependency>,
+}
+impl CargoPackageMetadata {
+    /// Gets a manifest declaring the same features and dependencies as the package, but without
+    /// any code. The packages which are not built for the target are replaced by such stubs in
+    /// the vendor directory, since cargo still needs them to resolve the dependency graph.
+    pub fn get_stub_manifest(&self, name: &str, version: &str) -> String {
+        let mut package = toml::map::Map::new();
+        package.insert("name".to_string(), toml::Value::String(name.to_string()));
+        package.insert("version".to_string(), toml::Value::String(version.to_string()));
+
+        let mut manifest = toml::map::Map::new();
+        manifest.insert("package".to_string(), toml::Value::Table(package));
+        let features = self
+            .features
+            .iter()
+            .map(|(f, values)| {
+                let values = values
+                    .iter()
+                    .map(|v| toml::Value::String(v.to_string()))
+                    .collect();
+                (f.to_string(), toml::Value::Array(values))
+            })
+            .collect();
+        manifest.insert("features".to_string(), toml::Value::Table(features));
+
+        for dependency in &self.dependencies {
+            let mut dependency_table = toml::map::Map::new();
+            dependency_table.insert(
+                "version".to_string(),
+                toml::Value::String(dependency.req.to_string()),
+            );
+            if let Some(package) = &dependency.package {
+                dependency_table.insert("package".to_string(), toml::Value::String(package.to_string()));
+            }
+            if dependency.optional {
+                dependency_table.insert("optional".to_string(), toml::Value::Boolean(true));
+            }
+            if !dependency.default_features {
+                dependency_table.insert("default-features".to_string(), toml::Value::Boolean(false));
+            }
+            if !dependency.features.is_empty() {
+                let features = dependency
+                    .features
+                    .iter()
+                    .map(|f| toml::Value::String(f.to_string()))
+                    .collect();
+                dependency_table.insert("features".to_string(), toml::Value::Array(features));
+            }
+
+            let mut parent_table = &mut manifest;
+            if let Some(target) = &dependency.target {
+                let targets = parent_table
+                    .entry("target".to_string())
+                    .or_insert_with(|| toml::Value::Table(toml::map::Map::new()));
+                parent_table = match targets.as_table_mut() {
+                    Some(t) => t,
+                    None => continue,
+                };
+                let platform = parent_table
+                    .entry(target.to_string())
+                    .or_insert_with(|| toml::Value::Table(toml::map::Map::new()));
+                parent_table = match platform.as_table_mut() {
+                    Some(t) => t,
+                    None => continue,
+                };
+            }
+            let dependencies_key = match dependency.kind.as_deref() {
+                Some("build") => "build-dependencies",
+                _ => "dependencies",
+            };
+            if let Some(dependencies) = parent_table
+                .entry(dependencies_key.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::map::Map::new()))
+                .as_table_mut()
+            {
+                dependencies.insert(dependency.name.to_string(), toml::Value::Table(dependency_table));
+            }
+        }
+        toml::to_string(&toml::Value::Table(manifest)).unwrap_or_default()
+    }
+}
+
+/// A target platform, used to evaluate the platform-specific dependencies.
+/// See https://doc.rust-lang.org/reference/conditional-compilation.html
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct CargoTarget {
+    pub triple: String,
+    pub arch: String,
+    pub vendor: String,
+    pub os: String,
+    pub env: String,
+}
+impl CargoTarget {
+    /// Parses a target triple, like `x86_64-unknown-linux-gnu` or `aarch64-linux-android`.
+    pub fn parse(triple: &str) -> Result<CargoTarget, String> {
+        let parts: Vec<&str> = triple.split('-').collect();
+        let (arch, vendor, os, env) = match parts.len() {
+            2 => (parts[0], "unknown", parts[1], ""),
+            3 if parts[1] == "linux" => (parts[0], "unknown", parts[1], parts[2]),
+            3 => (parts[0], parts[1], parts[2], ""),
+            4 => (parts[0], parts[1], parts[2], parts[3]),
+            _ => return Err(format!("Invalid target triple {}.", triple)),
+        };
+
+        let arch = match arch {
+            "i386" | "i586" | "i686" => "x86",
+            "powerpc64le" => "powerpc64",
+            "mipsel" => "mips",
+            "mips64el" => "mips64",
+            a if a.starts_with("arm") || a.starts_with("thumb") => "arm",
+            a if a.starts_with("riscv64") => "riscv64",
+            a if a.starts_with("riscv32") => "riscv32",
+            a => a,
+        };
+        let (os, env) = match (os, env) {
+            ("darwin", e) => ("macos", e),
+            ("linux", e) if e.starts_with("android") => ("android", ""),
+            (o, e) if e.starts_with("gnu") => (o, "gnu"),
+            (o, e) if e.starts_with("musl") => (o, "musl"),
+            (o, e) => (o, e),
+        };
+        Ok(CargoTarget {
+            triple: triple.to_string(),
+            arch: arch.to_string(),
+            vendor: vendor.to_string(),
+            os: os.to_string(),
+            env: env.to_string(),
+        })
+    }
+
+    pub fn get_families(&self) -> Vec<&str> {
+        match self.os.as_str() {
+            "windows" => vec!["windows"],
+            "linux" | "android" | "macos" | "ios" | "freebsd" | "netbsd" | "openbsd" | "dragonfly"
+            | "solaris" | "illumos" | "haiku" => vec!["unix"],
+            _ if self.arch.starts_with("wasm") => vec!["wasm"],
+            _ => vec![],
+        }
+    }
+
+    pub fn get_pointer_width(&self) -> &str {
+        match self.arch.as_str() {
+            "x86_64" | "aarch64" | "powerpc64" | "riscv64" | "s390x" | "mips64" | "sparc64" | "loongarch64"
+            | "wasm64" => "64",
+            _ => "32",
+        }
+    }
+
+    pub fn get_endian(&self) -> &str {
+        let is_little_endian = self.triple.starts_with("powerpc64le") || self.triple.starts_with("mipsel");
+        match self.arch.as_str() {
+            "s390x" | "sparc64" | "powerpc" | "powerpc64" | "mips" | "mips64" if !is_little_endian => "big",
+            _ => "little",
+        }
+    }
+
+    /// Whether a platform-specific dependency is used on this target. The platform can
+    /// be a target triple or a `cfg` expression. The CPU features (`target_feature` and
+    /// `target_has_atomic`) are assumed to be available so that no dependency is missed,
+    /// and the other unknown options are assumed not to be set.
+    /// See https://doc.rust-lang.org/cargo/reference/specifying-dependencies.html#platform-specific-dependencies
+    pub fn matches(&self, platform: &str) -> Result<bool, String> {
+        let platform = platform.trim();
+        let cfg_expression = match platform.strip_prefix("cfg(").and_then(|p| p.strip_suffix(')')) {
+            Some(e) => e,
+            None => return Ok(platform == self.triple),
+        };
+        let tokens = tokenize_cfg_expression(cfg_expression)?;
+        let mut position = 0;
+        let result = self.evaluate_cfg_predicate(&tokens, &mut position)?;
+        if position != tokens.len() {
+            return Err(format!("Invalid cfg expression {}.", platform));
+        }
+        Ok(result)
+    }
+
+    fn evaluate_cfg_predicate(&self, tokens: &[String], position: &mut usize) -> Result<bool, String> {
+        let name = match tokens.get(*position) {
+            Some(n) => n.to_string(),
+            None => return Err("Unexpected end of cfg expression.".to_string()),
+        };
+        *position += 1;
+
+        match tokens.get(*position).map(|t| t.as_str()) {
+            Some("(") => {
+                *position += 1;
+                let mut results: Vec<bool> = vec![];
+                while tokens.get(*position).map(|t| t.as_str()) != Some(")") {
+                    results.push(self.evaluate_cfg_predicate(tokens, position)?);
+                    match tokens.get(*position).map(|t| t.as_str()) {
+                        Some(",") => *position += 1,
+                        Some(")") => {}
+                        _ => return Err(format!("Invalid arguments for {} in cfg expression.", name)),
+                    }
+                }
+                *position += 1;
+                match name.as_str() {
+                    "all" => Ok(results.iter().all(|r| *r)),
+                    "any" => Ok(results.iter().any(|r| *r)),
+                    "not" if results.len() == 1 => Ok(!results[0]),
+                    _ => Err(format!("Invalid operator {} in cfg expression.", name)),
+                }
+            }
+            Some("=") => {
+                *position += 1;
+                let value = match tokens.get(*position) {
+                    Some(v) if v.starts_with('"') => v.trim_matches('"').to_string(),
+                    _ => return Err(format!("Missing value for {} in cfg expression.", name)),
+                };
+                *position += 1;
+                Ok(match name.as_str() {
+                    "target_arch" => self.arch == value,
+                    "target_os" => self.os == value,
+                    "target_env" => self.env == value,
+                    "target_vendor" => self.vendor == value,
+                    "target_family" => self.get_families().contains(&value.as_str()),
+                    "target_pointer_width" => self.get_pointer_width() == value,
+                    "target_endian" => self.get_endian() == value,
+                    // We cannot know which CPU features will be enabled, so we assume
+                    // they are all available to avoid missing any dependency.
+                    "target_feature" | "target_has_atomic" => true,
+                    "panic" => value == "unwind",
+                    _ => {
+                        log::warn!(
+                            "Unknown cfg option {} = \"{}\", assuming it is not set.",
+                            name,
+                            value
+                        );
+                        false
+                    }
+                })
+            }
+            _ => Ok(match name.as_str() {
+                "unix" | "windows" => self.get_families().contains(&name.as_str()),
+                _ => {
+                    log::warn!("Unknown cfg option {}, assuming it is not set.", name);
+                    false
+                }
+            }),
+        }
+    }
+}
+
+fn tokenize_cfg_expression(cfg_expression: &str) -> Result<Vec<String>, String> {
+    let mut tokens: Vec<String> = vec![];
+    let mut chars = cfg_expression.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' | ')' | ',' | '=' => tokens.push(c.to_string()),
+            '"' => {
+                let mut value = "\"".to_string();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => {
+                            return Err(format!(
+                                "Unterminated string in cfg expression {}.",
+                                cfg_expression
+                            ))
+                        }
+                    }
+                }
+                value.push('"');
+                tokens.push(value);
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut identifier = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if !next.is_alphanumeric() && *next != '_' {
+                        break;
+                    }
+                    identifier.push(*next);
+                    chars.next();
+                }
+                tokens.push(identifier);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+impl CargoLock {
+    /// Finds the package referenced by a `dependencies` entry of the lock file, which
+    /// can be `name`, `name version` or `name version (source)`.
+    pub fn find_package(&self, dependency: &str) -> Option<usize> {
+        let mut parts = dependency.splitn(3, ' ');
+        let name = parts.next().unwrap_or("");
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|s| s.trim_start_matches('(').trim_end_matches(')'));
+        self.package.iter().position(|p| {
+            p.name == name && version.is_none_or(|v| p.version == v) && source.is_none_or(|s| p.source == s)
+        })
+    }
+
+    /// Finds the packages built for the given targets, starting from the root packages with
+    /// the given features. The packages without metadata, like the ones from git repositories,
+    /// are assumed to use all of their dependencies with all their features.
+    pub fn get_target_packages(
+        &self,
+        metadata: &BTreeMap<usize, CargoPackageMetadata>,
+        root_packages: &[usize],
+        features: &[String],
+        targets: &[CargoTarget],
+    ) -> Result<BTreeSet<usize>, String> {
+        let mut enabled_features: BTreeMap<usize, BTreeSet<String>> = BTreeMap::new();
+        for root_package in root_packages {
+            let mut root_features: BTreeSet<String> = features.iter().cloned().collect();
+            root_features.insert("default".to_string());
+            enabled_features.insert(*root_package, root_features);
+        }
+
+        // The features of a package can enable features and optional dependencies of its
+        // dependencies, so we iterate until no new feature is enabled.
+        let mut has_changed = true;
+        while has_changed {
+            has_changed = false;
+            let packages: Vec<(usize, BTreeSet<String>)> =
+                enabled_features.iter().map(|(p, f)| (*p, f.clone())).collect();
+            for (package, features) in packages {
+                for (dependency_package, dependency_features) in
+                    self.get_activated_dependencies(package, &features, metadata.get(&package), targets)?
+                {
+                    let enabled = enabled_features.entry(dependency_package).or_insert_with(|| {
+                        has_changed = true;
+                        BTreeSet::new()
+                    });
+                    for feature in dependency_features {
+                        has_changed |= enabled.insert(feature);
+                    }
+                }
+            }
+        }
+        Ok(enabled_features.into_keys().collect())
+    }
+
+    fn get_activated_dependencies(
+        &self,
+        package: usize,
+        features: &BTreeSet<String>,
+        metadata: Option<&CargoPackageMetadata>,
+        targets: &[CargoTarget],
+    ) -> Result<Vec<(usize, Vec<String>)>, String> {
+        let lock_package = &self.package[package];
+        let metadata = match metadata {
+            Some(m) => m,
+            None => {
+                return Ok(lock_package
+                    .dependencies
+                    .iter()
+                    .filter_map(|d| self.find_package(d))
+                    .map(|p| (p, vec![ALL_FEATURES.to_string()]))
+                    .collect())
+            }
+        };
+
+        // Expanding the enabled features, and collecting the optional dependencies
+        // and the dependency features they enable.
+        let mut enabled_dependencies: BTreeSet<String> = BTreeSet::new();
+        let mut dependency_features: Vec<(String, String)> = vec![];
+        let mut expanded_features: BTreeSet<String> = BTreeSet::new();
+        let mut pending_features: Vec<String> = features.iter().cloned().collect();
+        if features.contains(ALL_FEATURES) {
+            pending_features.extend(metadata.features.keys().cloned());
+            enabled_dependencies.extend(metadata.dependencies.iter().map(|d| d.name.to_string()));
+        }
+        while let Some(feature) = pending_features.pop() {
+            if !expanded_features.insert(feature.to_string()) {
+                continue;
+            }
+            let feature_values = match metadata.features.get(&feature) {
+                Some(v) => v,
+                None => {
+                    // Optional dependencies define implicit features with the same name.
+                    if metadata
+                        .dependencies
+                        .iter()
+                        .any(|d| d.optional && d.name == feature)
+                    {
+                        enabled_dependencies.insert(feature.to_string());
+                    }
+                    continue;
+                }
+            };
+            for feature_value in feature_values {
+                if let Some(dependency_name) = feature_value.strip_prefix("dep:") {
+                    enabled_dependencies.insert(dependency_name.to_string());
+                } else if let Some((dependency_name, dependency_feature)) = feature_value.split_once('/') {
+                    match dependency_name.strip_suffix('?') {
+                        Some(dependency_name) => dependency_features
+                            .push((dependency_name.to_string(), dependency_feature.to_string())),
+                        None => {
+                            enabled_dependencies.insert(dependency_name.to_string());
+                            dependency_features
+                                .push((dependency_name.to_string(), dependency_feature.to_string()));
+                        }
+                    }
+                } else {
+                    pending_features.push(feature_value.to_string());
+                }
+            }
+        }
+
+        let mut activated_dependencies: Vec<(usize, Vec<String>)> = vec![];
+        for dependency in &metadata.dependencies {
+            if dependency.is_dev_dependency() {
+                continue;
+            }
+            if dependency.optional && !enabled_dependencies.contains(&dependency.name) {
+                continue;
+            }
+            if let Some(platform) = &dependency.target {
+                let mut is_used = false;
+                for target in targets {
+                    is_used |= target.matches(platform)?;
+                }
+                if !is_used {
+                    continue;
+                }
+            }
+
+            let mut features = dependency.features.clone();
+            if dependency.default_features {
+                features.push("default".to_string());
+            }
+            for (dependency_name, dependency_feature) in &dependency_features {
+                if dependency_name == &dependency.name {
+                    features.push(dependency_feature.to_string());
+                }
+            }
+            // The lock file tells us which version of the crate was selected.
+            let dependency_packages: Vec<usize> = lock_package
+                .dependencies
+                .iter()
+                .filter(|d| d.split(' ').next() == Some(dependency.get_crate_name()))
+                .filter_map(|d| self.find_package(d))
+                .collect();
+            if dependency_packages.is_empty() {
+                log::debug!(
+                    "Dependency {} of {} is not in the lock file.",
+                    dependency.name,
+                    lock_package.name
+                );
+            }
+            for dependency_package in dependency_packages {
+                activated_dependencies.push((dependency_package, features.clone()));
+            }
+        }
+        Ok(activated_dependencies)
+    }
+}
+
+/// Gets the Cargo config replacing the crates.io and git sources with the vendored sources.
+/// See https://doc.rust-lang.org/cargo/reference/source-replacement.html
+pub fn get_cargo_config(cargo_lock: &CargoLock) -> String {
+    let mut sources = toml::map::Map::new();
+
+    let mut crates_io_source = toml::map::Map::new();
+    crates_io_source.insert(
+        "replace-with".to_string(),
+        toml::Value::String(VENDORED_SOURCES_NAME.to_string()),
+    );
+    sources.insert("crates-io".to_string(), toml::Value::Table(crates_io_source));
+
+    for git_source in cargo_lock.get_git_sources() {
+        let mut git_source_config = toml::map::Map::new();
+        git_source_config.insert("git".to_string(), toml::Value::String(git_source.url.to_string()));
+        if let Some(branch) = &git_source.branch {
+            git_source_config.insert("branch".to_string(), toml::Value::String(branch.to_string()));
+        }
+        if let Some(tag) = &git_source.tag {
+            git_source_config.insert("tag".to_string(), toml::Value::String(tag.to_string()));
+        }
+        if let Some(rev) = &git_source.rev {
+            git_source_config.insert("rev".to_string(), toml::Value::String(rev.to_string()));
+        }
+        git_source_config.insert(
+            "replace-with".to_string(),
+            toml::Value::String(VENDORED_SOURCES_NAME.to_string()),
+        );
+        sources.insert(git_source.get_source_id(), toml::Value::Table(git_source_config));
+    }
+
+    let mut vendored_sources = toml::map::Map::new();
+    vendored_sources.insert(
+        "directory".to_string(),
+        toml::Value::String(DEFAULT_DESTINATION.to_string()),
+    );
+    sources.insert(
+        VENDORED_SOURCES_NAME.to_string(),
+        toml::Value::Table(vendored_sources),
+    );
+
+    let mut cargo_config = toml::map::Map::new();
+    cargo_config.insert("source".to_string(), toml::Value::Table(sources));
+    toml::to_string(&toml::Value::Table(cargo_config)).unwrap_or_default()
+}
+
+/// A package of a Cargo workspace.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CargoWorkspacePackage {
+    pub dir: path::PathBuf,
+    pub manifest: CargoManifest,
+}
+impl CargoWorkspacePackage {
+    pub fn get_name(&self) -> String {
+        match &self.manifest.package {
+            Some(p) => p.name.to_string(),
+            None => "".to_string(),
+        }
+    }
+
+    /// Gets the features of the package among the requested features. Like with cargo,
+    /// a feature can be qualified with the name of its package, as `package/feature`.
+    pub fn get_features(&self, features: &[String]) -> Vec<String> {
+        let package_name = self.get_name();
+        let mut package_features: Vec<String> = vec![];
+        for feature in features {
+            let feature_name = match feature.split_once('/') {
+                Some((name, feature_name)) if name == package_name => feature_name,
+                Some(_) => continue,
+                None => feature,
+            };
+            if self.manifest.has_feature(feature_name) && !package_features.iter().any(|f| f == feature_name) {
+                package_features.push(feature_name.to_string());
+            }
+        }
+        package_features
+    }
+}
+
+/// Matches a single path component against a pattern supporting the `*`
+/// and `?` wildcards.
+fn wildcard_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut pattern_index, mut text_index) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while text_index < text.len() {
+        if pattern_index < pattern.len()
+            && (pattern[pattern_index] == '?' || pattern[pattern_index] == text[text_index])
+        {
+            pattern_index += 1;
+            text_index += 1;
+        } else if pattern_index < pattern.len() && pattern[pattern_index] == '*' {
+            last_star = Some((pattern_index, text_index));
+            pattern_index += 1;
+        } else if let Some((star_pattern_index, star_text_index)) = last_star {
+            pattern_index = star_pattern_index + 1;
+            text_index = star_text_index + 1;
+            last_star = Some((star_pattern_index, star_text_index + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[pattern_index..].iter().all(|c| *c == '*')
+}
+
+/// Expands a workspace member, which can be a glob pattern, into the member directories.
+fn expand_workspace_member(workspace_dir: &path::Path, member: &str) -> Vec<path::PathBuf> {
+    let mut member_dirs: Vec<path::PathBuf> = vec![workspace_dir.to_path_buf()];
+    for member_part in member.split('/').filter(|p| !p.is_empty() && *p != ".") {
+        let mut next_member_dirs: Vec<path::PathBuf> = vec![];
+        for member_dir in member_dirs {
+            if !member_part.contains('*') && !member_part.contains('?') {
+                next_member_dirs.push(member_dir.join(member_part));
+                continue;
+            }
+            let dir_entries = match fs::read_dir(&member_dir) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            for dir_entry in dir_entries.flatten() {
+                let entry_name = dir_entry.file_name().to_str().unwrap_or("").to_string();
+                if dir_entry.path().is_dir() && wildcard_matches(member_part, &entry_name) {
+                    next_member_dirs.push(dir_entry.path());
+                }
+            }
+        }
+        member_dirs = next_member_dirs;
+    }
+    member_dirs.sort();
+    member_dirs
+}
+
+/// Gets the packages of a workspace, including the root package if any. The
+/// members are expanded, and the excluded directories are ignored.
+/// See https://doc.rust-lang.org/cargo/reference/workspaces.html
+pub fn get_workspace_packages(workspace_dir: &path::Path) -> Result<Vec<CargoWorkspacePackage>, String> {
+    let root_manifest = CargoManifest::load_from_file(&workspace_dir.join("Cargo.toml"))?;
+
+    let mut packages: Vec<CargoWorkspacePackage> = vec![];
+    if root_manifest.package.is_some() {
+        packages.push(CargoWorkspacePackage {
+            dir: workspace_dir.to_path_buf(),
+            manifest: root_manifest.clone(),
+        });
+    }
+
+    let workspace = match &root_manifest.workspace {
+        Some(w) => w,
+        None => return Ok(packages),
+    };
+    let excluded_dirs: Vec<path::PathBuf> = workspace.exclude.iter().map(|e| workspace_dir.join(e)).collect();
+    for member in &workspace.members {
+        for member_dir in expand_workspace_member(workspace_dir, member) {
+            if excluded_dirs.iter().any(|e| member_dir.starts_with(e)) {
+                log::debug!("Ignoring excluded workspace member {}.", member_dir.display());
+                continue;
+            }
+            if packages.iter().any(|p| p.dir == member_dir) {
+                continue;
+            }
+            let member_manifest_path = member_dir.join("Cargo.toml");
+            if !member_manifest_path.is_file() {
+                continue;
+            }
+            packages.push(CargoWorkspacePackage {
+                manifest: CargoManifest::load_from_file(&member_manifest_path)?,
+                dir: member_dir,
+            });
+        }
+    }
+    Ok(packages)
+}
+
+/// Finds the root directory of the workspace a Cargo manifest belongs to, by walking
+/// up the directories. This is where the Cargo.lock file is located.
+pub fn get_workspace_root_dir(manifest_path: &path::Path) -> Result<path::PathBuf, String> {
+    let manifest_dir = match manifest_path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => path::PathBuf::from("."),
+    };
+    let manifest_dir = match manifest_dir.canonicalize() {
+        Ok(d) => d,
+        Err(e) => {
+            return Err(format!(
+                "Invalid Cargo manifest path {}: {}",
+                manifest_path.display(),
+                e
+            ))
+        }
+    };
+
+    let cargo_manifest = CargoManifest::load_from_file(manifest_path)?;
+    if cargo_manifest.workspace.is_some() {
+        return Ok(manifest_dir);
+    }
+    // The root of the workspace can be configured explicitly.
+    if let Some(workspace_path) = cargo_manifest.package.as_ref().and_then(|p| p.workspace.as_ref()) {
+        return Ok(manifest_dir.join(workspace_path));
+    }
+
+    for ancestor_dir in manifest_dir.ancestors().skip(1) {
+        let ancestor_manifest_path = ancestor_dir.join("Cargo.toml");
+        if !ancestor_manifest_path.is_file() {
+            continue;
+        }
+        let ancestor_manifest = CargoManifest::load_from_file(&ancestor_manifest_path)?;
+        if ancestor_manifest.workspace.is_none() {
+            continue;
+        }
+        let workspace_packages = get_workspace_packages(ancestor_dir)?;
+        if workspace_packages.iter().any(|p| p.dir == manifest_dir) {
+            return Ok(ancestor_dir.to_path_buf());
+        }
+    }
+
+    // The package is not part of a workspace, so it is its own root.
+    Ok(manifest_dir)
+}
+
+pub fn get_cargo_module(
+    manifest_path: &str,
+    cargo_lock_manifest: &str,
+    options: &ImportOptions,
+) -> Result<FlatpakModule, String> {
+    let cargo_manifest_path = path::Path::new(manifest_path);
+    let cargo_manifest = CargoManifest::load_from_file(cargo_manifest_path)?;
+    let workspace_root_dir = get_workspace_root_dir(cargo_manifest_path)?;
+    let workspace_packages = get_workspace_packages(&workspace_root_dir)?;
+
+    // By default, we build the package that was imported, or all the packages
+    // when importing the root of a virtual workspace.
+    let mut selected_packages: Vec<&CargoWorkspacePackage> = vec![];
+    if !options.packages.is_empty() {
+        for package_name in &options.packages {
+            match workspace_packages.iter().find(|p| &p.get_name() == package_name) {
+                Some(p) => selected_packages.push(p),
+                None => {
+                    return Err(format!(
+                        "Package {} is not a member of the workspace at {}.",
+                        package_name,
+                        workspace_root_dir.display()
+                    ))
+                }
+            }
+        }
+    } else if let Some(package) = &cargo_manifest.package {
+        selected_packages = workspace_packages
+            .iter()
+            .filter(|p| p.get_name() == package.name)
+            .collect();
+    } else {
+        selected_packages = workspace_packages.iter().collect();
+    }
+
+    for feature in &options.features {
+        if !selected_packages
+            .iter()
+            .any(|p| !p.get_features(std::slice::from_ref(feature)).is_empty())
+        {
+            return Err(format!("Unknown feature {} in {}.", feature, manifest_path));
+        }
+    }
+
+    let module_name = match (&cargo_manifest.package, selected_packages.len()) {
+        (_, 1) => selected_packages[0].get_name(),
+        (Some(p), _) => p.name.to_string(),
+        (None, _) => "cargo_sources".to_string(),
+    };
+
+    let mut build_options_env: BTreeMap<String, String> = BTreeMap::new();
+    build_options_env.insert(
+        "CARGO_HOME".to_string(),
+        format!("/run/build/{}/{}", module_name, DEFAULT_CARGO_HOME),
+    );
+    let mut cargo_module = FlatpakModule {
+        name: module_name,
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        build_options: Some(FlatpakBuildOptions {
+            append_path: RUST_SDK_EXTENSION_PATH.to_string(),
+            env: FlatpakBuildOptionsEnv::Dict(build_options_env),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let mut build_command = "cargo --offline build --release --locked".to_string();
+    let mut build_features: Vec<String> = vec![];
+    if workspace_packages.len() > 1 {
+        // The features have to be qualified with their package when selecting packages,
+        // otherwise they are rejected or applied to the wrong package.
+        for package in &selected_packages {
+            build_command.push_str(&format!(" -p {}", package.get_name()));
+            for feature in package.get_features(&options.features) {
+                build_features.push(format!("{}/{}", package.get_name(), feature));
+            }
+        }
+    } else {
+        for package in &selected_packages {
+            build_features.append(&mut package.get_features(&options.features));
+        }
+    }
+    if !build_features.is_empty() {
+        build_command.push_str(&format!(" --features {}", build_features.join(",")));
+    }
+    cargo_module.build_commands.push(build_command);
+
+    let mut binaries: Vec<String> = vec![];
+    for package in &selected_packages {
+        binaries.append(
+            &mut package
+                .manifest
+                .get_binaries(&package.dir, &package.get_features(&options.features)),
+        );
+    }
+    if binaries.is_empty() {
+        log::warn!(
+            "No binaries found in {}, nothing will be installed.",
+            manifest_path
+        );
+    }
+    for binary in binaries {
+        cargo_module.build_commands.push(format!(
+            "install -Dm755 ./target/release/{} -t ${{FLATPAK_DEST}}/bin/",
+            binary
+        ));
+    }
+
+    let current_cargo_project_source = FlatpakSource {
+        r#type: Some(FlatpakSourceType::Git),
+        path: Some("./".to_string()),
+        ..Default::default()
+    };
     cargo_module
         .sources
         .push(FlatpakSourceItem::Description(current_cargo_project_source));
-    for source in get_sources(cargo_lock_manifest).unwrap() {
+    let cargo_lock = CargoLock::parse(cargo_lock_manifest)?;
+    let sources = if options.targets.is_empty() {
+        get_sources(cargo_lock_manifest)?
+    } else {
+        let workspace_root_manifest = CargoManifest::load_from_file(&workspace_root_dir.join("Cargo.toml"))?;
+        get_target_sources(
+            &cargo_lock,
+            &workspace_root_dir,
+            &workspace_root_manifest,
+            &workspace_packages,
+            &selected_packages,
+            options,
+        )?
+    };
+    for source in sources {
         cargo_module.sources.push(FlatpakSourceItem::Description(source));
     }
-    cargo_module
+
+    for source in get_vendor_config_sources(&cargo_lock) {
+        cargo_module.sources.push(FlatpakSourceItem::Description(source));
+    }
+    Ok(cargo_module)
 }
 
-pub fn get_sources(cargo_lock_manifest: &str) -> Result<Vec<FlatpakSource>, String> {
-    let mut sources: Vec<FlatpakSource> = vec![];
-    let cargo_lock: CargoLock = match toml::from_str(&cargo_lock_manifest) {
-        Ok(c) => c,
-        Err(e) => {
-            return Err(format!("Failed to parse the Cargo.lock manifest: {}.", e));
+/// Gets the sources writing the files cargo needs to build from the vendor directory:
+/// a `.cargo-checksum.json` file in every crate directory, and the source replacement
+/// config.
+pub fn get_vendor_config_sources(cargo_lock: &CargoLock) -> Vec<FlatpakSource> {
+    let mut checksum_commands: Vec<String> = vec![];
+    for cargo_package in &cargo_lock.package {
+        if let Some(checksum_file_content) = cargo_package.get_checksum_file_content() {
+            checksum_commands.push(format!(
+                "printf '%s' '{}' > {}/.cargo-checksum.json",
+                checksum_file_content,
+                cargo_package.get_vendor_dir()
+            ));
         }
+    }
+
+    let cargo_config = get_cargo_config(cargo_lock).replace('\'', "'\\''");
+    // The config is written in the CARGO_HOME of the module.
+    let config_commands = vec![
+        format!("mkdir -p {}", DEFAULT_CARGO_HOME),
+        format!(
+            "printf '%s' '{}' > {}/config.toml",
+            cargo_config, DEFAULT_CARGO_HOME
+        ),
+    ];
+
+    let mut sources: Vec<FlatpakSource> = vec![];
+    if !checksum_commands.is_empty() {
+        sources.push(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(checksum_commands),
+            ..Default::default()
+        });
+    }
+    sources.push(FlatpakSource {
+        r#type: Some(FlatpakSourceType::Shell),
+        commands: Some(config_commands),
+        ..Default::default()
+    });
+    sources
+}
+
+/// Gets the sources of the packages built for the given targets. The other crates.io
+/// packages are replaced by stubs in the vendor directory, since cargo still needs them
+/// to resolve the dependency graph from the lock file.
+pub fn get_target_sources(
+    cargo_lock: &CargoLock,
+    workspace_root_dir: &path::Path,
+    workspace_root_manifest: &CargoManifest,
+    workspace_packages: &[CargoWorkspacePackage],
+    selected_packages: &[&CargoWorkspacePackage],
+    options: &ImportOptions,
+) -> Result<Vec<FlatpakSource>, String> {
+    let mut targets: Vec<CargoTarget> = vec![];
+    for target in &options.targets {
+        targets.push(CargoTarget::parse(target)?);
+    }
+    let workspace_dependencies = match &workspace_root_manifest.workspace {
+        Some(w) => w.dependencies.clone(),
+        None => BTreeMap::new(),
     };
+
+    let local_crate_dirs = get_local_crate_dirs(workspace_root_dir);
+
+    let mut metadata: BTreeMap<usize, CargoPackageMetadata> = BTreeMap::new();
+    let mut root_packages: Vec<usize> = vec![];
+    for (package_index, cargo_package) in cargo_lock.package.iter().enumerate() {
+        if cargo_package.source.is_empty() {
+            let workspace_package = match workspace_packages
+                .iter()
+                .find(|p| p.get_name() == cargo_package.name)
+            {
+                Some(p) => p,
+                None => continue,
+            };
+            metadata.insert(
+                package_index,
+                workspace_package.manifest.get_metadata(&workspace_dependencies),
+            );
+            if selected_packages
+                .iter()
+                .any(|p| p.get_name() == cargo_package.name)
+            {
+                root_packages.push(package_index);
+            }
+        } else if cargo_package.is_from_crates_io() {
+            if let Some(package_metadata) = get_local_metadata(cargo_package, &local_crate_dirs) {
+                metadata.insert(package_index, package_metadata);
+                continue;
+            }
+            log::debug!(
+                "Fetching the metadata of crate {} {}.",
+                cargo_package.name,
+                cargo_package.version
+            );
+            // Without metadata, the package and all its dependencies are vendored.
+            match CargoIndexEntry::fetch(&cargo_package.name, &cargo_package.version) {
+                Ok(index_entry) => {
+                    metadata.insert(package_index, index_entry.get_metadata());
+                }
+                Err(e) => log::warn!(
+                    "Could not get the metadata of crate {} {}, it will be vendored with all its dependencies: {}",
+                    cargo_package.name,
+                    cargo_package.version,
+                    e
+                ),
+            }
+        }
+    }
+    let mut root_features: Vec<String> = vec![];
+    for package in selected_packages {
+        root_features.append(&mut package.get_features(&options.features));
+    }
+    let target_packages =
+        cargo_lock.get_target_packages(&metadata, &root_packages, &root_features, &targets)?;
+
+    let mut sources: Vec<FlatpakSource> = vec![];
+    for git_source in cargo_lock.get_git_sources() {
+        sources.push(git_source.get_flatpak_source());
+    }
+    if let Some(script_source) = get_git_crate_vendor_script_source(cargo_lock) {
+        sources.push(script_source);
+    }
+    let mut stub_commands: Vec<String> = vec![];
+    for (package_index, cargo_package) in cargo_lock.package.iter().enumerate() {
+        let package_metadata = match metadata.get(&package_index) {
+            Some(m) if cargo_package.is_from_crates_io() && !target_packages.contains(&package_index) => m,
+            _ => {
+                sources.append(&mut cargo_package.get_sources());
+                continue;
+            }
+        };
+        log::debug!(
+            "Crate {} {} is not built for {}, using a stub.",
+            cargo_package.name,
+            cargo_package.version,
+            options.targets.join(", ")
+        );
+        let stub_manifest = package_metadata
+            .get_stub_manifest(&cargo_package.name, &cargo_package.version)
+            .replace('\'', "'\\''");
+        let vendor_dir = cargo_package.get_vendor_dir();
+        stub_commands.push(format!(
+            "mkdir -p {}/src && printf '%s' '{}' > {}/Cargo.toml && touch {}/src/lib.rs",
+            vendor_dir, stub_manifest, vendor_dir, vendor_dir
+        ));
+    }
+    if !stub_commands.is_empty() {
+        log::info!(
+            "{} crates are not built for {} and were replaced by stubs.",
+            stub_commands.len(),
+            options.targets.join(", ")
+        );
+        sources.push(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(stub_commands),
+            ..Default::default()
+        });
+    }
+    Ok(sources)
+}
+
+/// Gets the source writing the script which vendors the crates from git repositories,
+/// if the lock file has any.
+pub fn get_git_crate_vendor_script_source(cargo_lock: &CargoLock) -> Option<FlatpakSource> {
+    if cargo_lock.get_git_sources().is_empty() {
+        return None;
+    }
+    Some(FlatpakSource {
+        r#type: Some(FlatpakSourceType::Shell),
+        commands: Some(vec![format!(
+            "printf '%s\\n' '{}' > {}",
+            GIT_CRATE_VENDOR_SCRIPT.join("' '"),
+            GIT_CRATE_VENDOR_SCRIPT_PATH
+        )]),
+        ..Default::default()
+    })
+}
+
+pub fn get_sources(cargo_lock_manifest: &str) -> Result<Vec<FlatpakSource>, String> {
+    let mut sources: Vec<FlatpakSource> = vec![];
+    let cargo_lock = CargoLock::parse(cargo_lock_manifest)?;
+
+    // The git repositories have to be checked out before the crates
+    // can be copied to the vendor directory.
+    for git_source in cargo_lock.get_git_sources() {
+        sources.push(git_source.get_flatpak_source());
+    }
+    if let Some(script_source) = get_git_crate_vendor_script_source(&cargo_lock) {
+        sources.push(script_source);
+    }
     for cargo_package in &cargo_lock.package {
         let new_sources = cargo_package.get_sources();
         for new_source in new_sources {
@@ -106,6 +1890,43 @@ pub fn get_sources(cargo_lock_manifest: &str) -> Result<Vec<FlatpakSource>, Stri
     Ok(sources)
 }
 
+pub struct CargoImporter {}
+impl Importer for CargoImporter {
+    fn get_name(&self) -> String {
+        "cargo".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        CargoManifest::file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, options: &ImportOptions) -> Result<ImportResult, String> {
+        // The Cargo.lock file is at the root of the workspace.
+        let workspace_root_dir = match get_workspace_root_dir(path::Path::new(manifest_path)) {
+            Ok(d) => d,
+            Err(e) => {
+                return Err(format!(
+                    "Could not find the Cargo workspace of {}: {}",
+                    manifest_path, e
+                ))
+            }
+        };
+        let cargo_lock_path = workspace_root_dir.join("Cargo.lock");
+        let cargo_lock_content = match fs::read_to_string(&cargo_lock_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read Cargo.lock file at {}: {}",
+                    cargo_lock_path.display(),
+                    e
+                ))
+            }
+        };
+        let cargo_module = get_cargo_module(manifest_path, &cargo_lock_content, options)?;
+        Ok(ImportResult::new(cargo_module))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -159,4 +1980,609 @@ mod tests {
 
         assert_eq!(sources.len(), 5);
     }
+
+    #[test]
+    pub fn test_parse_git_source() {
+        let git_source = CargoGitSource::parse(
+            "git+https://github.com/louib/flatpak-rs?branch=main#2d9cba64a1b3c7d8e9f0a1b2c3d4e5f6a7b8c9d0",
+        )
+        .unwrap();
+        assert_eq!(git_source.url, "https://github.com/louib/flatpak-rs");
+        assert_eq!(git_source.branch, Some("main".to_string()));
+        assert_eq!(git_source.commit, "2d9cba64a1b3c7d8e9f0a1b2c3d4e5f6a7b8c9d0");
+        assert_eq!(
+            git_source.get_source_id(),
+            "git+https://github.com/louib/flatpak-rs?branch=main"
+        );
+        assert_eq!(git_source.get_dest(), "cargo/git/flatpak-rs-2d9cba6");
+
+        let git_source =
+            CargoGitSource::parse("git+https://github.com/louib/fpm.git?tag=v0.1.0#abcdef").unwrap();
+        assert_eq!(git_source.tag, Some("v0.1.0".to_string()));
+        assert_eq!(git_source.get_dest(), "cargo/git/fpm-abcdef");
+
+        let git_source = CargoGitSource::parse("git+https://github.com/louib/fpm#abcdef").unwrap();
+        assert_eq!(git_source.get_source_id(), "git+https://github.com/louib/fpm");
+
+        assert!(CargoGitSource::parse("git+https://github.com/louib/fpm").is_err());
+        assert!(CargoGitSource::parse("registry+https://github.com/rust-lang/crates.io-index").is_err());
+    }
+
+    #[test]
+    pub fn test_get_git_sources() {
+        let cargo_lock_manifest: &str = r###"
+            version = 3
+
+            [[package]]
+            name = "flatpak-rs"
+            version = "0.18.0"
+            source = "git+https://github.com/louib/flatpak-rs?rev=2d9cba6#2d9cba64a1b3c7d8e9f0a1b2c3d4e5f6a7b8c9d0"
+
+            [[package]]
+            name = "flatpak-rs-macros"
+            version = "0.1.0"
+            source = "git+https://github.com/louib/flatpak-rs?rev=2d9cba6#2d9cba64a1b3c7d8e9f0a1b2c3d4e5f6a7b8c9d0"
+
+            [[package]]
+            name = "cfg-if"
+            version = "1.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "baf1de4339761588bc0619e3cbc0120ee582ebb74b53b4efbf79117bd2da40fd"
+        "###;
+        let sources = get_sources(cargo_lock_manifest).unwrap();
+        // One checkout for the git repository, the vendoring script, one copy per git crate,
+        // and one archive.
+        assert_eq!(sources.len(), 5);
+        assert_eq!(sources[0].r#type, Some(FlatpakSourceType::Git));
+        assert_eq!(
+            sources[0].commit,
+            Some("2d9cba64a1b3c7d8e9f0a1b2c3d4e5f6a7b8c9d0".to_string())
+        );
+
+        let cargo_config = get_cargo_config(&CargoLock::parse(cargo_lock_manifest).unwrap());
+        let cargo_config: toml::Value = toml::from_str(&cargo_config).unwrap();
+        let git_source_config = &cargo_config["source"]["git+https://github.com/louib/flatpak-rs?rev=2d9cba6"];
+        assert_eq!(
+            git_source_config["git"].as_str(),
+            Some("https://github.com/louib/flatpak-rs")
+        );
+        assert_eq!(git_source_config["rev"].as_str(), Some("2d9cba6"));
+        assert_eq!(
+            git_source_config["replace-with"].as_str(),
+            Some("vendored-sources")
+        );
+        assert_eq!(
+            cargo_config["source"]["vendored-sources"]["directory"].as_str(),
+            Some("cargo/vendor")
+        );
+    }
+
+    #[test]
+    pub fn test_get_vendor_config_sources() {
+        let cargo_lock_manifest: &str = r###"
+            version = 3
+
+            [[package]]
+            name = "fpm"
+            version = "0.0.3"
+
+            [[package]]
+            name = "flatpak-rs"
+            version = "0.18.0"
+            source = "git+https://github.com/louib/flatpak-rs#2d9cba64a1b3c7d8e9f0a1b2c3d4e5f6a7b8c9d0"
+
+            [[package]]
+            name = "cfg-if"
+            version = "1.0.0"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            checksum = "baf1de4339761588bc0619e3cbc0120ee582ebb74b53b4efbf79117bd2da40fd"
+        "###;
+        let cargo_lock = CargoLock::parse(cargo_lock_manifest).unwrap();
+        let sources = get_vendor_config_sources(&cargo_lock);
+        assert_eq!(sources.len(), 2);
+
+        let checksum_commands = sources[0].commands.as_ref().unwrap();
+        assert_eq!(checksum_commands.len(), 2);
+        assert_eq!(
+            checksum_commands[0],
+            "printf '%s' '{\"files\":{},\"package\":null}' > cargo/vendor/flatpak-rs-0.18.0/.cargo-checksum.json"
+        );
+        assert_eq!(
+            checksum_commands[1],
+            "printf '%s' '{\"files\":{},\"package\":\"baf1de4339761588bc0619e3cbc0120ee582ebb74b53b4efbf79117bd2da40fd\"}' > cargo/vendor/cfg-if-1.0.0/.cargo-checksum.json"
+        );
+
+        let config_commands = sources[1].commands.as_ref().unwrap();
+        assert!(config_commands[1].ends_with("> cargo/config.toml"));
+    }
+
+    #[test]
+    pub fn test_parse_manifest() {
+        let cargo_manifest = CargoManifest::parse(
+            r###"
+            [package]
+            name = "fpm"
+            version = "0.0.3"
+            autobins = false
+
+            [[bin]]
+            name = "fpm-cli"
+            path = "src/main.rs"
+
+            [[bin]]
+            name = "fpm-extended"
+            path = "src/extended.rs"
+            required-features = ["extended"]
+
+            [features]
+            default = []
+            extended = ["flatpak-rs/extended"]
+
+            [dependencies]
+            log = "0.4"
+            serde = { version = "1.0", optional = true }
+            "###,
+        )
+        .unwrap();
+
+        assert_eq!(cargo_manifest.package.as_ref().unwrap().name, "fpm");
+        assert!(cargo_manifest.has_feature("extended"));
+        assert!(cargo_manifest.has_feature("serde"));
+        assert!(!cargo_manifest.has_feature("log"));
+        assert!(!cargo_manifest.has_feature("unknown"));
+        assert_eq!(
+            cargo_manifest.get_binaries(path::Path::new("./"), &[]),
+            vec!["fpm-cli"]
+        );
+        assert_eq!(
+            cargo_manifest.get_binaries(path::Path::new("./"), &["extended".to_string()]),
+            vec!["fpm-cli", "fpm-extended"]
+        );
+    }
+
+    #[test]
+    pub fn test_parse_workspace_manifest() {
+        let cargo_manifest = CargoManifest::parse(
+            r###"
+            [workspace]
+            members = ["cli", "core"]
+            "###,
+        )
+        .unwrap();
+
+        assert!(cargo_manifest.package.is_none());
+        assert_eq!(cargo_manifest.workspace.unwrap().members, vec!["cli", "core"]);
+    }
+
+    #[test]
+    pub fn test_get_binaries() {
+        let manifest_dir = std::env::temp_dir().join(format!("fpm-test-cargo-binaries-{}", std::process::id()));
+        fs::create_dir_all(manifest_dir.join("src/bin/helper")).unwrap();
+        fs::write(manifest_dir.join("src/main.rs"), "fn main() {}").unwrap();
+        fs::write(manifest_dir.join("src/bin/tool.rs"), "fn main() {}").unwrap();
+        fs::write(manifest_dir.join("src/bin/helper/main.rs"), "fn main() {}").unwrap();
+        fs::write(manifest_dir.join("src/bin/debug.rs"), "fn main() {}").unwrap();
+        let cargo_manifest = CargoManifest::parse(
+            r###"
+            [package]
+            name = "app"
+            version = "0.1.0"
+
+            [[bin]]
+            name = "app-cli"
+            path = "src/main.rs"
+
+            [[bin]]
+            name = "tool"
+
+            [[bin]]
+            name = "renamed-helper"
+            path = "./src/bin/helper/main.rs"
+
+            [[bin]]
+            name = "app-debug"
+            path = "src/bin/debug.rs"
+            required-features = ["debug"]
+            "###,
+        )
+        .unwrap();
+
+        let binaries = cargo_manifest.get_binaries(&manifest_dir, &[]);
+        let _ = fs::remove_dir_all(&manifest_dir);
+
+        // The discovered binaries using the path of a target are not built.
+        assert_eq!(binaries, vec!["app-cli", "renamed-helper", "tool"]);
+    }
+
+    #[test]
+    pub fn test_vendor_git_crate() {
+        let build_dir = std::env::temp_dir().join(format!("fpm-test-cargo-git-crate-{}", std::process::id()));
+        let cargo_lock = CargoLock::parse(
+            r###"
+            version = 3
+
+            [[package]]
+            name = "fpm-core"
+            version = "0.1.0"
+            source = "git+https://github.com/louib/fpm?branch=main#abcdef0123"
+        "###,
+        )
+        .unwrap();
+        let cargo_package = &cargo_lock.package[0];
+        let git_source = CargoGitSource::parse(&cargo_package.source).unwrap();
+        let checkout_dir = build_dir.join(git_source.get_dest());
+        fs::create_dir_all(checkout_dir.join("cli")).unwrap();
+        fs::create_dir_all(checkout_dir.join("core/src")).unwrap();
+        fs::write(
+            checkout_dir.join("Cargo.toml"),
+            r###"
+            [workspace]
+            members = ["cli", "core"]
+
+            [workspace.package]
+            version = "0.1.0"
+            license-file = "LICENSE"
+
+            [workspace.dependencies]
+            serde = { version = "1.0", features = ["derive"] }
+            fpm-macros = { path = "macros", version = "0.1.0" }
+            "###,
+        )
+        .unwrap();
+        fs::write(checkout_dir.join("LICENSE"), "MIT").unwrap();
+        // The crate name is also used as the name of a binary of another crate.
+        fs::write(
+            checkout_dir.join("cli/Cargo.toml"),
+            "[package]\nname = \"fpm-cli\"\n\n[[bin]]\nname = \"fpm-core\"\n",
+        )
+        .unwrap();
+        fs::write(
+            checkout_dir.join("core/Cargo.toml"),
+            r###"
+            [package]
+            name = "fpm-core"
+            version.workspace = true
+            license-file = { workspace = true }
+            description = "The 'core' of fpm"
+
+            [dependencies]
+            serde = { workspace = true, features = ["rc"] }
+            fpm-macros.workspace = true
+
+            [dev-dependencies]
+            fpm-test-utils = { path = "../test-utils" }
+            "###,
+        )
+        .unwrap();
+        fs::write(checkout_dir.join("core/src/lib.rs"), "").unwrap();
+
+        let mut commands = get_git_crate_vendor_script_source(&cargo_lock)
+            .unwrap()
+            .commands
+            .unwrap();
+        commands.append(&mut cargo_package.get_sources()[0].commands.clone().unwrap());
+        let vendor_output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(commands.join(" && "))
+            .current_dir(&build_dir)
+            .output()
+            .unwrap();
+        let vendor_dir = build_dir.join(cargo_package.get_vendor_dir());
+        let vendored_manifest = fs::read_to_string(vendor_dir.join("Cargo.toml"));
+        let vendored_files = (
+            vendor_dir.join("LICENSE").is_file(),
+            vendor_dir.join("src/lib.rs").is_file(),
+        );
+        let _ = fs::remove_dir_all(&build_dir);
+
+        assert!(
+            vendor_output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&vendor_output.stderr)
+        );
+        assert_eq!(vendored_files, (true, true));
+        let vendored_manifest: toml::Value = toml::from_str(&vendored_manifest.unwrap()).unwrap();
+        let expected_manifest: toml::Value = toml::from_str(
+            r###"
+            [package]
+            name = "fpm-core"
+            version = "0.1.0"
+            license-file = "LICENSE"
+            description = "The 'core' of fpm"
+
+            [dependencies]
+            serde = { version = "1.0", features = ["derive", "rc"] }
+            fpm-macros = { version = "0.1.0" }
+
+            [dev-dependencies]
+            "###,
+        )
+        .unwrap();
+        assert_eq!(vendored_manifest, expected_manifest);
+    }
+
+    #[test]
+    pub fn test_wildcard_matches() {
+        assert!(wildcard_matches("*", "fpm-core"));
+        assert!(wildcard_matches("fpm-*", "fpm-core"));
+        assert!(wildcard_matches("fpm-?ore", "fpm-core"));
+        assert!(wildcard_matches("*-core", "fpm-core"));
+        assert!(!wildcard_matches("fpm-*", "flatpak-rs"));
+        assert!(!wildcard_matches("fpm", "fpm-core"));
+    }
+
+    #[test]
+    pub fn test_get_workspace_packages() {
+        let workspace_dir = std::env::temp_dir().join(format!("fpm-cargo-workspace-{}", std::process::id()));
+        for member in ["crates/cli", "crates/core", "crates/excluded"] {
+            let member_dir = workspace_dir.join(member);
+            fs::create_dir_all(member_dir.join("src")).unwrap();
+            let member_name = member.rsplit('/').next().unwrap();
+            fs::write(
+                member_dir.join("Cargo.toml"),
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n\n[features]\n{}-extras = []\n",
+                    member_name, member_name
+                ),
+            )
+            .unwrap();
+        }
+        fs::write(workspace_dir.join("crates/cli/src/main.rs"), "").unwrap();
+        fs::write(
+            workspace_dir.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/excluded\"]\n",
+        )
+        .unwrap();
+
+        let packages = get_workspace_packages(&workspace_dir).unwrap();
+        let package_names: Vec<String> = packages.iter().map(|p| p.get_name()).collect();
+        assert_eq!(package_names, vec!["cli", "core"]);
+
+        let root_dir = get_workspace_root_dir(&workspace_dir.join("crates/core/Cargo.toml")).unwrap();
+        assert_eq!(root_dir, workspace_dir.canonicalize().unwrap());
+
+        let options = crate::importers::ImportOptions {
+            packages: vec!["cli".to_string()],
+            ..Default::default()
+        };
+        let cargo_lock_manifest = "version = 3\n\n[[package]]\nname = \"cli\"\nversion = \"0.1.0\"\n";
+        let cargo_module = get_cargo_module(
+            workspace_dir.join("Cargo.toml").to_str().unwrap(),
+            cargo_lock_manifest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(cargo_module.name, "cli");
+        assert_eq!(
+            cargo_module.build_commands,
+            vec![
+                "cargo --offline build --release --locked -p cli",
+                "install -Dm755 ./target/release/cli -t ${FLATPAK_DEST}/bin/",
+            ]
+        );
+
+        let options = crate::importers::ImportOptions {
+            packages: vec!["cli".to_string(), "core".to_string()],
+            features: vec!["cli-extras".to_string(), "core/core-extras".to_string()],
+            ..Default::default()
+        };
+        let cargo_module = get_cargo_module(
+            workspace_dir.join("Cargo.toml").to_str().unwrap(),
+            cargo_lock_manifest,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            cargo_module.build_commands[0],
+            "cargo --offline build --release --locked -p cli -p core --features cli/cli-extras,core/core-extras"
+        );
+        let options = crate::importers::ImportOptions {
+            packages: vec!["cli".to_string()],
+            features: vec!["core/core-extras".to_string()],
+            ..Default::default()
+        };
+        assert!(get_cargo_module(
+            workspace_dir.join("Cargo.toml").to_str().unwrap(),
+            cargo_lock_manifest,
+            &options,
+        )
+        .is_err());
+
+        fs::remove_dir_all(&workspace_dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_target_matches() {
+        let linux_target = CargoTarget::parse("x86_64-unknown-linux-gnu").unwrap();
+        assert!(linux_target.matches("cfg(unix)").unwrap());
+        assert!(!linux_target.matches("cfg(windows)").unwrap());
+        assert!(linux_target.matches("cfg(target_os = \"linux\")").unwrap());
+        assert!(linux_target
+            .matches("cfg(all(unix, not(target_os = \"macos\")))")
+            .unwrap());
+        assert!(linux_target
+            .matches("cfg(any(target_arch = \"x86\", target_pointer_width = \"64\"))")
+            .unwrap());
+        assert!(!linux_target.matches("cfg(target_env = \"msvc\")").unwrap());
+        assert!(linux_target.matches("x86_64-unknown-linux-gnu").unwrap());
+        assert!(!linux_target.matches("x86_64-pc-windows-gnu").unwrap());
+        assert!(linux_target.matches("cfg(all(unix)").is_err());
+        assert!(linux_target.matches("cfg(panic = \"unwind\")").unwrap());
+        assert!(linux_target.matches("cfg(target_feature = \"sse2\")").unwrap());
+        assert!(!linux_target.matches("cfg(debug_assertions)").unwrap());
+
+        let arm_target = CargoTarget::parse("armv7-unknown-linux-gnueabihf").unwrap();
+        assert_eq!(arm_target.arch, "arm");
+        assert_eq!(arm_target.env, "gnu");
+        assert!(arm_target.matches("cfg(target_pointer_width = \"32\")").unwrap());
+
+        let android_target = CargoTarget::parse("aarch64-linux-android").unwrap();
+        assert_eq!(android_target.os, "android");
+        assert!(android_target.matches("cfg(target_family = \"unix\")").unwrap());
+    }
+
+    #[test]
+    pub fn test_get_local_metadata() {
+        let workspace_dir = std::env::temp_dir().join(format!("fpm-cargo-vendor-{}", std::process::id()));
+        let crate_dir = workspace_dir.join("vendor").join("libc");
+        fs::create_dir_all(&crate_dir).unwrap();
+        fs::write(
+            crate_dir.join("Cargo.toml"),
+            r#"
+[package]
+name = "libc"
+version = "0.2.150"
+
+[features]
+default = ["std"]
+std = []
+
+[target.'cfg(unix)'.dependencies.rustc-std-workspace-core]
+version = "1.0.0"
+optional = true
+"#,
+        )
+        .unwrap();
+
+        let mut cargo_package = CargoLockPackage {
+            name: "libc".to_string(),
+            source: CRATES_IO_SOURCES[0].to_string(),
+            version: "0.2.150".to_string(),
+            ..Default::default()
+        };
+        let local_crate_dirs = vec![workspace_dir.join("vendor")];
+        let metadata = get_local_metadata(&cargo_package, &local_crate_dirs).unwrap();
+        assert_eq!(metadata.features.len(), 2);
+        assert_eq!(metadata.dependencies.len(), 1);
+        assert_eq!(metadata.dependencies[0].target, Some("cfg(unix)".to_string()));
+
+        // The vendored crate is another version.
+        cargo_package.version = "0.2.151".to_string();
+        assert!(get_local_metadata(&cargo_package, &local_crate_dirs).is_none());
+
+        fs::remove_dir_all(&workspace_dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_get_target_packages() {
+        let cargo_lock_manifest: &str = r###"
+            version = 3
+
+            [[package]]
+            name = "app"
+            version = "0.1.0"
+            dependencies = ["atty", "serde"]
+
+            [[package]]
+            name = "atty"
+            version = "0.2.14"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            dependencies = ["libc", "winapi"]
+
+            [[package]]
+            name = "libc"
+            version = "0.2.126"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "serde"
+            version = "1.0.137"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+            dependencies = ["serde_derive"]
+
+            [[package]]
+            name = "serde_derive"
+            version = "1.0.137"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+
+            [[package]]
+            name = "winapi"
+            version = "0.3.9"
+            source = "registry+https://github.com/rust-lang/crates.io-index"
+        "###;
+        let cargo_lock = CargoLock::parse(cargo_lock_manifest).unwrap();
+
+        let app_manifest = CargoManifest::parse(
+            r###"
+            [package]
+            name = "app"
+
+            [features]
+            derive = ["serde/derive"]
+
+            [dependencies]
+            atty = "0.2"
+            serde = { workspace = true }
+        "###,
+        )
+        .unwrap();
+        let workspace_manifest = CargoManifest::parse(
+            r###"
+            [workspace]
+            members = ["app"]
+
+            [workspace.dependencies]
+            serde = { version = "1.0", default-features = false }
+        "###,
+        )
+        .unwrap();
+        let app_metadata = app_manifest.get_metadata(&workspace_manifest.workspace.unwrap().dependencies);
+        assert_eq!(app_metadata.dependencies[1].req, "1.0");
+        assert!(!app_metadata.dependencies[1].default_features);
+
+        let atty_entry: CargoIndexEntry = serde_json::from_str(
+            r#"{"name":"atty","vers":"0.2.14","deps":[
+                {"name":"libc","req":"^0.2","features":[],"optional":false,"default_features":false,"target":"cfg(unix)","kind":"normal"},
+                {"name":"winapi","req":"^0.3","features":["consoleapi"],"optional":false,"default_features":true,"target":"cfg(windows)","kind":"normal"}
+            ],"features":{}}"#,
+        )
+        .unwrap();
+        let serde_entry: CargoIndexEntry = serde_json::from_str(
+            r#"{"name":"serde","vers":"1.0.137","deps":[
+                {"name":"serde_derive","req":"=1.0.137","features":[],"optional":true,"default_features":true,"target":null,"kind":"normal"}
+            ],"features":{"default":["std"],"derive":["serde_derive"],"std":[]}}"#,
+        )
+        .unwrap();
+        let mut metadata: BTreeMap<usize, CargoPackageMetadata> = BTreeMap::new();
+        metadata.insert(0, app_metadata);
+        metadata.insert(1, atty_entry.get_metadata());
+        metadata.insert(2, CargoPackageMetadata::default());
+        metadata.insert(3, serde_entry.get_metadata());
+        metadata.insert(4, CargoPackageMetadata::default());
+        metadata.insert(5, CargoPackageMetadata::default());
+
+        let linux_target = CargoTarget::parse("x86_64-unknown-linux-gnu").unwrap();
+        let windows_target = CargoTarget::parse("x86_64-pc-windows-msvc").unwrap();
+        let get_package_names = |packages: BTreeSet<usize>| -> Vec<String> {
+            packages
+                .into_iter()
+                .map(|p| cargo_lock.package[p].name.to_string())
+                .collect()
+        };
+
+        let packages = cargo_lock
+            .get_target_packages(&metadata, &[0], &[], std::slice::from_ref(&linux_target))
+            .unwrap();
+        assert_eq!(get_package_names(packages), vec!["app", "atty", "libc", "serde"]);
+
+        let packages = cargo_lock
+            .get_target_packages(
+                &metadata,
+                &[0],
+                &["derive".to_string()],
+                &[linux_target, windows_target],
+            )
+            .unwrap();
+        assert_eq!(
+            get_package_names(packages),
+            vec!["app", "atty", "libc", "serde", "serde_derive", "winapi"]
+        );
+
+        let stub_manifest = metadata[&1].get_stub_manifest("atty", "0.2.14");
+        let stub_manifest = CargoManifest::parse(&stub_manifest).unwrap();
+        assert_eq!(stub_manifest.package.unwrap().name, "atty");
+        assert!(stub_manifest.target["cfg(windows)"]
+            .dependencies
+            .contains_key("winapi"));
+    }
 }
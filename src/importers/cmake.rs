@@ -0,0 +1,456 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const DEFAULT_DESTINATION: &str = "flatpak-fetchcontent";
+
+/// Returns the name and the arguments of every top-level function call
+/// found in a CMake file.
+pub fn get_cmake_function_calls(cmake_content: &str) -> Vec<(String, Vec<String>)> {
+    let mut calls: Vec<(String, Vec<String>)> = vec![];
+
+    let mut content = String::new();
+    for line in cmake_content.lines() {
+        // Comments cannot contain parenthesis that we care about.
+        let line = match line.find('#') {
+            Some(i) => &line[..i],
+            None => line,
+        };
+        content.push_str(line);
+        content.push('\n');
+    }
+
+    let mut remaining = content.as_str();
+    while let Some(open_index) = remaining.find('(') {
+        let function_name = remaining[..open_index]
+            .split_whitespace()
+            .last()
+            .unwrap_or("")
+            .to_lowercase();
+        let after_open = &remaining[open_index + 1..];
+
+        let mut depth = 1;
+        let mut close_index = after_open.len();
+        for (i, c) in after_open.char_indices() {
+            if c == '(' {
+                depth += 1;
+            } else if c == ')' {
+                depth -= 1;
+                if depth == 0 {
+                    close_index = i;
+                    break;
+                }
+            }
+        }
+
+        let args: Vec<String> = after_open[..close_index]
+            .split_whitespace()
+            .map(|a| a.trim_matches('"').to_string())
+            .collect();
+        calls.push((function_name, args));
+
+        if close_index >= after_open.len() {
+            break;
+        }
+        remaining = &after_open[close_index + 1..];
+    }
+
+    calls
+}
+
+pub fn get_cmake_arg_value(args: &[String], arg_name: &str) -> Option<String> {
+    let arg_index = args.iter().position(|a| a == arg_name)?;
+    args.get(arg_index + 1).map(|v| v.to_string())
+}
+
+/// Gets all the values following an argument, until the next keyword argument.
+pub fn get_cmake_arg_values(args: &[String], arg_name: &str) -> Vec<String> {
+    let arg_index = match args.iter().position(|a| a == arg_name) {
+        Some(i) => i,
+        None => return vec![],
+    };
+    args[arg_index + 1..]
+        .iter()
+        .take_while(|a| !is_cmake_keyword(a))
+        .map(|a| a.to_string())
+        .collect()
+}
+
+fn is_cmake_keyword(arg: &str) -> bool {
+    !arg.is_empty() && arg.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+}
+
+/// An external dependency declared with `FetchContent_Declare` or `ExternalProject_Add`.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct CMakeExternalContent {
+    pub name: String,
+    /// Whether the content was declared with `FetchContent_Declare`.
+    pub is_fetch_content: bool,
+    pub urls: Vec<String>,
+    pub url_hash: Option<String>,
+    pub git_repository: Option<String>,
+    pub git_tag: Option<String>,
+}
+impl CMakeExternalContent {
+    pub fn from_function_call(function_name: &str, args: &[String]) -> Option<CMakeExternalContent> {
+        if function_name != "fetchcontent_declare" && function_name != "externalproject_add" {
+            return None;
+        }
+        Some(CMakeExternalContent {
+            name: args.first()?.to_string(),
+            is_fetch_content: function_name == "fetchcontent_declare",
+            urls: get_cmake_arg_values(args, "URL"),
+            url_hash: get_cmake_arg_value(args, "URL_HASH"),
+            git_repository: get_cmake_arg_value(args, "GIT_REPOSITORY"),
+            git_tag: get_cmake_arg_value(args, "GIT_TAG"),
+        })
+    }
+
+    pub fn get_dest(&self) -> String {
+        format!("{}/{}", DEFAULT_DESTINATION, self.name.to_lowercase())
+    }
+
+    pub fn get_source(&self) -> Result<FlatpakSource, String> {
+        let values = self.urls.iter().chain(self.git_repository.iter());
+        // The variables are only known when configuring the project.
+        if let Some(value) = values.into_iter().find(|v| v.contains("${")) {
+            return Err(format!(
+                "{} uses variables in {}, which are not supported.",
+                self.name, value
+            ));
+        }
+
+        if let Some(url) = self.urls.first() {
+            let mut archive_source = FlatpakSource {
+                r#type: Some(FlatpakSourceType::Archive),
+                url: Some(url.to_string()),
+                dest: Some(self.get_dest()),
+                ..Default::default()
+            };
+            if self.urls.len() > 1 {
+                archive_source.mirror_urls = Some(self.urls[1..].to_vec());
+            }
+            let (algorithm, hash) = match self.url_hash.as_ref().and_then(|h| h.split_once('=')) {
+                Some((a, h)) => (a.to_uppercase(), h.to_lowercase()),
+                None => return Err(format!("{} has no URL_HASH.", self.name)),
+            };
+            match algorithm.as_str() {
+                "SHA256" => archive_source.sha256 = Some(hash),
+                "SHA512" => archive_source.sha512 = Some(hash),
+                _ => {
+                    return Err(format!(
+                        "Unsupported hash algorithm {} for {}.",
+                        algorithm, self.name
+                    ))
+                }
+            }
+            return Ok(archive_source);
+        }
+
+        if let Some(git_repository) = &self.git_repository {
+            let mut git_source = FlatpakSource {
+                r#type: Some(FlatpakSourceType::Git),
+                url: Some(git_repository.to_string()),
+                dest: Some(self.get_dest()),
+                ..Default::default()
+            };
+            match &self.git_tag {
+                Some(t) if t.len() == 40 && t.chars().all(|c| c.is_ascii_hexdigit()) => {
+                    git_source.commit = Some(t.to_string());
+                }
+                Some(t) => {
+                    log::warn!("{} is pinned to {}, which is not a commit.", self.name, t);
+                    git_source.tag = Some(t.to_string());
+                }
+                None => log::warn!("{} is not pinned to a GIT_TAG.", self.name),
+            }
+            return Ok(git_source);
+        }
+
+        Err(format!("{} has no URL or GIT_REPOSITORY.", self.name))
+    }
+
+    /// Gets the option making FetchContent use the source from flatpak-builder instead of
+    /// downloading it.
+    pub fn get_config_opt(&self, module_name: &str) -> Option<String> {
+        if !self.is_fetch_content {
+            return None;
+        }
+        Some(format!(
+            "-DFETCHCONTENT_SOURCE_DIR_{}=/run/build/{}/{}",
+            self.name.to_uppercase(),
+            module_name,
+            self.get_dest()
+        ))
+    }
+}
+
+pub fn file_path_matches(path: &str) -> bool {
+    path.ends_with("CMakeLists.txt")
+}
+
+fn is_cmake_file(path: &path::Path) -> bool {
+    match path.file_name().and_then(|f| f.to_str()) {
+        Some(f) => f == "CMakeLists.txt" || f.ends_with(".cmake"),
+        None => false,
+    }
+}
+
+/// Gets the external contents declared in all the CMake files of the project.
+pub fn get_external_contents(cmake_lists_path: &str) -> Result<Vec<CMakeExternalContent>, String> {
+    let project_dir = match path::Path::new(cmake_lists_path).parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => path::PathBuf::from("."),
+    };
+    let mut file_paths = match fpm_core::utils::get_all_paths(&project_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            return Err(format!(
+                "Could not get file paths for dir {}: {}",
+                project_dir.display(),
+                e
+            ))
+        }
+    };
+    file_paths.sort();
+
+    // The first declaration of a content is the one used by FetchContent.
+    let mut external_contents: BTreeMap<String, CMakeExternalContent> = BTreeMap::new();
+    for file_path in file_paths {
+        if !file_path.is_file() || !is_cmake_file(&file_path) {
+            continue;
+        }
+        let relative_path = file_path.strip_prefix(&project_dir).unwrap_or(&file_path);
+        // Hidden directories and the dependencies fetched by previous builds are ignored.
+        if relative_path.iter().any(|c| {
+            c.to_str()
+                .map(|c| c.starts_with('.') || c == "_deps")
+                .unwrap_or(false)
+        }) {
+            continue;
+        }
+
+        let cmake_content = match fs::read_to_string(&file_path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read {}: {}", file_path.display(), e)),
+        };
+        for (function_name, args) in get_cmake_function_calls(&cmake_content) {
+            if let Some(external_content) = CMakeExternalContent::from_function_call(&function_name, &args) {
+                log::debug!("Found {} in {}.", external_content.name, file_path.display());
+                external_contents
+                    .entry(external_content.name.to_lowercase())
+                    .or_insert(external_content);
+            }
+        }
+    }
+    Ok(external_contents.into_values().collect())
+}
+
+/// Gets the name of the project from the `project()` call of a `CMakeLists.txt` file.
+pub fn get_project_name(cmake_content: &str) -> Option<String> {
+    get_cmake_function_calls(cmake_content)
+        .into_iter()
+        .find(|(function_name, _)| function_name == "project")
+        .and_then(|(_, args)| args.first().map(|a| a.to_string()))
+}
+
+/// Gets the module building the project, with the external contents declared with FetchContent.
+/// The contents declared with `ExternalProject_Add` are reported as unsupported, since their
+/// source directory cannot be overridden, as well as the contents which cannot be converted
+/// to a source.
+pub fn get_cmake_module(cmake_lists_path: &str) -> Result<ImportResult, String> {
+    let cmake_content = match fs::read_to_string(cmake_lists_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read {}: {}", cmake_lists_path, e)),
+    };
+
+    let mut cmake_module = FlatpakModule {
+        name: get_project_name(&cmake_content).unwrap_or("cmake-project".to_string()),
+        buildsystem: Some(FlatpakBuildSystem::CMakeNinja),
+        ..Default::default()
+    };
+    cmake_module
+        .config_opts
+        .push("-DFETCHCONTENT_FULLY_DISCONNECTED=ON".to_string());
+    cmake_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+
+    let mut unsupported_entries: Vec<String> = vec![];
+    for external_content in get_external_contents(cmake_lists_path)? {
+        let config_opt = match external_content.get_config_opt(&cmake_module.name) {
+            Some(o) => o,
+            None => {
+                unsupported_entries.push(format!("{} (ExternalProject_Add)", external_content.name));
+                continue;
+            }
+        };
+        match external_content.get_source() {
+            Ok(s) => cmake_module.sources.push(FlatpakSourceItem::Description(s)),
+            Err(e) => {
+                unsupported_entries.push(e);
+                continue;
+            }
+        }
+        cmake_module.config_opts.push(config_opt);
+    }
+
+    let mut import_result = ImportResult::new(cmake_module);
+    import_result.unsupported_entries = unsupported_entries;
+    Ok(import_result)
+}
+
+pub struct CMakeImporter {}
+impl Importer for CMakeImporter {
+    fn get_name(&self) -> String {
+        "cmake".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        get_cmake_module(manifest_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_external_contents() {
+        let calls = get_cmake_function_calls(
+            r###"
+cmake_minimum_required(VERSION 3.14)
+project(my-app CXX)
+
+include(FetchContent)
+FetchContent_Declare(
+  googletest
+  URL https://github.com/google/googletest/archive/refs/tags/v1.14.0.tar.gz
+      https://mirror.example.org/googletest-1.14.0.tar.gz
+  URL_HASH SHA256=8ad598c73ad796e0d8280b082cebd82a630d73e73cd3c70057938a6501bba5d7
+)
+FetchContent_Declare(fmt
+  GIT_REPOSITORY https://github.com/fmtlib/fmt.git
+  GIT_TAG        f5e54359df4c26b6230fc61d38aa294581393084 # 10.1.1
+)
+ExternalProject_Add(json
+  GIT_REPOSITORY "https://github.com/nlohmann/json.git"
+  GIT_TAG v3.11.2
+)
+"###,
+        );
+        assert_eq!(
+            get_project_name("project(my-app CXX)"),
+            Some("my-app".to_string())
+        );
+
+        let external_contents: Vec<CMakeExternalContent> = calls
+            .iter()
+            .filter_map(|(n, a)| CMakeExternalContent::from_function_call(n, a))
+            .collect();
+        assert_eq!(external_contents.len(), 3);
+
+        let googletest_source = external_contents[0].get_source().unwrap();
+        assert_eq!(googletest_source.r#type, Some(FlatpakSourceType::Archive));
+        assert_eq!(googletest_source.mirror_urls.unwrap().len(), 1);
+        assert_eq!(
+            googletest_source.sha256,
+            Some("8ad598c73ad796e0d8280b082cebd82a630d73e73cd3c70057938a6501bba5d7".to_string())
+        );
+        assert_eq!(
+            googletest_source.dest,
+            Some("flatpak-fetchcontent/googletest".to_string())
+        );
+        assert_eq!(
+            external_contents[0].get_config_opt("my-app"),
+            Some(
+                "-DFETCHCONTENT_SOURCE_DIR_GOOGLETEST=/run/build/my-app/flatpak-fetchcontent/googletest"
+                    .to_string()
+            )
+        );
+
+        let fmt_source = external_contents[1].get_source().unwrap();
+        assert_eq!(
+            fmt_source.commit,
+            Some("f5e54359df4c26b6230fc61d38aa294581393084".to_string())
+        );
+
+        let json_source = external_contents[2].get_source().unwrap();
+        assert_eq!(
+            json_source.url,
+            Some("https://github.com/nlohmann/json.git".to_string())
+        );
+        assert_eq!(json_source.tag, Some("v3.11.2".to_string()));
+        assert_eq!(external_contents[2].get_config_opt("my-app"), None);
+    }
+
+    #[test]
+    pub fn test_get_cmake_module() {
+        let project_dir = std::env::temp_dir().join(format!("fpm-test-cmake-module-{}", std::process::id()));
+        fs::create_dir_all(&project_dir).unwrap();
+        let cmake_lists_path = project_dir.join("CMakeLists.txt");
+        fs::write(
+            &cmake_lists_path,
+            r###"
+project(my-app CXX)
+FetchContent_Declare(fmt
+  GIT_REPOSITORY https://github.com/fmtlib/fmt.git
+  GIT_TAG        f5e54359df4c26b6230fc61d38aa294581393084
+)
+ExternalProject_Add(json
+  GIT_REPOSITORY "https://github.com/nlohmann/json.git"
+  GIT_TAG v3.11.2
+)
+FetchContent_Declare(zlib URL https://zlib.net/zlib-${ZLIB_VERSION}.tar.gz)
+"###,
+        )
+        .unwrap();
+
+        let import_result = get_cmake_module(cmake_lists_path.to_str().unwrap()).unwrap();
+        assert_eq!(import_result.module.name, "my-app");
+        // The project itself and fmt.
+        assert_eq!(import_result.module.sources.len(), 2);
+        assert_eq!(import_result.module.config_opts.len(), 2);
+        assert_eq!(
+            import_result.unsupported_entries,
+            vec![
+                "json (ExternalProject_Add)".to_string(),
+                "zlib uses variables in https://zlib.net/zlib-${ZLIB_VERSION}.tar.gz, which are not supported."
+                    .to_string()
+            ]
+        );
+
+        fs::remove_dir_all(&project_dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_get_source_errors() {
+        let calls = get_cmake_function_calls(
+            r###"
+FetchContent_Declare(nohash URL https://example.org/nohash.tar.gz)
+FetchContent_Declare(md5 URL https://example.org/md5.tar.gz URL_HASH MD5=d41d8cd98f00b204e9800998ecf8427e)
+FetchContent_Declare(variable URL https://example.org/variable-${VERSION}.tar.gz URL_HASH SHA256=abcd)
+"###,
+        );
+        for (function_name, args) in calls {
+            let external_content = CMakeExternalContent::from_function_call(&function_name, &args).unwrap();
+            assert!(external_content.get_source().is_err());
+        }
+    }
+}
@@ -0,0 +1,402 @@
+//! External importers are executables named `fpm-importer-<name>` found in the PATH.
+//!
+//! fpm communicates with them by writing a single JSON request to their standard input
+//! and reading a single JSON response from their standard output. When none of the builtin
+//! importers handles a dependency manifest, the importers are asked to describe themselves,
+//! and have to answer within a few seconds:
+//!
+//! ```json
+//! {"command": "describe"}
+//! {"name": "conan", "file-names": ["conanfile.txt", "conan.lock"]}
+//! ```
+//!
+//! and are then asked to import the dependency manifests they handle:
+//!
+//! ```json
+//! {"command": "import", "manifest-path": "./conan.lock", "options": {"features": [], "packages": [], "targets": []}}
+//! {"module": {"name": "conan-dependencies", "sources": []}, "unsupported-entries": []}
+//! ```
+//!
+//! Errors are reported with `{"error": "..."}` or with a non-zero exit code.
+use std::cell::OnceCell;
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use flatpak_rs::module::FlatpakModule;
+use serde::{Deserialize, Serialize};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const EXTERNAL_IMPORTER_PREFIX: &str = "fpm-importer-";
+/// The time an importer has to describe itself. The imports are not limited in time, since
+/// they can require network access.
+pub const DESCRIBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ExternalImporterRequest {
+    Describe,
+    Import {
+        #[serde(rename = "manifest-path")]
+        manifest_path: String,
+        options: ImportOptions,
+    },
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct ExternalImporterDescription {
+    pub name: String,
+    /// The names of the dependency manifests handled by the importer.
+    pub file_names: Vec<String>,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct ExternalImporterResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module: Option<FlatpakModule>,
+    pub unsupported_entries: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ExternalImporter {
+    name: String,
+    executable_path: path::PathBuf,
+    /// The description of the importer, only requested when it is needed.
+    description: OnceCell<Option<ExternalImporterDescription>>,
+}
+impl ExternalImporter {
+    /// Creates an importer from an executable. The name of the importer is the name of the
+    /// executable without the `fpm-importer-` prefix.
+    pub fn from_executable(executable_path: &path::Path) -> Option<ExternalImporter> {
+        let name = executable_path
+            .file_name()?
+            .to_str()?
+            .strip_prefix(EXTERNAL_IMPORTER_PREFIX)?;
+        if name.is_empty() {
+            return None;
+        }
+        Some(ExternalImporter {
+            name: name.to_lowercase(),
+            executable_path: executable_path.to_path_buf(),
+            description: OnceCell::new(),
+        })
+    }
+
+    pub fn get_path(&self) -> &path::Path {
+        &self.executable_path
+    }
+
+    /// Gets the description of the importer, asking the importer for it on the first call.
+    pub fn get_description(&self) -> Option<&ExternalImporterDescription> {
+        self.description
+            .get_or_init(|| match self.describe() {
+                Ok(d) => Some(d),
+                Err(e) => {
+                    log::warn!(
+                        "Could not load external importer {}: {}",
+                        self.executable_path.display(),
+                        e
+                    );
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    fn describe(&self) -> Result<ExternalImporterDescription, String> {
+        let output = call_executable(
+            &self.executable_path,
+            &ExternalImporterRequest::Describe,
+            Some(DESCRIBE_TIMEOUT),
+        )?;
+        let description: ExternalImporterDescription = match serde_json::from_str(&output) {
+            Ok(d) => d,
+            Err(e) => return Err(format!("Invalid description: {}", e)),
+        };
+        if description.name != self.name {
+            return Err(format!(
+                "The importer described itself as {}, but its executable is named after {}.",
+                description.name, self.name
+            ));
+        }
+        Ok(description)
+    }
+}
+impl Importer for ExternalImporter {
+    fn get_name(&self) -> String {
+        self.name.to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        let file_name = match path::Path::new(manifest_path).file_name() {
+            Some(f) => f.to_str().unwrap_or(""),
+            None => return false,
+        };
+        match self.get_description() {
+            Some(d) => d.file_names.iter().any(|f| f == file_name),
+            None => false,
+        }
+    }
+
+    fn import(&self, manifest_path: &str, options: &ImportOptions) -> Result<ImportResult, String> {
+        let request = ExternalImporterRequest::Import {
+            manifest_path: manifest_path.to_string(),
+            options: options.clone(),
+        };
+        let output = call_executable(&self.executable_path, &request, None)?;
+        let response: ExternalImporterResponse = match serde_json::from_str(&output) {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Invalid response from {}: {}", self.get_name(), e)),
+        };
+        if let Some(error) = response.error {
+            return Err(error);
+        }
+        match response.module {
+            Some(module) => Ok(ImportResult {
+                module,
+                unsupported_entries: response.unsupported_entries,
+            }),
+            None => Err(format!(
+                "The {} importer did not return a module.",
+                self.get_name()
+            )),
+        }
+    }
+}
+
+fn call_executable(
+    executable_path: &path::Path,
+    request: &ExternalImporterRequest,
+    timeout: Option<Duration>,
+) -> Result<String, String> {
+    let request = match serde_json::to_string(request) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("Could not serialize request: {}", e)),
+    };
+
+    let mut child = match Command::new(executable_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not run {}: {}", executable_path.display(), e)),
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(e) = writeln!(stdin, "{}", request) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("Could not write to {}: {}", executable_path.display(), e));
+        }
+    }
+
+    // The output is read in another thread, so that the importer does not block on a full pipe
+    // while we wait for it to exit.
+    let mut stdout = child.stdout.take();
+    let output_reader = thread::spawn(move || {
+        let mut output: Vec<u8> = vec![];
+        if let Some(stdout) = stdout.as_mut() {
+            let _ = stdout.read_to_end(&mut output);
+        }
+        output
+    });
+
+    let status = match timeout {
+        Some(t) => wait_with_timeout(&mut child, t),
+        None => child.wait().map(Some),
+    };
+    let status = match status {
+        Ok(Some(s)) => s,
+        Ok(None) => {
+            return Err(format!(
+                "{} did not answer within {} seconds.",
+                executable_path.display(),
+                timeout.unwrap_or_default().as_secs_f32()
+            ))
+        }
+        Err(e) => return Err(format!("Could not run {}: {}", executable_path.display(), e)),
+    };
+    let output = output_reader.join().unwrap_or_default();
+    if !status.success() {
+        return Err(format!("{} exited with {}", executable_path.display(), status));
+    }
+    match String::from_utf8(output) {
+        Ok(o) => Ok(o),
+        Err(e) => Err(format!(
+            "Invalid output from {}: {}",
+            executable_path.display(),
+            e
+        )),
+    }
+}
+
+/// Waits for the child to exit, and kills it if it is still running after the timeout.
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> std::io::Result<Option<ExitStatus>> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() > timeout {
+            child.kill()?;
+            child.wait()?;
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+fn is_executable(file_path: &path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match fs::metadata(file_path) {
+        Ok(m) => m.is_file() && m.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+/// Finds the external importers in the PATH. When multiple executables have the same
+/// name, the first one found is used, like a shell would. The importers are not run
+/// until they are needed.
+pub fn get_external_importers() -> Vec<ExternalImporter> {
+    let path_var = match env::var_os("PATH") {
+        Some(p) => p,
+        None => return vec![],
+    };
+
+    let mut executable_names: Vec<String> = vec![];
+    let mut importers: Vec<ExternalImporter> = vec![];
+    for dir in env::split_paths(&path_var) {
+        let dir_entries = match fs::read_dir(&dir) {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        let mut executable_paths: Vec<path::PathBuf> = dir_entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|f| f.to_str())
+                    .is_some_and(|f| f.starts_with(EXTERNAL_IMPORTER_PREFIX))
+            })
+            .collect();
+        executable_paths.sort();
+
+        for executable_path in executable_paths {
+            let executable_name = executable_path.file_name().unwrap().to_str().unwrap().to_string();
+            if executable_names.contains(&executable_name) || !is_executable(&executable_path) {
+                continue;
+            }
+            executable_names.push(executable_name);
+
+            if let Some(importer) = ExternalImporter::from_executable(&executable_path) {
+                log::debug!("Found external importer {}.", importer.get_name());
+                importers.push(importer);
+            }
+        }
+    }
+    importers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_serialize_import_request() {
+        let request = ExternalImporterRequest::Import {
+            manifest_path: "./conan.lock".to_string(),
+            options: ImportOptions::default(),
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"command":"import","manifest-path":"./conan.lock","options":{"features":[],"packages":[],"targets":[]}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&ExternalImporterRequest::Describe).unwrap(),
+            r#"{"command":"describe"}"#
+        );
+    }
+
+    #[test]
+    pub fn test_external_importer() {
+        let importer_dir =
+            std::env::temp_dir().join(format!("fpm-test-external-importer-{}", std::process::id()));
+        fs::create_dir_all(&importer_dir).unwrap();
+        let importer_path = importer_dir.join("fpm-importer-conan");
+        fs::write(
+            &importer_path,
+            r#"#!/bin/sh
+read request
+case "$request" in
+  *describe*) echo '{"name": "conan", "file-names": ["conan.lock"]}' ;;
+  *) echo '{"module": {"name": "conan-dependencies"}, "unsupported-entries": ["zlib/1.2.13"]}' ;;
+esac
+"#,
+        )
+        .unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&importer_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let importer = ExternalImporter::from_executable(&importer_path).unwrap();
+        assert_eq!(importer.get_name(), "conan");
+        assert!(importer.description.get().is_none());
+        let matches_lockfile = importer.file_path_matches("./third-party/conan.lock");
+        let matches_conanfile = importer.file_path_matches("./conanfile.py");
+        let import_result = importer.import("./conan.lock", &ImportOptions::default());
+        let _ = fs::remove_dir_all(&importer_dir);
+
+        assert!(matches_lockfile);
+        assert!(!matches_conanfile);
+        let import_result = import_result.unwrap();
+        assert_eq!(import_result.module.name, "conan-dependencies");
+        assert_eq!(import_result.unsupported_entries, vec!["zlib/1.2.13".to_string()]);
+    }
+
+    #[test]
+    pub fn test_describe_timeout() {
+        let importer_dir = std::env::temp_dir().join(format!(
+            "fpm-test-external-importer-timeout-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&importer_dir).unwrap();
+        let importer_path = importer_dir.join("fpm-importer-slow");
+        fs::write(&importer_path, "#!/bin/sh\nsleep 5\n").unwrap();
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&importer_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let start = Instant::now();
+        let output = call_executable(
+            &importer_path,
+            &ExternalImporterRequest::Describe,
+            Some(Duration::from_millis(200)),
+        );
+        let _ = fs::remove_dir_all(&importer_dir);
+
+        assert!(output.unwrap_err().contains("did not answer"));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
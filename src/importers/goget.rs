@@ -1,12 +1,659 @@
-pub struct GoGet {}
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path;
 
-impl GoGet {
+use flatpak_rs::archive::FlatpakArchiveType;
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const GO_PROXY_URL: &str = "https://proxy.golang.org";
+pub const DEFAULT_DESTINATION: &str = "vendor";
+pub const MODULES_TXT_SCRIPT_NAME: &str = "generate-modules-txt.sh";
+
+/// Main structure for a Go modules manifest (`go.mod`).
+/// See https://go.dev/ref/mod#go-mod-file
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GoModManifest {
+    pub module: String,
+
+    pub go_version: Option<String>,
+
+    pub requires: Vec<GoModRequirement>,
+
+    pub replaces: Vec<GoModReplacement>,
+}
+impl GoModManifest {
     pub fn file_path_matches(path: &str) -> bool {
-        false
+        path.ends_with("go.mod")
+    }
+
+    pub fn parse(manifest_path: &str, manifest_content: &str) -> Result<GoModManifest, String> {
+        if !GoModManifest::file_path_matches(manifest_path) {
+            return Err(format!("{} is not a go.mod file.", manifest_path));
+        }
+
+        let mut go_mod = GoModManifest::default();
+        let mut current_block: Option<String> = None;
+
+        for (line_number, line) in manifest_content.lines().enumerate() {
+            let (line, comment) = match line.find("//") {
+                Some(i) => (&line[..i], &line[i + 2..]),
+                None => (line, ""),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (directive, args) = match &current_block {
+                Some(block) => {
+                    if line == ")" {
+                        current_block = None;
+                        continue;
+                    }
+                    (block.to_string(), line.to_string())
+                }
+                None => {
+                    let (directive, args) = match line.split_once(char::is_whitespace) {
+                        Some((d, a)) => (d.to_string(), a.trim().to_string()),
+                        None => (line.to_string(), "".to_string()),
+                    };
+                    if args == "(" {
+                        current_block = Some(directive);
+                        continue;
+                    }
+                    (directive, args)
+                }
+            };
+            let args: Vec<&str> = args.split_whitespace().map(|a| a.trim_matches('"')).collect();
+
+            match directive.as_str() {
+                "module" => {
+                    go_mod.module = args.first().unwrap_or(&"").to_string();
+                }
+                "go" => {
+                    go_mod.go_version = args.first().map(|v| v.to_string());
+                }
+                "require" => {
+                    if args.len() != 2 {
+                        return Err(format!("Invalid require directive on line {}.", line_number + 1));
+                    }
+                    go_mod.requires.push(GoModRequirement {
+                        path: args[0].to_string(),
+                        version: args[1].to_string(),
+                        indirect: comment.trim() == "indirect",
+                    });
+                }
+                "replace" => {
+                    let arrow_index = match args.iter().position(|a| *a == "=>") {
+                        Some(i) => i,
+                        None => {
+                            return Err(format!("Invalid replace directive on line {}.", line_number + 1));
+                        }
+                    };
+                    let (old, new) = (&args[..arrow_index], &args[arrow_index + 1..]);
+                    if old.is_empty() || old.len() > 2 || new.is_empty() || new.len() > 2 {
+                        return Err(format!("Invalid replace directive on line {}.", line_number + 1));
+                    }
+                    go_mod.replaces.push(GoModReplacement {
+                        old_path: old[0].to_string(),
+                        old_version: old.get(1).map(|v| v.to_string()),
+                        new_path: new[0].to_string(),
+                        new_version: new.get(1).map(|v| v.to_string()),
+                    });
+                }
+                // Those directives do not have an impact on the vendored modules.
+                "exclude" | "retract" | "toolchain" | "godebug" => {}
+                _ => {
+                    log::warn!(
+                        "Unknown go.mod directive {} on line {}.",
+                        directive,
+                        line_number + 1
+                    );
+                }
+            }
+        }
+
+        if go_mod.module.is_empty() {
+            return Err("Required module directive is missing from go.mod.".to_string());
+        }
+
+        Ok(go_mod)
     }
 
-    pub fn parse(manifest_path: &str, manifest_content: &str) -> Result<GoGet, String> {
-        Err("Not implemented yet.".to_string())
+    /// Gets the replacement for a required module, if any. A replacement for a
+    /// specific version takes precedence over a replacement for all the versions.
+    pub fn get_replacement(&self, requirement: &GoModRequirement) -> Option<&GoModReplacement> {
+        let versioned_replacement = self
+            .replaces
+            .iter()
+            .find(|r| r.old_path == requirement.path && r.old_version.as_ref() == Some(&requirement.version));
+        if versioned_replacement.is_some() {
+            return versioned_replacement;
+        }
+        self.replaces
+            .iter()
+            .find(|r| r.old_path == requirement.path && r.old_version.is_none())
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GoModRequirement {
+    pub path: String,
+    pub version: String,
+    pub indirect: bool,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GoModReplacement {
+    pub old_path: String,
+    pub old_version: Option<String>,
+    pub new_path: String,
+    pub new_version: Option<String>,
+}
+impl GoModReplacement {
+    /// Replacements can point to a directory instead of another module.
+    pub fn is_local(&self) -> bool {
+        self.new_path.starts_with("./") || self.new_path.starts_with("../") || self.new_path.starts_with('/')
+    }
+}
+
+/// The checksums of the modules, from a `go.sum` file.
+/// See https://go.dev/ref/mod#go-sum-files
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GoSum {
+    pub entries: Vec<GoSumEntry>,
+}
+impl GoSum {
+    pub fn parse(go_sum_content: &str) -> Result<GoSum, String> {
+        let mut go_sum = GoSum::default();
+        for (line_number, line) in go_sum_content.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.is_empty() {
+                continue;
+            }
+            if parts.len() != 3 {
+                return Err(format!("Invalid go.sum entry on line {}.", line_number + 1));
+            }
+            let (version, go_mod_only) = match parts[1].strip_suffix("/go.mod") {
+                Some(v) => (v, true),
+                None => (parts[1], false),
+            };
+            go_sum.entries.push(GoSumEntry {
+                path: parts[0].to_string(),
+                version: version.to_string(),
+                go_mod_only,
+                hash: parts[2].to_string(),
+            });
+        }
+        Ok(go_sum)
+    }
+
+    pub fn get_module_hash(&self, path: &str, version: &str) -> Option<String> {
+        self.entries
+            .iter()
+            .find(|e| e.path == path && e.version == version && !e.go_mod_only)
+            .map(|e| e.hash.to_string())
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct GoSumEntry {
+    pub path: String,
+    pub version: String,
+    /// Whether the entry is only for the `go.mod` file of the module.
+    pub go_mod_only: bool,
+    pub hash: String,
+}
+
+/// Escapes a module path or version the way the module proxy and the module cache
+/// expect it, with every uppercase letter replaced by an exclamation mark followed
+/// by the lowercase letter.
+pub fn escape_module_path(module_path: &str) -> String {
+    let mut escaped_path = String::new();
+    for c in module_path.chars() {
+        if c.is_ascii_uppercase() {
+            escaped_path.push('!');
+            escaped_path.push(c.to_ascii_lowercase());
+        } else {
+            escaped_path.push(c);
+        }
+    }
+    escaped_path
+}
+
+/// Gets the commit of a pseudo-version, for example `v0.0.0-20191109021931-daa7c04131f5`.
+/// See https://go.dev/ref/mod#pseudo-versions
+pub fn get_pseudo_version_commit(version: &str) -> Option<String> {
+    let version = version.trim_end_matches("+incompatible");
+    let parts: Vec<&str> = version.rsplitn(3, '-').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let (commit, timestamp) = (parts[0], parts[1]);
+    let timestamp = timestamp.rsplit('.').next().unwrap_or(timestamp);
+    if commit.len() != 12 || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    if timestamp.len() != 14 || !timestamp.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(commit.to_string())
+}
+
+/// Gets the git repository hosting a module, along with the path of the module
+/// inside the repository. Only the hosts with a predictable layout are supported.
+pub fn get_module_git_repository(module_path: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = module_path.split('/').collect();
+    match parts[0] {
+        "github.com" | "gitlab.com" | "bitbucket.org" => {
+            if parts.len() < 3 {
+                return None;
+            }
+            Some((
+                format!("https://{}/{}/{}.git", parts[0], parts[1], parts[2]),
+                parts[3..].join("/"),
+            ))
+        }
+        "golang.org" => {
+            if parts.len() < 3 || parts[1] != "x" {
+                return None;
+            }
+            Some((
+                format!("https://go.googlesource.com/{}", parts[2]),
+                parts[3..].join("/"),
+            ))
+        }
+        "gopkg.in" => {
+            // gopkg.in/pkg.v3 is hosted at github.com/go-pkg/pkg, and
+            // gopkg.in/user/pkg.v3 is hosted at github.com/user/pkg.
+            let (user, package) = match parts.len() {
+                2 => (None, parts[1]),
+                3 => (Some(parts[1]), parts[2]),
+                _ => return None,
+            };
+            let package = package.split(".v").next().unwrap_or(package);
+            let user = user.map(|u| u.to_string()).unwrap_or(format!("go-{}", package));
+            Some((
+                format!("https://github.com/{}/{}.git", user, package),
+                "".to_string(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Gets the directory of the local module cache, as populated by `go mod download`.
+pub fn get_module_cache_dir() -> Option<path::PathBuf> {
+    if let Ok(module_cache_dir) = env::var("GOMODCACHE") {
+        return Some(path::PathBuf::from(module_cache_dir));
+    }
+    if let Ok(go_path) = env::var("GOPATH") {
+        if let Some(go_path) = go_path.split(':').next() {
+            return Some(path::Path::new(go_path).join("pkg").join("mod"));
+        }
+    }
+    match env::var("HOME") {
+        Ok(home) => Some(path::Path::new(&home).join("go").join("pkg").join("mod")),
+        Err(_) => None,
+    }
+}
+
+fn is_major_version_suffix(path_element: &str) -> bool {
+    path_element.len() > 1 && path_element.starts_with('v') && path_element[1..].parse::<u32>().is_ok()
+}
+
+fn get_go_proxy_url() -> String {
+    if let Ok(go_proxy) = env::var("GOPROXY") {
+        for proxy in go_proxy.split([',', '|']) {
+            if proxy.starts_with("https://") || proxy.starts_with("http://") {
+                return proxy.trim_end_matches('/').to_string();
+            }
+        }
+    }
+    GO_PROXY_URL.to_string()
+}
+
+/// Gets the go version declared by a module, using the `go.mod` file stored
+/// in the local module cache.
+fn get_cached_module_go_version(
+    module_cache_dir: &path::Path,
+    module_path: &str,
+    version: &str,
+) -> Option<String> {
+    let go_mod_path = module_cache_dir
+        .join("cache/download")
+        .join(escape_module_path(module_path))
+        .join("@v")
+        .join(format!("{}.mod", escape_module_path(version)));
+    let go_mod_content = fs::read_to_string(go_mod_path).ok()?;
+    let go_mod = GoModManifest::parse("go.mod", &go_mod_content).ok()?;
+    go_mod.go_version
+}
+
+/// Gets the full commit of a module version from the references of its git repository.
+/// Returns the tag of the version, if it is not a pseudo-version, with the commit.
+pub fn resolve_module_commit(
+    refs: &BTreeMap<String, String>,
+    module_path: &str,
+    version: &str,
+) -> Result<(Option<String>, String), String> {
+    if let Some(abbreviated_commit) = get_pseudo_version_commit(version) {
+        // Pseudo-versions only contain an abbreviated commit hash.
+        return match refs.values().find(|c| c.starts_with(&abbreviated_commit)) {
+            Some(c) => Ok((None, c.to_string())),
+            None => Err(format!(
+                "Could not resolve commit {} of module {}@{}, since no branch or tag points to it. Run `go mod download` first.",
+                abbreviated_commit, module_path, version
+            )),
+        };
+    }
+    let tag = version.trim_end_matches("+incompatible").to_string();
+    match refs.get(&format!("refs/tags/{}", tag)) {
+        Some(c) => Ok((Some(tag), c.to_string())),
+        None => Err(format!(
+            "Could not find tag {} of module {}@{}.",
+            tag, module_path, version
+        )),
+    }
+}
+
+/// Gets a pinned source for a module. The module zip from the module proxy is used
+/// when it is available in the local module cache, after checking that it matches the
+/// checksum from `go.sum`, which cannot be used by flatpak-builder directly. Otherwise,
+/// we fallback to the git repository of the module, pinned to a full commit.
+pub fn get_module_source(
+    module_path: &str,
+    version: &str,
+    go_sum_hash: &str,
+    dest: &str,
+) -> Result<FlatpakSource, String> {
+    let escaped_path = escape_module_path(module_path);
+    let escaped_version = escape_module_path(version);
+
+    if let Some(module_cache_dir) = get_module_cache_dir() {
+        let module_zip_path = module_cache_dir
+            .join("cache/download")
+            .join(&escaped_path)
+            .join("@v")
+            .join(format!("{}.zip", escaped_version));
+        // The go command stores the go.sum checksum of every zip it downloads.
+        let ziphash_path = module_zip_path.with_extension("ziphash");
+        let zip_hash = fs::read_to_string(&ziphash_path)
+            .ok()
+            .map(|h| h.trim().to_string());
+        if module_zip_path.is_file() {
+            match zip_hash {
+                Some(h) if h != go_sum_hash => {
+                    return Err(format!(
+                        "The zip of module {}@{} in the module cache does not match go.sum ({} instead of {}).",
+                        module_path, version, h, go_sum_hash
+                    ))
+                }
+                Some(_) => {}
+                None => log::warn!(
+                    "Could not read {}, the zip of module {}@{} is not checked against go.sum.",
+                    ziphash_path.display(),
+                    module_path,
+                    version
+                ),
+            }
+            let sha256 = crate::utils::get_file_sha256(&module_zip_path)?;
+            // The files in the module zip are prefixed with module@version/.
+            let strip_components = module_path.split('/').count() as i64;
+            return Ok(FlatpakSource {
+                r#type: Some(FlatpakSourceType::Archive),
+                archive_type: Some(FlatpakArchiveType::Zip),
+                url: Some(format!(
+                    "{}/{}/@v/{}.zip",
+                    get_go_proxy_url(),
+                    escaped_path,
+                    escaped_version
+                )),
+                sha256: Some(sha256),
+                strip_components: Some(strip_components),
+                dest: Some(dest.to_string()),
+                ..Default::default()
+            });
+        }
+    }
+
+    let (repository_url, module_subdir) = match get_module_git_repository(module_path) {
+        Some(r) => r,
+        None => {
+            return Err(format!(
+                "Module {}@{} is not in the module cache and its repository could not be determined. Run `go mod download` first.",
+                module_path, version
+            ))
+        }
+    };
+
+    // Major version suffixes are not part of the directory layout of the repository.
+    let mut module_subdir_parts: Vec<&str> = module_subdir.split('/').filter(|p| !p.is_empty()).collect();
+    if let Some(last_part) = module_subdir_parts.last() {
+        if is_major_version_suffix(last_part) {
+            module_subdir_parts.pop();
+        }
+    }
+    if !module_subdir_parts.is_empty() {
+        return Err(format!(
+            "Module {}@{} is in a sub-directory of {}, which is not supported without the module cache. Run `go mod download` first.",
+            module_path, version, repository_url
+        ));
+    }
+
+    let refs = crate::utils::get_git_remote_refs(&repository_url)?;
+    let (tag, commit) = resolve_module_commit(&refs, module_path, version)?;
+    Ok(FlatpakSource {
+        r#type: Some(FlatpakSourceType::Git),
+        url: Some(repository_url),
+        tag,
+        commit: Some(commit),
+        dest: Some(dest.to_string()),
+        ..Default::default()
+    })
+}
+
+/// Gets the sources for all the modules required by the main module, laid out
+/// as a `vendor/` tree. The last source is a script generating `vendor/modules.txt`.
+/// Gets the command listing the packages of a module from the vendor directory. Every
+/// directory containing go files is a package of the module, except for the directories of
+/// the nested modules, which are the other required modules under the module path and the
+/// directories with their own `go.mod` file.
+pub fn get_packages_command(module_path: &str, nested_module_paths: &[&str]) -> String {
+    let mut nested_module_filters: Vec<String> = nested_module_paths
+        .iter()
+        .map(|p| format!("-path {}", p))
+        .collect();
+    nested_module_filters.push("-exec test -e {}/go.mod \\;".to_string());
+    format!(
+        "find {} -mindepth 1 -type d \\( {} \\) -prune -o -name '*.go' ! -name '*_test.go' ! -path '*/testdata/*' -exec dirname {{}} \\; | sort -u",
+        module_path,
+        nested_module_filters.join(" -o ")
+    )
+}
+
+pub fn get_sources(go_mod: &GoModManifest, go_sum: &GoSum) -> Result<Vec<FlatpakSource>, String> {
+    let mut sources: Vec<FlatpakSource> = vec![];
+    let mut modules_txt_commands: Vec<String> = vec![];
+    let module_cache_dir = get_module_cache_dir();
+
+    modules_txt_commands.push(format!("rm -f {}/modules.txt", DEFAULT_DESTINATION));
+    for requirement in &go_mod.requires {
+        if requirement.indirect {
+            log::debug!("Module {} is an indirect dependency.", requirement.path);
+        }
+        let dest = format!("{}/{}", DEFAULT_DESTINATION, requirement.path);
+        let mut module_line = format!("# {} {}", requirement.path, requirement.version);
+
+        let (source_path, source_version) = match go_mod.get_replacement(requirement) {
+            Some(replacement) => {
+                module_line.push_str(&format!(" => {}", replacement.new_path));
+                if let Some(new_version) = &replacement.new_version {
+                    module_line.push_str(&format!(" {}", new_version));
+                }
+                if replacement.is_local() {
+                    modules_txt_commands.push(format!(
+                        "mkdir -p {} && cp -r {}/. {}/",
+                        dest, replacement.new_path, dest
+                    ));
+                    (None, None)
+                } else {
+                    (
+                        Some(replacement.new_path.to_string()),
+                        Some(
+                            replacement
+                                .new_version
+                                .clone()
+                                .unwrap_or(requirement.version.to_string()),
+                        ),
+                    )
+                }
+            }
+            None => (
+                Some(requirement.path.to_string()),
+                Some(requirement.version.to_string()),
+            ),
+        };
+
+        let mut go_version: Option<String> = None;
+        if let (Some(source_path), Some(source_version)) = (source_path, source_version) {
+            let go_sum_hash = match go_sum.get_module_hash(&source_path, &source_version) {
+                Some(h) => h,
+                None => {
+                    return Err(format!(
+                        "Missing go.sum entry for module {}@{}.",
+                        source_path, source_version
+                    ))
+                }
+            };
+            sources.push(get_module_source(
+                &source_path,
+                &source_version,
+                &go_sum_hash,
+                &dest,
+            )?);
+            if let Some(module_cache_dir) = &module_cache_dir {
+                go_version = get_cached_module_go_version(module_cache_dir, &source_path, &source_version);
+            }
+        }
+
+        modules_txt_commands.push(format!(
+            "echo '{}' >> {}/modules.txt",
+            module_line, DEFAULT_DESTINATION
+        ));
+        match go_version {
+            Some(v) => modules_txt_commands.push(format!(
+                "echo '## explicit; go {}' >> {}/modules.txt",
+                v, DEFAULT_DESTINATION
+            )),
+            None => modules_txt_commands.push(format!(
+                "echo '## explicit' >> {}/modules.txt",
+                DEFAULT_DESTINATION
+            )),
+        }
+        let nested_module_paths: Vec<&str> = go_mod
+            .requires
+            .iter()
+            .map(|r| r.path.as_str())
+            .filter(|p| p.starts_with(&format!("{}/", requirement.path)))
+            .collect();
+        modules_txt_commands.push(format!(
+            "(cd {} && {}) >> {}/modules.txt",
+            DEFAULT_DESTINATION,
+            get_packages_command(&requirement.path, &nested_module_paths),
+            DEFAULT_DESTINATION
+        ));
+    }
+
+    sources.push(FlatpakSource {
+        r#type: Some(FlatpakSourceType::Script),
+        commands: Some(modules_txt_commands),
+        dest_filename: Some(MODULES_TXT_SCRIPT_NAME.to_string()),
+        ..Default::default()
+    });
+
+    Ok(sources)
+}
+
+pub fn get_go_module(go_mod: &GoModManifest, go_sum: &GoSum) -> Result<FlatpakModule, String> {
+    // The last element of the module path is the name of the project, unless it's
+    // a major version suffix.
+    let module_path_parts: Vec<&str> = go_mod.module.split('/').collect();
+    let mut module_name = module_path_parts[module_path_parts.len() - 1];
+    if module_path_parts.len() > 1 && is_major_version_suffix(module_name) {
+        module_name = module_path_parts[module_path_parts.len() - 2];
+    }
+
+    let mut go_module = FlatpakModule {
+        name: module_name.to_string(),
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        ..Default::default()
+    };
+    go_module
+        .build_commands
+        .push(format!("./{}", MODULES_TXT_SCRIPT_NAME));
+    go_module
+        .build_commands
+        .push("GOPROXY=off go build -mod=vendor -trimpath -o ${FLATPAK_DEST}/bin/ ./...".to_string());
+
+    go_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    for source in get_sources(go_mod, go_sum)? {
+        go_module.sources.push(FlatpakSourceItem::Description(source));
+    }
+    Ok(go_module)
+}
+
+pub struct GoImporter {}
+impl Importer for GoImporter {
+    fn get_name(&self) -> String {
+        "go".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        GoModManifest::file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        let go_mod_content = match fs::read_to_string(manifest_path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read go.mod file at {}: {}", manifest_path, e)),
+        };
+        let go_mod = GoModManifest::parse(manifest_path, &go_mod_content)?;
+
+        let go_sum_path = path::Path::new(manifest_path).with_file_name("go.sum");
+        let go_sum_content = match fs::read_to_string(&go_sum_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read go.sum file at {}: {}",
+                    go_sum_path.to_str().unwrap_or(""),
+                    e
+                ))
+            }
+        };
+        let go_sum = GoSum::parse(&go_sum_content)?;
+        Ok(ImportResult::new(get_go_module(&go_mod, &go_sum)?))
     }
 }
 
@@ -15,5 +662,162 @@ mod manifest_tests {
     use super::*;
 
     #[test]
-    pub fn test_parse_manifest() {}
+    pub fn test_parse_manifest() {
+        let go_mod = GoModManifest::parse(
+            "go.mod",
+            r###"
+            module github.com/louib/example/v2
+
+            go 1.17
+
+            require github.com/spf13/cobra v1.2.1
+
+            require (
+                golang.org/x/sys v0.0.0-20210630005230-0f9fa26af87c // indirect
+                gopkg.in/yaml.v3 v3.0.0 // indirect
+            )
+
+            replace (
+                github.com/spf13/cobra => github.com/louib/cobra v1.2.2
+                gopkg.in/yaml.v3 v3.0.0 => ./yaml
+            )
+
+            exclude golang.org/x/net v1.2.3
+            "###,
+        )
+        .unwrap();
+
+        assert_eq!(go_mod.module, "github.com/louib/example/v2");
+        assert_eq!(go_mod.go_version, Some("1.17".to_string()));
+        assert_eq!(go_mod.requires.len(), 3);
+        assert!(!go_mod.requires[0].indirect);
+        assert!(go_mod.requires[1].indirect);
+        assert_eq!(go_mod.replaces.len(), 2);
+
+        let replacement = go_mod.get_replacement(&go_mod.requires[0]).unwrap();
+        assert_eq!(replacement.new_path, "github.com/louib/cobra");
+        assert!(!replacement.is_local());
+        assert!(go_mod.get_replacement(&go_mod.requires[2]).unwrap().is_local());
+        assert!(go_mod.get_replacement(&go_mod.requires[1]).is_none());
+    }
+
+    #[test]
+    pub fn test_parse_missing_module() {
+        assert!(GoModManifest::parse("go.mod", "go 1.17").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_go_sum() {
+        let go_sum = GoSum::parse(
+            r###"
+github.com/spf13/cobra v1.2.1 h1:+KmjbUw1hriSNMF55oPrkZcb27aECyrj8V2ytv7kWDw=
+github.com/spf13/cobra v1.2.1/go.mod h1:ExllRjgxM/piMAM+3tAZvg8fsklGAf3tPfi+i8t68Nk=
+            "###,
+        )
+        .unwrap();
+        assert_eq!(go_sum.entries.len(), 2);
+        assert_eq!(
+            go_sum.get_module_hash("github.com/spf13/cobra", "v1.2.1"),
+            Some("h1:+KmjbUw1hriSNMF55oPrkZcb27aECyrj8V2ytv7kWDw=".to_string())
+        );
+        assert!(go_sum
+            .get_module_hash("github.com/spf13/cobra", "v1.2.0")
+            .is_none());
+    }
+
+    #[test]
+    pub fn test_get_pseudo_version_commit() {
+        assert_eq!(
+            get_pseudo_version_commit("v0.0.0-20210630005230-0f9fa26af87c"),
+            Some("0f9fa26af87c".to_string())
+        );
+        assert_eq!(
+            get_pseudo_version_commit("v1.2.4-0.20191109021931-daa7c04131f5+incompatible"),
+            Some("daa7c04131f5".to_string())
+        );
+        assert!(get_pseudo_version_commit("v1.2.3").is_none());
+        assert!(get_pseudo_version_commit("v1.2.3-beta-1").is_none());
+    }
+
+    #[test]
+    pub fn test_resolve_module_commit() {
+        let mut refs: BTreeMap<String, String> = BTreeMap::new();
+        refs.insert(
+            "refs/heads/main".to_string(),
+            "0f9fa26af87c3b1f1f7e5a0c1e7e0b6b6f2d1a3c".to_string(),
+        );
+        refs.insert(
+            "refs/tags/v1.2.1".to_string(),
+            "9e1d6f1c5e8a1e9a3f9a4b1c2d3e4f5a6b7c8d9e".to_string(),
+        );
+        assert_eq!(
+            resolve_module_commit(&refs, "example.com/m", "v0.0.0-20210630005230-0f9fa26af87c").unwrap(),
+            (None, "0f9fa26af87c3b1f1f7e5a0c1e7e0b6b6f2d1a3c".to_string())
+        );
+        assert_eq!(
+            resolve_module_commit(&refs, "example.com/m", "v1.2.1+incompatible").unwrap(),
+            (
+                Some("v1.2.1".to_string()),
+                "9e1d6f1c5e8a1e9a3f9a4b1c2d3e4f5a6b7c8d9e".to_string()
+            )
+        );
+        assert!(resolve_module_commit(&refs, "example.com/m", "v1.3.0").is_err());
+        assert!(resolve_module_commit(&refs, "example.com/m", "v0.0.0-20210630005230-abcdefabcdef").is_err());
+    }
+
+    #[test]
+    pub fn test_get_packages_command() {
+        let vendor_dir = std::env::temp_dir().join(format!("fpm-test-go-vendor-{}", std::process::id()));
+        for (file_path, content) in [
+            ("golang.org/x/tools/go.mod", "module golang.org/x/tools"),
+            ("golang.org/x/tools/tools.go", ""),
+            ("golang.org/x/tools/go/ast/ast.go", ""),
+            ("golang.org/x/tools/go/ast/ast_test.go", ""),
+            ("golang.org/x/tools/gopls/main.go", ""),
+            (
+                "golang.org/x/tools/cmd/stringer/go.mod",
+                "module golang.org/x/tools/cmd/stringer",
+            ),
+            ("golang.org/x/tools/cmd/stringer/main.go", ""),
+            ("golang.org/x/tools/cmd/godoc/main.go", ""),
+        ] {
+            let file_path = vendor_dir.join(file_path);
+            fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            fs::write(file_path, content).unwrap();
+        }
+
+        let packages_command = get_packages_command("golang.org/x/tools", &["golang.org/x/tools/gopls"]);
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(&packages_command)
+            .current_dir(&vendor_dir)
+            .output()
+            .unwrap();
+        fs::remove_dir_all(&vendor_dir).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "golang.org/x/tools\ngolang.org/x/tools/cmd/godoc\ngolang.org/x/tools/go/ast\n"
+        );
+    }
+
+    #[test]
+    pub fn test_get_module_git_repository() {
+        assert_eq!(
+            get_module_git_repository("github.com/spf13/cobra"),
+            Some(("https://github.com/spf13/cobra.git".to_string(), "".to_string()))
+        );
+        assert_eq!(
+            get_module_git_repository("golang.org/x/sys"),
+            Some(("https://go.googlesource.com/sys".to_string(), "".to_string()))
+        );
+        assert_eq!(
+            get_module_git_repository("gopkg.in/yaml.v3"),
+            Some(("https://github.com/go-yaml/yaml.git".to_string(), "".to_string()))
+        );
+        assert!(get_module_git_repository("example.com/module").is_none());
+        assert_eq!(
+            escape_module_path("github.com/BurntSushi/toml"),
+            "github.com/!burnt!sushi/toml"
+        );
+    }
 }
@@ -0,0 +1,924 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const MAVEN_CENTRAL_URL: &str = "https://repo.maven.apache.org/maven2";
+pub const GRADLE_PLUGINS_URL: &str = "https://plugins.gradle.org/m2";
+pub const GOOGLE_MAVEN_URL: &str = "https://dl.google.com/dl/android/maven2";
+/// The id of Maven Central in the Maven repositories.
+pub const MAVEN_CENTRAL_ID: &str = "central";
+/// The file in which Maven records the repository each file of the local repository was
+/// downloaded from.
+pub const REMOTE_REPOSITORIES_FILE_NAME: &str = "_remote.repositories";
+/// The Gradle scripts in which the repositories of the project are declared.
+pub const GRADLE_SCRIPT_NAMES: [&str; 4] = [
+    "settings.gradle",
+    "settings.gradle.kts",
+    "build.gradle",
+    "build.gradle.kts",
+];
+pub const DEFAULT_DESTINATION: &str = "maven-local";
+pub const DEPENDENCY_LIST_FILE_NAME: &str = "dependencies.txt";
+/// The local Maven repository in which the project's artifacts were downloaded, next to the `pom.xml`.
+pub const LOCAL_REPOSITORY_DIR_NAME: &str = ".flatpak-maven-repository";
+pub const GRADLE_INIT_SCRIPT_NAME: &str = "flatpak-init.gradle";
+/// The init script replacing the repositories of the Gradle build with the local repository,
+/// which is declared as `flatpakRepository` before it. The repositories declared in the settings
+/// are replaced once the settings are evaluated, and the repositories of the projects are only
+/// replaced when the settings allow them, since the builds using `FAIL_ON_PROJECT_REPOS` fail
+/// when a project declares a repository.
+pub const GRADLE_INIT_SCRIPT: [&str; 15] = [
+    "def preferProjectRepositories = true",
+    "settingsEvaluated { settings ->",
+    "    settings.pluginManagement.repositories { clear(); maven { url flatpakRepository } }",
+    "    if (settings.hasProperty(\"dependencyResolutionManagement\")) {",
+    "        def dependencyResolutionManagement = settings.dependencyResolutionManagement",
+    "        dependencyResolutionManagement.repositories { clear(); maven { url flatpakRepository } }",
+    "        preferProjectRepositories = dependencyResolutionManagement.repositoriesMode.get().name() == \"PREFER_PROJECT\"",
+    "    }",
+    "}",
+    "allprojects {",
+    "    buildscript.repositories { clear(); maven { url flatpakRepository } }",
+    "    if (preferProjectRepositories) {",
+    "        repositories { clear(); maven { url flatpakRepository } }",
+    "    }",
+    "}",
+];
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub enum JavaBuildTool {
+    #[default]
+    Maven,
+    Gradle,
+}
+
+/// An artifact of a Maven repository, identified by its coordinates.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub struct MavenArtifact {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub extension: String,
+    pub classifier: Option<String>,
+}
+impl MavenArtifact {
+    /// Gets the directory of the artifact in the Maven repository layout.
+    pub fn get_repository_dir(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.group_id.replace('.', "/"),
+            self.artifact_id,
+            self.version
+        )
+    }
+
+    pub fn get_filename(&self) -> String {
+        match &self.classifier {
+            Some(c) => format!("{}-{}-{}.{}", self.artifact_id, self.version, c, self.extension),
+            None => format!("{}-{}.{}", self.artifact_id, self.version, self.extension),
+        }
+    }
+
+    /// Gets the pom of the artifact, which is needed by the build tools to resolve the artifact
+    /// from the repository.
+    pub fn get_pom(&self) -> MavenArtifact {
+        MavenArtifact {
+            extension: "pom".to_string(),
+            classifier: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// A file of the local Maven repository, with its checksum.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct MavenRepositoryFile {
+    /// The directory of the file in the Maven repository layout.
+    pub dir: String,
+    pub filename: String,
+    pub sha256: String,
+    /// The content of the metadata files, which are generated by Maven from the metadata of
+    /// the remote repository when it was populated, and cannot be downloaded again.
+    pub contents: Option<String>,
+    /// The URLs of the repositories from which the file can be downloaded, in order of
+    /// preference. Maven Central is used when empty.
+    pub repository_urls: Vec<String>,
+}
+impl MavenRepositoryFile {
+    pub fn get_source(&self) -> FlatpakSource {
+        if let Some(contents) = &self.contents {
+            let file_dir = format!("{}/{}", DEFAULT_DESTINATION, self.dir);
+            return FlatpakSource {
+                r#type: Some(FlatpakSourceType::Shell),
+                commands: Some(vec![
+                    format!("mkdir -p {}", file_dir),
+                    format!(
+                        "printf '%s' '{}' > {}/{}",
+                        contents.replace('\'', "'\\''"),
+                        file_dir,
+                        self.filename
+                    ),
+                ]),
+                ..Default::default()
+            };
+        }
+        let mut urls: Vec<String> = self
+            .repository_urls
+            .iter()
+            .map(|r| format!("{}/{}/{}", r.trim_end_matches('/'), self.dir, self.filename))
+            .collect();
+        if urls.is_empty() {
+            urls.push(format!("{}/{}/{}", MAVEN_CENTRAL_URL, self.dir, self.filename));
+        }
+        let url = urls.remove(0);
+        FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(url),
+            mirror_urls: if urls.is_empty() { None } else { Some(urls) },
+            sha256: Some(self.sha256.to_string()),
+            dest: Some(format!("{}/{}", DEFAULT_DESTINATION, self.dir)),
+            dest_filename: Some(self.filename.to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn file_path_matches(path: &str) -> bool {
+    path.ends_with("pom.xml")
+        || path.ends_with("verification-metadata.xml")
+        || path.ends_with("gradle.lockfile")
+}
+
+/// Parses the output of `mvn dependency:list -DoutputFile=dependencies.txt`. Each dependency
+/// is in the `groupId:artifactId:type[:classifier]:version:scope` format.
+pub fn parse_dependency_list(dependency_list_content: &str) -> Result<Vec<MavenArtifact>, String> {
+    let mut artifacts: Vec<MavenArtifact> = vec![];
+    for line in dependency_list_content.lines() {
+        let line = line.trim();
+        // Recent versions of the dependency plugin add the Java module name after the coordinates.
+        let line = line.split(" -- ").next().unwrap_or(line).trim();
+        if line.is_empty() || line.ends_with(':') || line == "none" {
+            continue;
+        }
+        let coordinates: Vec<&str> = line.split(':').collect();
+        let artifact = match coordinates.len() {
+            5 => MavenArtifact {
+                group_id: coordinates[0].to_string(),
+                artifact_id: coordinates[1].to_string(),
+                extension: coordinates[2].to_string(),
+                version: coordinates[3].to_string(),
+                classifier: None,
+            },
+            6 => MavenArtifact {
+                group_id: coordinates[0].to_string(),
+                artifact_id: coordinates[1].to_string(),
+                extension: coordinates[2].to_string(),
+                classifier: Some(coordinates[3].to_string()),
+                version: coordinates[4].to_string(),
+            },
+            _ => return Err(format!("Invalid dependency {}.", line)),
+        };
+        artifacts.push(artifact);
+    }
+    Ok(artifacts)
+}
+
+/// Gets the artifactId of the project from a `pom.xml` file.
+pub fn get_pom_artifact_id(pom_content: &str) -> Option<String> {
+    // The artifactId of the parent project is not the one we're looking for.
+    let mut pom_content = pom_content.to_string();
+    if let (Some(parent_start), Some(parent_end)) =
+        (pom_content.find("<parent>"), pom_content.find("</parent>"))
+    {
+        pom_content.replace_range(parent_start..parent_end, "");
+    }
+    let artifact_id_start = pom_content.find("<artifactId>")? + "<artifactId>".len();
+    let artifact_id_end = pom_content[artifact_id_start..].find("</artifactId>")? + artifact_id_start;
+    Some(pom_content[artifact_id_start..artifact_id_end].trim().to_string())
+}
+
+/// Gets the repositories declared in a `pom.xml` file, indexed by their id, with Maven Central.
+pub fn get_pom_repositories(pom_content: &str) -> BTreeMap<String, String> {
+    let mut repositories: BTreeMap<String, String> = BTreeMap::new();
+    repositories.insert(MAVEN_CENTRAL_ID.to_string(), MAVEN_CENTRAL_URL.to_string());
+    for repository_tag in ["repository", "pluginRepository"] {
+        for repository in pom_content.split(&format!("<{}>", repository_tag)).skip(1) {
+            let repository = repository
+                .split(&format!("</{}>", repository_tag))
+                .next()
+                .unwrap_or("");
+            let (id, url) = match (
+                get_xml_element(repository, "id"),
+                get_xml_element(repository, "url"),
+            ) {
+                (Some(i), Some(u)) => (i, u),
+                _ => continue,
+            };
+            repositories.insert(id, url);
+        }
+    }
+    repositories
+}
+
+fn get_xml_element(xml_content: &str, element_name: &str) -> Option<String> {
+    let element_start = xml_content.find(&format!("<{}>", element_name))? + element_name.len() + 2;
+    let element_end = xml_content[element_start..].find(&format!("</{}>", element_name))? + element_start;
+    Some(xml_content[element_start..element_end].trim().to_string())
+}
+
+/// Parses a `_remote.repositories` file, which contains the id of the repository each
+/// file was downloaded from, in the `filename>repository=` format. The repository is empty
+/// for the artifacts installed locally.
+pub fn parse_remote_repositories(remote_repositories_content: &str) -> BTreeMap<String, String> {
+    let mut remote_repositories: BTreeMap<String, String> = BTreeMap::new();
+    for line in remote_repositories_content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some((filename, repository)) = line.split_once('>') {
+            let repository = repository.trim_end_matches('=');
+            if !repository.is_empty() {
+                remote_repositories.insert(filename.to_string(), repository.to_string());
+            }
+        }
+    }
+    remote_repositories
+}
+
+/// Gets the files of the Maven repository populated for the project, with the dependencies,
+/// the plugins and their dependencies, and the parent and imported POMs. The metadata files
+/// are kept, since Maven needs the metadata of the plugin groups to resolve the plugin
+/// prefixes offline, but not the files in which Maven records the state of the downloads.
+/// The files are downloaded from the repository Maven recorded for them, using the
+/// repositories of the project.
+pub fn get_maven_repository_files(
+    repository_dir: &path::Path,
+    repositories: &BTreeMap<String, String>,
+) -> Result<Vec<MavenRepositoryFile>, String> {
+    let file_paths = match fpm_core::utils::get_all_paths(repository_dir) {
+        Ok(p) => p,
+        Err(e) => {
+            return Err(format!(
+                "Could not read the Maven repository at {}: {}",
+                repository_dir.display(),
+                e
+            ))
+        }
+    };
+
+    let mut repository_files: Vec<MavenRepositoryFile> = vec![];
+    for file_path in file_paths {
+        if !file_path.is_file() {
+            continue;
+        }
+        let relative_path = match file_path.strip_prefix(repository_dir) {
+            Ok(p) => p,
+            Err(_) => continue,
+        };
+        let (dir, filename) = match (
+            relative_path.parent().and_then(|d| d.to_str()),
+            relative_path.file_name().and_then(|f| f.to_str()),
+        ) {
+            (Some(d), Some(f)) if !d.is_empty() => (d.to_string(), f.to_string()),
+            _ => continue,
+        };
+        if filename == REMOTE_REPOSITORIES_FILE_NAME
+            || filename == "resolver-status.properties"
+            || filename.ends_with(".lastUpdated")
+            || filename.ends_with(".sha1")
+        {
+            continue;
+        }
+        let mut contents: Option<String> = None;
+        if filename.starts_with("maven-metadata") {
+            match fs::read_to_string(&file_path) {
+                Ok(c) => contents = Some(c),
+                Err(e) => return Err(format!("Could not read {}: {}", file_path.display(), e)),
+            };
+        }
+        let remote_repositories =
+            match fs::read_to_string(file_path.with_file_name(REMOTE_REPOSITORIES_FILE_NAME)) {
+                Ok(c) => parse_remote_repositories(&c),
+                Err(_) => BTreeMap::new(),
+            };
+        let mut repository_urls: Vec<String> = vec![];
+        if let Some(repository_id) = remote_repositories.get(&filename) {
+            match repositories.get(repository_id) {
+                Some(u) => repository_urls.push(u.to_string()),
+                None => log::warn!(
+                    "Unknown repository {} for {}, using Maven Central.",
+                    repository_id,
+                    filename
+                ),
+            };
+        }
+        repository_files.push(MavenRepositoryFile {
+            sha256: crate::utils::get_file_sha256(&file_path)?,
+            dir,
+            filename,
+            contents,
+            repository_urls,
+        });
+    }
+    repository_files.sort_by(|a, b| (&a.dir, &a.filename).cmp(&(&b.dir, &b.filename)));
+    Ok(repository_files)
+}
+
+/// Checks that the artifacts of the dependency list, and their POMs, are in the repository.
+pub fn check_dependency_list(
+    artifacts: &[MavenArtifact],
+    repository_files: &[MavenRepositoryFile],
+) -> Result<(), String> {
+    for artifact in artifacts {
+        for artifact in [artifact.clone(), artifact.get_pom()] {
+            let dir = artifact.get_repository_dir();
+            let filename = artifact.get_filename();
+            if !repository_files
+                .iter()
+                .any(|f| f.dir == dir && f.filename == filename)
+            {
+                return Err(format!(
+                    "Artifact {} is not in the Maven repository of the project.",
+                    filename
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn get_xml_attribute(tag: &str, attribute_name: &str) -> Option<String> {
+    let attribute_start = tag.find(&format!(" {}=\"", attribute_name))? + attribute_name.len() + 3;
+    let attribute_end = tag[attribute_start..].find('"')? + attribute_start;
+    Some(tag[attribute_start..attribute_end].to_string())
+}
+
+/// Parses the Gradle dependency verification file, which contains the sha256 checksums
+/// of all the artifacts used by the build, plugins included.
+/// See https://docs.gradle.org/current/userguide/dependency_verification.html
+pub fn parse_verification_metadata(metadata_content: &str) -> Result<Vec<MavenRepositoryFile>, String> {
+    let mut repository_files: Vec<MavenRepositoryFile> = vec![];
+    let mut current_dir: Option<String> = None;
+    let mut current_file: Option<MavenRepositoryFile> = None;
+
+    for tag in metadata_content.split('<') {
+        if tag.starts_with("component ") {
+            let (group, name, version) = match (
+                get_xml_attribute(tag, "group"),
+                get_xml_attribute(tag, "name"),
+                get_xml_attribute(tag, "version"),
+            ) {
+                (Some(g), Some(n), Some(v)) => (g, n, v),
+                _ => return Err(format!("Invalid component <{}.", tag.trim())),
+            };
+            current_dir = Some(format!("{}/{}/{}", group.replace('.', "/"), name, version));
+        } else if tag.starts_with("/component") {
+            current_dir = None;
+        } else if tag.starts_with("artifact ") {
+            let dir = match &current_dir {
+                Some(d) => d,
+                None => continue,
+            };
+            if let Some(filename) = get_xml_attribute(tag, "name") {
+                current_file = Some(MavenRepositoryFile {
+                    dir: dir.to_string(),
+                    filename,
+                    ..Default::default()
+                });
+            }
+        } else if tag.starts_with("/artifact") {
+            if let Some(repository_file) = current_file.take() {
+                if repository_file.sha256.is_empty() {
+                    log::warn!("No sha256 checksum for {}, ignoring.", repository_file.filename);
+                    continue;
+                }
+                repository_files.push(repository_file);
+            }
+        } else if tag.starts_with("sha256 ") {
+            if let Some(repository_file) = current_file.as_mut() {
+                // Other trusted checksums can be listed for the same artifact.
+                if repository_file.sha256.is_empty() {
+                    repository_file.sha256 = get_xml_attribute(tag, "value").unwrap_or_default();
+                }
+            }
+        }
+    }
+    Ok(repository_files)
+}
+
+/// Gets the URLs of the repositories declared in the Gradle scripts of the project. Gradle
+/// resolves the plugins from the Gradle plugin portal when no plugin repository is declared,
+/// so it is always used last.
+pub fn get_gradle_repositories(project_dir: &path::Path) -> Vec<String> {
+    let mut repository_urls: Vec<String> = vec![];
+    for script_name in GRADLE_SCRIPT_NAMES {
+        let script_content = match fs::read_to_string(project_dir.join(script_name)) {
+            Ok(c) => c,
+            Err(_) => continue,
+        };
+        for line in script_content.lines() {
+            let line = line.trim();
+            let repository_url = if line.contains("mavenCentral()") {
+                Some(MAVEN_CENTRAL_URL.to_string())
+            } else if line.contains("google()") {
+                Some(GOOGLE_MAVEN_URL.to_string())
+            } else if line.contains("gradlePluginPortal()") {
+                Some(GRADLE_PLUGINS_URL.to_string())
+            } else if line.contains("url") || line.contains("maven(") {
+                line.split(['"', '\''])
+                    .find(|s| s.starts_with("https://") || s.starts_with("http://"))
+                    .map(|u| u.trim_end_matches('/').to_string())
+            } else {
+                None
+            };
+            if let Some(repository_url) = repository_url {
+                if !repository_urls.contains(&repository_url) {
+                    repository_urls.push(repository_url);
+                }
+            }
+        }
+    }
+    if repository_urls.is_empty() {
+        repository_urls.push(MAVEN_CENTRAL_URL.to_string());
+    }
+    if !repository_urls.iter().any(|u| u == GRADLE_PLUGINS_URL) {
+        repository_urls.push(GRADLE_PLUGINS_URL.to_string());
+    }
+    repository_urls
+}
+
+/// Parses a Gradle lockfile, in which each line is in the `group:name:version=configurations` format.
+/// See https://docs.gradle.org/current/userguide/dependency_locking.html
+pub fn parse_gradle_lockfile(lockfile_content: &str) -> Result<Vec<MavenArtifact>, String> {
+    let mut artifacts: Vec<MavenArtifact> = vec![];
+    for line in lockfile_content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("empty=") {
+            continue;
+        }
+        let coordinates = line.split('=').next().unwrap_or(line);
+        let coordinates: Vec<&str> = coordinates.split(':').collect();
+        if coordinates.len() != 3 {
+            return Err(format!("Invalid Gradle lockfile entry {}.", line));
+        }
+        artifacts.push(MavenArtifact {
+            group_id: coordinates[0].to_string(),
+            artifact_id: coordinates[1].to_string(),
+            version: coordinates[2].to_string(),
+            extension: "jar".to_string(),
+            classifier: None,
+        });
+    }
+    Ok(artifacts)
+}
+
+pub fn get_gradle_cache_dir() -> Option<path::PathBuf> {
+    if let Ok(gradle_home) = env::var("GRADLE_USER_HOME") {
+        return Some(path::Path::new(&gradle_home).join("caches/modules-2/files-2.1"));
+    }
+    let home_dir = env::var("HOME").ok()?;
+    Some(path::Path::new(&home_dir).join(".gradle/caches/modules-2/files-2.1"))
+}
+
+/// Gets the files of the artifacts from the Gradle cache. The files of each artifact are
+/// stored in a directory named after their sha1 checksum.
+pub fn get_gradle_cache_files(artifacts: &[MavenArtifact]) -> Result<Vec<MavenRepositoryFile>, String> {
+    let gradle_cache_dir = match get_gradle_cache_dir() {
+        Some(d) => d,
+        None => return Err("Could not find the Gradle cache.".to_string()),
+    };
+
+    let mut repository_files: Vec<MavenRepositoryFile> = vec![];
+    for artifact in artifacts {
+        let artifact_cache_dir = gradle_cache_dir
+            .join(&artifact.group_id)
+            .join(&artifact.artifact_id)
+            .join(&artifact.version);
+        let artifact_files = match fpm_core::utils::get_all_paths(&artifact_cache_dir) {
+            Ok(f) => f,
+            Err(_) => {
+                return Err(format!(
+                    "Artifact {}:{}:{} is not in the Gradle cache. Run the Gradle build first.",
+                    artifact.group_id, artifact.artifact_id, artifact.version
+                ))
+            }
+        };
+        for artifact_file in artifact_files {
+            if !artifact_file.is_file() {
+                continue;
+            }
+            let filename = match artifact_file.file_name().and_then(|f| f.to_str()) {
+                Some(f) => f.to_string(),
+                None => continue,
+            };
+            repository_files.push(MavenRepositoryFile {
+                dir: artifact.get_repository_dir(),
+                filename,
+                sha256: crate::utils::get_file_sha256(&artifact_file)?,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(repository_files)
+}
+
+pub fn get_java_module(manifest_path: &str) -> Result<FlatpakModule, String> {
+    let manifest_content = match fs::read_to_string(manifest_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read {}: {}", manifest_path, e)),
+    };
+
+    let (build_tool, module_name, repository_files) = if manifest_path.ends_with("pom.xml") {
+        let repository_dir = path::Path::new(manifest_path).with_file_name(LOCAL_REPOSITORY_DIR_NAME);
+        if !repository_dir.is_dir() {
+            return Err(format!(
+                "Could not find the Maven repository of the project at {}. Run `mvn -Dmaven.repo.local={} dependency:go-offline package dependency:copy-dependencies dependency:list -DoutputFile={}` first.",
+                repository_dir.display(),
+                LOCAL_REPOSITORY_DIR_NAME,
+                DEPENDENCY_LIST_FILE_NAME
+            ));
+        }
+        let repository_files =
+            get_maven_repository_files(&repository_dir, &get_pom_repositories(&manifest_content))?;
+
+        let dependency_list_path = path::Path::new(manifest_path).with_file_name(DEPENDENCY_LIST_FILE_NAME);
+        match fs::read_to_string(&dependency_list_path) {
+            Ok(c) => check_dependency_list(&parse_dependency_list(&c)?, &repository_files)?,
+            Err(e) => log::warn!(
+                "Could not read {}, the dependencies are not checked: {}",
+                dependency_list_path.display(),
+                e
+            ),
+        };
+        (
+            JavaBuildTool::Maven,
+            get_pom_artifact_id(&manifest_content).unwrap_or("maven-project".to_string()),
+            repository_files,
+        )
+    } else {
+        // The dependency verification file is in the gradle directory of the project.
+        let manifest_dir = path::Path::new(manifest_path)
+            .parent()
+            .unwrap_or(path::Path::new("."));
+        let (project_dir, mut repository_files) = if manifest_path.ends_with("verification-metadata.xml") {
+            (
+                manifest_dir.parent().unwrap_or(manifest_dir),
+                parse_verification_metadata(&manifest_content)?,
+            )
+        } else {
+            let artifacts = parse_gradle_lockfile(&manifest_content)?;
+            log::warn!(
+                "The Gradle lockfile does not list the plugins, which are only imported from the dependency verification file."
+            );
+            (manifest_dir, get_gradle_cache_files(&artifacts)?)
+        };
+        // Gradle does not record the repository each artifact was downloaded from, so all
+        // the repositories of the project are tried.
+        let repository_urls = get_gradle_repositories(project_dir);
+        for repository_file in &mut repository_files {
+            repository_file.repository_urls = repository_urls.clone();
+        }
+        (
+            JavaBuildTool::Gradle,
+            "gradle-project".to_string(),
+            repository_files,
+        )
+    };
+
+    let mut java_module = FlatpakModule {
+        name: module_name,
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        ..Default::default()
+    };
+    java_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    for repository_file in &repository_files {
+        java_module
+            .sources
+            .push(FlatpakSourceItem::Description(repository_file.get_source()));
+    }
+
+    match build_tool {
+        JavaBuildTool::Maven => {
+            java_module.build_commands.push(format!(
+                "mvn --offline -Dmaven.repo.local=$PWD/{} package dependency:copy-dependencies -DincludeScope=runtime",
+                DEFAULT_DESTINATION
+            ));
+            java_module.build_commands.push(format!(
+                "install -Dm644 target/*.jar -t ${{FLATPAK_DEST}}/share/{}/",
+                java_module.name
+            ));
+            java_module.build_commands.push(format!(
+                "install -Dm644 target/dependency/*.jar -t ${{FLATPAK_DEST}}/share/{}/lib/",
+                java_module.name
+            ));
+        }
+        JavaBuildTool::Gradle => {
+            // The repositories of the project are replaced with the local repository.
+            java_module
+                .sources
+                .push(FlatpakSourceItem::Description(FlatpakSource {
+                    r#type: Some(FlatpakSourceType::Shell),
+                    commands: Some(vec![
+                        format!(
+                            "printf 'def flatpakRepository = \"file://%s/{}\"\\n' \"$PWD\" > {}",
+                            DEFAULT_DESTINATION, GRADLE_INIT_SCRIPT_NAME
+                        ),
+                        format!(
+                            "printf '%s\\n' '{}' >> {}",
+                            GRADLE_INIT_SCRIPT.join("' '"),
+                            GRADLE_INIT_SCRIPT_NAME
+                        ),
+                    ]),
+                    ..Default::default()
+                }));
+            java_module.build_commands.push(format!(
+                "gradle --offline --no-daemon --init-script {} build",
+                GRADLE_INIT_SCRIPT_NAME
+            ));
+            java_module.build_commands.push(format!(
+                "install -Dm644 build/libs/*.jar -t ${{FLATPAK_DEST}}/share/{}/",
+                java_module.name
+            ));
+        }
+    }
+    Ok(java_module)
+}
+
+pub struct MavenImporter {}
+impl Importer for MavenImporter {
+    fn get_name(&self) -> String {
+        "maven".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        Ok(ImportResult::new(get_java_module(manifest_path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_dependency_list() {
+        let artifacts = parse_dependency_list(
+            r###"
+The following files have been resolved:
+   com.google.guava:guava:jar:31.1-jre:compile -- module com.google.common
+   org.lwjgl:lwjgl:jar:natives-linux:3.3.1:runtime
+   junit:junit:jar:4.13.2:test
+"###,
+        )
+        .unwrap();
+        assert_eq!(artifacts.len(), 3);
+        assert_eq!(
+            artifacts[0].get_repository_dir(),
+            "com/google/guava/guava/31.1-jre"
+        );
+        assert_eq!(artifacts[0].get_filename(), "guava-31.1-jre.jar");
+        assert_eq!(artifacts[0].get_pom().get_filename(), "guava-31.1-jre.pom");
+        assert_eq!(artifacts[1].get_filename(), "lwjgl-3.3.1-natives-linux.jar");
+        assert_eq!(artifacts[1].get_pom().get_filename(), "lwjgl-3.3.1.pom");
+    }
+
+    #[test]
+    pub fn test_get_maven_repository_files() {
+        let repository_dir =
+            std::env::temp_dir().join(format!("fpm-test-maven-repository-{}", std::process::id()));
+        let guava_dir = repository_dir.join("com/google/guava/guava/31.1-jre");
+        let parent_dir = repository_dir.join("com/google/guava/guava-parent/31.1-jre");
+        let plugins_dir = repository_dir.join("org/apache/maven/plugins");
+        fs::create_dir_all(&guava_dir).unwrap();
+        fs::create_dir_all(&parent_dir).unwrap();
+        fs::create_dir_all(&plugins_dir).unwrap();
+        fs::write(
+            plugins_dir.join("maven-metadata-central.xml"),
+            "<metadata><plugins><plugin><prefix>dependency</prefix></plugin></plugins></metadata>",
+        )
+        .unwrap();
+        for file_path in [
+            guava_dir.join("guava-31.1-jre.jar"),
+            guava_dir.join("guava-31.1-jre.pom"),
+            guava_dir.join("guava-31.1-jre.pom.sha1"),
+            parent_dir.join("guava-parent-31.1-jre.pom"),
+        ] {
+            fs::write(file_path, "content").unwrap();
+        }
+        fs::write(
+            guava_dir.join("_remote.repositories"),
+            "#NOTE: This is a Maven Resolver internal implementation file\nguava-31.1-jre.jar>internal=\nguava-31.1-jre.pom>central=\n",
+        )
+        .unwrap();
+
+        let repositories = get_pom_repositories(
+            r###"
+<project>
+  <repositories>
+    <repository>
+      <id>internal</id>
+      <url>https://maven.example.com/releases/</url>
+    </repository>
+  </repositories>
+  <distributionManagement>
+    <snapshotRepository>
+      <id>snapshots</id>
+    </snapshotRepository>
+  </distributionManagement>
+</project>
+"###,
+        );
+        assert_eq!(repositories.len(), 2);
+        let repository_files = get_maven_repository_files(&repository_dir, &repositories).unwrap();
+        assert_eq!(
+            repository_files
+                .iter()
+                .map(|f| f.filename.as_str())
+                .collect::<Vec<&str>>(),
+            vec![
+                "guava-parent-31.1-jre.pom",
+                "guava-31.1-jre.jar",
+                "guava-31.1-jre.pom",
+                "maven-metadata-central.xml"
+            ]
+        );
+        assert_eq!(repository_files[1].dir, "com/google/guava/guava/31.1-jre");
+        assert!(repository_files[1].contents.is_none());
+        assert_eq!(
+            repository_files[1].get_source().url,
+            Some(
+                "https://maven.example.com/releases/com/google/guava/guava/31.1-jre/guava-31.1-jre.jar"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            repository_files[2].get_source().url,
+            Some(
+                "https://repo.maven.apache.org/maven2/com/google/guava/guava/31.1-jre/guava-31.1-jre.pom"
+                    .to_string()
+            )
+        );
+        let metadata_source = repository_files[3].get_source();
+        assert_eq!(metadata_source.r#type, Some(FlatpakSourceType::Shell));
+        assert_eq!(
+            metadata_source.commands.unwrap()[1],
+            "printf '%s' '<metadata><plugins><plugin><prefix>dependency</prefix></plugin></plugins></metadata>' > maven-local/org/apache/maven/plugins/maven-metadata-central.xml"
+        );
+
+        let artifacts = parse_dependency_list("com.google.guava:guava:jar:31.1-jre:compile").unwrap();
+        assert!(check_dependency_list(&artifacts, &repository_files).is_ok());
+        let artifacts = parse_dependency_list("junit:junit:jar:4.13.2:test").unwrap();
+        assert!(check_dependency_list(&artifacts, &repository_files).is_err());
+
+        fs::remove_dir_all(&repository_dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_get_pom_artifact_id() {
+        let artifact_id = get_pom_artifact_id(
+            r###"
+<project>
+  <parent>
+    <groupId>org.example</groupId>
+    <artifactId>parent-project</artifactId>
+  </parent>
+  <artifactId> my-app </artifactId>
+</project>
+"###,
+        );
+        assert_eq!(artifact_id, Some("my-app".to_string()));
+    }
+
+    #[test]
+    pub fn test_parse_verification_metadata() {
+        let repository_files = parse_verification_metadata(
+            r###"<?xml version="1.0" encoding="UTF-8"?>
+<verification-metadata xmlns="https://schema.gradle.org/dependency-verification">
+   <configuration>
+      <verify-metadata>true</verify-metadata>
+   </configuration>
+   <components>
+      <component group="com.google.guava" name="guava" version="31.1-jre">
+         <artifact name="guava-31.1-jre.jar">
+            <sha256 value="a42edc9cab792e39fe39bb94f3fca655ed157ff87a8af78e1d6ba5b07c4a00ab" origin="Generated by Gradle"/>
+         </artifact>
+         <artifact name="guava-31.1-jre.pom">
+            <sha256 value="e1b5d4b6d8a4a4d2b2e9b6b7a5c1e5f8d2a3b6c7e8f9a0b1c2d3e4f5a6b7c8d9" origin="Generated by Gradle">
+               <also-trust value="0000000000000000000000000000000000000000000000000000000000000000"/>
+            </sha256>
+         </artifact>
+      </component>
+      <component group="org.example" name="unverified" version="1.0">
+         <artifact name="unverified-1.0.jar">
+            <sha1 value="5b8a3a7765dfe001261dde915589e782f8c94d1e"/>
+         </artifact>
+      </component>
+   </components>
+</verification-metadata>
+"###,
+        )
+        .unwrap();
+        assert_eq!(repository_files.len(), 2);
+        assert_eq!(repository_files[0].dir, "com/google/guava/guava/31.1-jre");
+        assert_eq!(repository_files[0].filename, "guava-31.1-jre.jar");
+        assert_eq!(
+            repository_files[1].sha256,
+            "e1b5d4b6d8a4a4d2b2e9b6b7a5c1e5f8d2a3b6c7e8f9a0b1c2d3e4f5a6b7c8d9"
+        );
+
+        let source = repository_files[0].get_source();
+        assert_eq!(
+            source.url,
+            Some(
+                "https://repo.maven.apache.org/maven2/com/google/guava/guava/31.1-jre/guava-31.1-jre.jar"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            source.dest,
+            Some("maven-local/com/google/guava/guava/31.1-jre".to_string())
+        );
+        assert!(source.mirror_urls.is_none());
+    }
+
+    #[test]
+    pub fn test_get_gradle_repositories() {
+        let project_dir = std::env::temp_dir().join(format!("fpm-test-gradle-project-{}", std::process::id()));
+        fs::create_dir_all(&project_dir).unwrap();
+        assert_eq!(
+            get_gradle_repositories(&project_dir),
+            vec![MAVEN_CENTRAL_URL, GRADLE_PLUGINS_URL]
+        );
+
+        fs::write(
+            project_dir.join("settings.gradle.kts"),
+            r###"
+dependencyResolutionManagement {
+    repositoriesMode.set(RepositoriesMode.FAIL_ON_PROJECT_REPOS)
+    repositories {
+        google()
+        mavenCentral()
+        maven("https://jitpack.io/")
+        maven {
+            url = uri("https://maven.example.com/releases")
+        }
+    }
+}
+"###,
+        )
+        .unwrap();
+        assert_eq!(
+            get_gradle_repositories(&project_dir),
+            vec![
+                GOOGLE_MAVEN_URL,
+                MAVEN_CENTRAL_URL,
+                "https://jitpack.io",
+                "https://maven.example.com/releases",
+                GRADLE_PLUGINS_URL
+            ]
+        );
+        fs::remove_dir_all(&project_dir).unwrap();
+    }
+
+    #[test]
+    pub fn test_parse_gradle_lockfile() {
+        let artifacts = parse_gradle_lockfile(
+            r###"
+# This is a Gradle generated file for dependency locking.
+# Manual edits can break the build and are not advised.
+# This file is expected to be part of source control.
+com.google.guava:guava:31.1-jre=compileClasspath,runtimeClasspath
+junit:junit:4.13.2=testCompileClasspath
+empty=annotationProcessor
+"###,
+        )
+        .unwrap();
+        assert_eq!(artifacts.len(), 2);
+        assert_eq!(artifacts[1].get_filename(), "junit-4.13.2.jar");
+    }
+}
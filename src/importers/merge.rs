@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem};
+
+/// The result of merging the sources of an import with the sources of a previous import.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct SourcesMerge {
+    /// The merged sources, in the order of the new sources.
+    pub sources: Vec<FlatpakSourceItem>,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub kept: Vec<String>,
+}
+
+/// Gets the key identifying a source. The remote sources are identified by their URL and
+/// checksum, and the other sources by their whole description.
+pub fn get_source_key(source_item: &FlatpakSourceItem) -> String {
+    let source = match source_item {
+        FlatpakSourceItem::Path(p) => return format!("path:{}", p),
+        FlatpakSourceItem::Description(s) => s,
+    };
+    match (&source.url, get_checksum(source)) {
+        (Some(url), Some((algorithm, checksum))) => {
+            format!("{}#{}:{}", url, algorithm, checksum.to_lowercase())
+        }
+        _ => serde_json::to_string(source).unwrap_or_default(),
+    }
+}
+
+/// Gets a short description of a source, to report it to the user.
+pub fn get_source_description(source_item: &FlatpakSourceItem) -> String {
+    let source = match source_item {
+        FlatpakSourceItem::Path(p) => return p.to_string(),
+        FlatpakSourceItem::Description(s) => s,
+    };
+    if let Some(url) = &source.url {
+        return url.to_string();
+    }
+    if let Some(path) = &source.path {
+        return format!("{} source {}", source.get_type_name(), path);
+    }
+    if let Some(dest_filename) = &source.dest_filename {
+        return format!("{} source {}", source.get_type_name(), dest_filename);
+    }
+    match &source.commands {
+        Some(commands) => format!(
+            "{} source with {} command(s)",
+            source.get_type_name(),
+            commands.len()
+        ),
+        None => format!("{} source", source.get_type_name()),
+    }
+}
+
+/// Gets the strongest checksum of a source.
+fn get_checksum(source: &FlatpakSource) -> Option<(&'static str, &String)> {
+    if let Some(sha512) = &source.sha512 {
+        return Some(("sha512", sha512));
+    }
+    if let Some(sha256) = &source.sha256 {
+        return Some(("sha256", sha256));
+    }
+    if let Some(sha1) = &source.sha1 {
+        return Some(("sha1", sha1));
+    }
+    None
+}
+
+/// Whether two sources downloading the same URL have different checksums, for the checksum
+/// algorithms they have in common.
+fn have_conflicting_checksums(source: &FlatpakSource, other_source: &FlatpakSource) -> bool {
+    [
+        (&source.sha1, &other_source.sha1),
+        (&source.sha256, &other_source.sha256),
+        (&source.sha512, &other_source.sha512),
+    ]
+    .iter()
+    .any(|(checksum, other_checksum)| match (checksum, other_checksum) {
+        (Some(c), Some(o)) => c.to_lowercase() != o.to_lowercase(),
+        _ => false,
+    })
+}
+
+/// Finds the URLs downloaded by another source, but with a different checksum. This usually
+/// means that the file was modified upstream, or that one of the dependency manifests is
+/// corrupted.
+pub fn get_checksum_conflicts(
+    sources: &[FlatpakSourceItem],
+    other_sources: &[FlatpakSourceItem],
+) -> Vec<String> {
+    let mut sources_by_url: BTreeMap<&String, &FlatpakSource> = BTreeMap::new();
+    for source_item in other_sources {
+        if let FlatpakSourceItem::Description(source) = source_item {
+            if let Some(url) = &source.url {
+                sources_by_url.insert(url, source);
+            }
+        }
+    }
+
+    let mut conflicts: Vec<String> = vec![];
+    for source_item in sources {
+        let source = match source_item {
+            FlatpakSourceItem::Description(s) => s,
+            FlatpakSourceItem::Path(_) => continue,
+        };
+        let url = match &source.url {
+            Some(u) => u,
+            None => continue,
+        };
+        match sources_by_url.get(url) {
+            Some(other_source) if have_conflicting_checksums(source, other_source) => {
+                if !conflicts.contains(url) {
+                    conflicts.push(url.to_string());
+                }
+            }
+            Some(_) => {}
+            None => {
+                sources_by_url.insert(url, source);
+            }
+        }
+    }
+    conflicts
+}
+
+/// Gets the sources which are also used by other sources, by URL and checksum.
+pub fn get_shared_sources(sources: &[FlatpakSourceItem], other_sources: &[FlatpakSourceItem]) -> Vec<String> {
+    let other_source_keys: Vec<String> = other_sources.iter().map(get_source_key).collect();
+    sources
+        .iter()
+        .filter(|s| matches!(s, FlatpakSourceItem::Description(d) if d.url.is_some()))
+        .filter(|s| other_source_keys.contains(&get_source_key(s)))
+        .map(get_source_description)
+        .collect()
+}
+
+/// Removes the duplicated sources, keeping the first occurrence of each source.
+pub fn dedupe_sources(sources: Vec<FlatpakSourceItem>) -> Vec<FlatpakSourceItem> {
+    let mut source_keys: Vec<String> = vec![];
+    let mut deduped_sources: Vec<FlatpakSourceItem> = vec![];
+    for source in sources {
+        let source_key = get_source_key(&source);
+        if source_keys.contains(&source_key) {
+            log::debug!("Ignoring duplicated source {}.", get_source_description(&source));
+            continue;
+        }
+        source_keys.push(source_key);
+        deduped_sources.push(source);
+    }
+    deduped_sources
+}
+
+/// Merges the sources of an import with the sources of a previous import of the same
+/// module. The sources which were already present are kept as they were, so that the
+/// changes made to them after the previous import, like added mirror URLs, are preserved.
+pub fn merge_sources(
+    existing_sources: &[FlatpakSourceItem],
+    new_sources: Vec<FlatpakSourceItem>,
+) -> SourcesMerge {
+    let mut existing_sources_by_key: BTreeMap<String, &FlatpakSourceItem> = BTreeMap::new();
+    for existing_source in existing_sources {
+        existing_sources_by_key
+            .entry(get_source_key(existing_source))
+            .or_insert(existing_source);
+    }
+
+    let mut sources_merge = SourcesMerge::default();
+    let mut new_source_keys: Vec<String> = vec![];
+    for new_source in dedupe_sources(new_sources) {
+        let source_key = get_source_key(&new_source);
+        match existing_sources_by_key.get(&source_key) {
+            Some(existing_source) => {
+                sources_merge.kept.push(get_source_description(existing_source));
+                sources_merge.sources.push((*existing_source).clone());
+            }
+            None => {
+                sources_merge.added.push(get_source_description(&new_source));
+                sources_merge.sources.push(new_source);
+            }
+        }
+        new_source_keys.push(source_key);
+    }
+    for (source_key, existing_source) in existing_sources_by_key {
+        if !new_source_keys.contains(&source_key) {
+            sources_merge
+                .removed
+                .push(get_source_description(existing_source));
+        }
+    }
+    sources_merge
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flatpak_rs::source::FlatpakSourceType;
+
+    fn get_file_source(url: &str, sha256: &str) -> FlatpakSourceItem {
+        FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(url.to_string()),
+            sha256: Some(sha256.to_string()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    pub fn test_merge_sources() {
+        let mut existing_source = FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some("https://example.com/a.tar.gz".to_string()),
+            sha256: Some("aaaa".to_string()),
+            ..Default::default()
+        };
+        existing_source.mirror_urls = Some(vec!["https://mirror.example.com/a.tar.gz".to_string()]);
+        let existing_sources = vec![
+            FlatpakSourceItem::Description(existing_source),
+            get_file_source("https://example.com/b.tar.gz", "bbbb"),
+        ];
+        let new_sources = vec![
+            get_file_source("https://example.com/a.tar.gz", "aaaa"),
+            get_file_source("https://example.com/c.tar.gz", "cccc"),
+            get_file_source("https://example.com/c.tar.gz", "cccc"),
+        ];
+
+        let sources_merge = merge_sources(&existing_sources, new_sources);
+        assert_eq!(sources_merge.sources.len(), 2);
+        assert_eq!(sources_merge.kept, vec!["https://example.com/a.tar.gz"]);
+        assert_eq!(sources_merge.added, vec!["https://example.com/c.tar.gz"]);
+        assert_eq!(sources_merge.removed, vec!["https://example.com/b.tar.gz"]);
+        match &sources_merge.sources[0] {
+            FlatpakSourceItem::Description(s) => assert!(s.mirror_urls.is_some()),
+            FlatpakSourceItem::Path(_) => panic!("Expected a source description"),
+        }
+    }
+
+    #[test]
+    pub fn test_get_checksum_conflicts() {
+        let sources = vec![
+            get_file_source("https://example.com/a.tar.gz", "aaaa"),
+            get_file_source("https://example.com/b.tar.gz", "bbbb"),
+        ];
+        let other_sources = vec![
+            get_file_source("https://example.com/a.tar.gz", "AAAA"),
+            get_file_source("https://example.com/b.tar.gz", "ffff"),
+        ];
+        assert_eq!(
+            get_checksum_conflicts(&sources, &other_sources),
+            vec!["https://example.com/b.tar.gz"]
+        );
+        assert!(get_checksum_conflicts(&sources, &[]).is_empty());
+        assert_eq!(
+            get_shared_sources(&sources, &other_sources),
+            vec!["https://example.com/a.tar.gz"]
+        );
+    }
+}
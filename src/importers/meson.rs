@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const SUBPROJECTS_DIR: &str = "subprojects";
+/// The directory in which meson looks for the downloaded archives of the subprojects.
+pub const PACKAGE_CACHE_DIR: &str = "subprojects/packagecache";
+
+/// A meson wrap file, describing how to fetch a subproject.
+/// See https://mesonbuild.com/Wrap-dependency-system-manual.html
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct MesonWrap {
+    /// The name of the wrap file, without the `.wrap` extension.
+    pub name: String,
+    /// The type of the wrap, for example `wrap-file` or `wrap-git`.
+    pub r#type: String,
+    /// The key/value pairs of the wrap section.
+    pub values: BTreeMap<String, String>,
+}
+impl MesonWrap {
+    pub fn parse(name: &str, wrap_content: &str) -> Result<MesonWrap, String> {
+        let mut wrap = MesonWrap {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let mut current_section = "";
+        for line in wrap_content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                current_section = section.trim();
+                if current_section.starts_with("wrap-") {
+                    if !wrap.r#type.is_empty() {
+                        return Err(format!("Multiple wrap sections in wrap file {}.", name));
+                    }
+                    wrap.r#type = current_section.to_string();
+                }
+                continue;
+            }
+            // The provide section is only used to resolve the dependencies.
+            if !current_section.starts_with("wrap-") {
+                continue;
+            }
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    wrap.values
+                        .insert(key.trim().to_string(), value.trim().to_string());
+                }
+                None => return Err(format!("Invalid line {} in wrap file {}.", line, name)),
+            }
+        }
+        if wrap.r#type.is_empty() {
+            return Err(format!("No wrap section in wrap file {}.", name));
+        }
+        Ok(wrap)
+    }
+
+    pub fn get_value(&self, key: &str) -> Option<&String> {
+        self.values.get(key)
+    }
+
+    /// Gets the directory of the subproject, relative to the subprojects directory.
+    pub fn get_directory(&self) -> String {
+        match self.get_value("directory") {
+            Some(d) => d.to_string(),
+            None => self.name.to_string(),
+        }
+    }
+
+    /// Gets a file of the package cache, from the url, filename and hash keys
+    /// with the given prefix.
+    fn get_package_cache_source(&self, prefix: &str) -> Result<Option<FlatpakSource>, String> {
+        let url = match self.get_value(&format!("{}_url", prefix)) {
+            Some(u) => u,
+            None => return Ok(None),
+        };
+        let filename = match self.get_value(&format!("{}_filename", prefix)) {
+            Some(f) => f,
+            None => return Err(format!("No {}_filename in wrap file {}.", prefix, self.name)),
+        };
+        let hash = match self.get_value(&format!("{}_hash", prefix)) {
+            Some(h) => h,
+            None => return Err(format!("No {}_hash in wrap file {}.", prefix, self.name)),
+        };
+
+        let mut mirror_urls: Option<Vec<String>> = None;
+        if let Some(fallback_url) = self.get_value(&format!("{}_fallback_url", prefix)) {
+            mirror_urls = Some(vec![fallback_url.to_string()]);
+        }
+        Ok(Some(FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(url.to_string()),
+            mirror_urls,
+            sha256: Some(hash.to_string()),
+            dest: Some(PACKAGE_CACHE_DIR.to_string()),
+            dest_filename: Some(filename.to_string()),
+            ..Default::default()
+        }))
+    }
+
+    /// Gets the commands applying the patches of a subproject, the way meson does when it
+    /// fetches the subproject itself: the patch archive is extracted over the subprojects
+    /// directory, then the patch directory is copied and the diff files are applied.
+    fn get_patch_commands(&self) -> Vec<String> {
+        let subproject_dir = format!("{}/{}", SUBPROJECTS_DIR, self.get_directory());
+        let mut patch_commands: Vec<String> = vec![];
+        if let Some(patch_filename) = self.get_value("patch_filename") {
+            patch_commands.push(format!(
+                "python3 -c \"import shutil; shutil.unpack_archive('{}/{}', '{}')\"",
+                PACKAGE_CACHE_DIR, patch_filename, SUBPROJECTS_DIR
+            ));
+        }
+        if let Some(patch_directory) = self.get_value("patch_directory") {
+            patch_commands.push(format!(
+                "cp -r {}/packagefiles/{}/. {}/",
+                SUBPROJECTS_DIR, patch_directory, subproject_dir
+            ));
+        }
+        if let Some(diff_files) = self.get_value("diff_files") {
+            for diff_file in diff_files.split(',').map(|f| f.trim()).filter(|f| !f.is_empty()) {
+                patch_commands.push(format!(
+                    "patch -p1 -d {} < {}/packagefiles/{}",
+                    subproject_dir, SUBPROJECTS_DIR, diff_file
+                ));
+            }
+        }
+        patch_commands
+    }
+
+    pub fn get_sources(&self) -> Result<Vec<FlatpakSource>, String> {
+        let mut sources: Vec<FlatpakSource> = vec![];
+        match self.r#type.as_str() {
+            "wrap-file" => {
+                // Meson extracts the archives and applies the patches itself when they are
+                // found in the package cache.
+                match self.get_package_cache_source("source")? {
+                    Some(s) => sources.push(s),
+                    None => return Err(format!("No source_url in wrap file {}.", self.name)),
+                };
+                if let Some(patch_source) = self.get_package_cache_source("patch")? {
+                    sources.push(patch_source);
+                }
+            }
+            "wrap-git" => {
+                let url = match self.get_value("url") {
+                    Some(u) => u,
+                    None => return Err(format!("No url in wrap file {}.", self.name)),
+                };
+                let mut git_source = FlatpakSource {
+                    r#type: Some(FlatpakSourceType::Git),
+                    url: Some(url.to_string()),
+                    dest: Some(format!("{}/{}", SUBPROJECTS_DIR, self.get_directory())),
+                    ..Default::default()
+                };
+                match self.get_value("revision").map(|r| r.as_str()) {
+                    Some(r) if r.len() == 40 && r.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        git_source.commit = Some(r.to_string());
+                    }
+                    Some("head") | Some("HEAD") | None => {
+                        log::warn!("Subproject {} is not pinned to a revision.", self.name);
+                    }
+                    Some(r) => {
+                        log::warn!(
+                            "Subproject {} is pinned to {}, which is not a commit.",
+                            self.name,
+                            r
+                        );
+                        git_source.branch = Some(r.to_string());
+                    }
+                }
+                sources.push(git_source);
+
+                // The subproject is already there when meson runs, so meson does not patch it.
+                if let Some(patch_source) = self.get_package_cache_source("patch")? {
+                    sources.push(patch_source);
+                }
+                let patch_commands = self.get_patch_commands();
+                if !patch_commands.is_empty() {
+                    sources.push(FlatpakSource {
+                        r#type: Some(FlatpakSourceType::Shell),
+                        commands: Some(patch_commands),
+                        ..Default::default()
+                    });
+                }
+            }
+            "wrap-redirect" => {
+                log::debug!("Ignoring wrap-redirect file {}.", self.name);
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported wrap type {} for {}.",
+                    self.r#type, self.name
+                ))
+            }
+        }
+        Ok(sources)
+    }
+}
+
+/// Gets the name of the project from the `project()` call of a `meson.build` file.
+pub fn get_project_name(meson_build_content: &str) -> Option<String> {
+    let project_call_start = meson_build_content.find("project(")? + "project(".len();
+    let project_args = meson_build_content[project_call_start..].trim_start();
+    let quote = project_args.chars().next()?;
+    if quote != '\'' && quote != '"' {
+        return None;
+    }
+    let name_end = project_args[1..].find(quote)? + 1;
+    Some(project_args[1..name_end].to_string())
+}
+
+pub fn file_path_matches(path: &str) -> bool {
+    path.ends_with("meson.build")
+}
+
+/// Gets all the wrap files from the subprojects directory next to the `meson.build` file.
+pub fn get_wraps(meson_build_path: &str) -> Result<Vec<MesonWrap>, String> {
+    let subprojects_dir = path::Path::new(meson_build_path).with_file_name(SUBPROJECTS_DIR);
+    let subprojects_dir_entries = match fs::read_dir(&subprojects_dir) {
+        Ok(e) => e,
+        Err(e) => return Err(format!("Could not read {}: {}", subprojects_dir.display(), e)),
+    };
+
+    let mut wraps: Vec<MesonWrap> = vec![];
+    for subprojects_dir_entry in subprojects_dir_entries {
+        let wrap_path = match subprojects_dir_entry {
+            Ok(e) => e.path(),
+            Err(e) => return Err(format!("Could not read {}: {}", subprojects_dir.display(), e)),
+        };
+        if wrap_path.extension().and_then(|e| e.to_str()) != Some("wrap") {
+            continue;
+        }
+        let wrap_name = match wrap_path.file_stem().and_then(|n| n.to_str()) {
+            Some(n) => n.to_string(),
+            None => continue,
+        };
+        let wrap_content = match fs::read_to_string(&wrap_path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read {}: {}", wrap_path.display(), e)),
+        };
+        wraps.push(MesonWrap::parse(&wrap_name, &wrap_content)?);
+    }
+    wraps.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(wraps)
+}
+
+pub fn get_meson_module(meson_build_path: &str) -> Result<FlatpakModule, String> {
+    let meson_build_content = match fs::read_to_string(meson_build_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read {}: {}", meson_build_path, e)),
+    };
+
+    let mut meson_module = FlatpakModule {
+        name: get_project_name(&meson_build_content).unwrap_or("meson-project".to_string()),
+        buildsystem: Some(FlatpakBuildSystem::Meson),
+        ..Default::default()
+    };
+    meson_module
+        .config_opts
+        .push("--wrap-mode=nodownload".to_string());
+
+    meson_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    for wrap in get_wraps(meson_build_path)? {
+        for source in wrap.get_sources()? {
+            meson_module.sources.push(FlatpakSourceItem::Description(source));
+        }
+    }
+    Ok(meson_module)
+}
+
+pub struct MesonImporter {}
+impl Importer for MesonImporter {
+    fn get_name(&self) -> String {
+        "meson".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        Ok(ImportResult::new(get_meson_module(manifest_path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_wrap_file() {
+        let wrap = MesonWrap::parse(
+            "zlib",
+            r###"
+[wrap-file]
+directory = zlib-1.2.13
+source_url = http://zlib.net/fossils/zlib-1.2.13.tar.gz
+source_fallback_url = https://github.com/mesonbuild/wrapdb/releases/download/zlib_1.2.13-1/zlib-1.2.13.tar.gz
+source_filename = zlib-1.2.13.tar.gz
+source_hash = b3a24de97a8fdbc835b9833169501030b8977031bcb54b3b3ac13740f846ab30
+patch_filename = zlib_1.2.13-1_patch.zip
+patch_url = https://wrapdb.mesonbuild.com/v2/zlib_1.2.13-1/get_patch
+patch_hash = 9d6e7c2a1f5e8e5a3bb1ba2d8b4c5a9b8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a
+
+[provide]
+zlib = zlib_dep
+"###,
+        )
+        .unwrap();
+        assert_eq!(wrap.r#type, "wrap-file");
+        assert_eq!(wrap.get_directory(), "zlib-1.2.13");
+
+        let sources = wrap.get_sources().unwrap();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(sources[0].dest, Some(PACKAGE_CACHE_DIR.to_string()));
+        assert_eq!(sources[0].dest_filename, Some("zlib-1.2.13.tar.gz".to_string()));
+        assert_eq!(
+            sources[0].sha256,
+            Some("b3a24de97a8fdbc835b9833169501030b8977031bcb54b3b3ac13740f846ab30".to_string())
+        );
+        assert_eq!(sources[0].mirror_urls.as_ref().unwrap().len(), 1);
+        assert_eq!(
+            sources[1].dest_filename,
+            Some("zlib_1.2.13-1_patch.zip".to_string())
+        );
+    }
+
+    #[test]
+    pub fn test_parse_wrap_git() {
+        let wrap = MesonWrap::parse(
+            "libfoo",
+            r###"
+[wrap-git]
+url = https://github.com/louib/libfoo.git
+revision = 0b5ec4f2a1d2b1c69b4a3e5a0c1b5b2d8f0e7c3a
+depth = 1
+"###,
+        )
+        .unwrap();
+        let sources = wrap.get_sources().unwrap();
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].dest, Some("subprojects/libfoo".to_string()));
+        assert_eq!(
+            sources[0].commit,
+            Some("0b5ec4f2a1d2b1c69b4a3e5a0c1b5b2d8f0e7c3a".to_string())
+        );
+
+        let wrap = MesonWrap::parse(
+            "libbar",
+            "[wrap-git]\nurl = https://github.com/louib/libbar.git\nrevision = v1.0\n",
+        )
+        .unwrap();
+        assert_eq!(wrap.get_sources().unwrap()[0].branch, Some("v1.0".to_string()));
+
+        let wrap = MesonWrap::parse(
+            "libbaz",
+            r###"
+[wrap-git]
+directory = libbaz-1.0
+url = https://github.com/louib/libbaz.git
+revision = 0b5ec4f2a1d2b1c69b4a3e5a0c1b5b2d8f0e7c3a
+patch_filename = libbaz_1.0-1_patch.zip
+patch_url = https://wrapdb.mesonbuild.com/v2/libbaz_1.0-1/get_patch
+patch_hash = 9d6e7c2a1f5e8e5a3bb1ba2d8b4c5a9b8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a
+diff_files = libbaz/0001-fix-build.patch, libbaz/0002-fix-tests.patch
+"###,
+        )
+        .unwrap();
+        let sources = wrap.get_sources().unwrap();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[1].dest, Some(PACKAGE_CACHE_DIR.to_string()));
+        assert_eq!(
+            sources[1].dest_filename,
+            Some("libbaz_1.0-1_patch.zip".to_string())
+        );
+        assert_eq!(
+            sources[2].commands,
+            Some(vec![
+                "python3 -c \"import shutil; shutil.unpack_archive('subprojects/packagecache/libbaz_1.0-1_patch.zip', 'subprojects')\"".to_string(),
+                "patch -p1 -d subprojects/libbaz-1.0 < subprojects/packagefiles/libbaz/0001-fix-build.patch".to_string(),
+                "patch -p1 -d subprojects/libbaz-1.0 < subprojects/packagefiles/libbaz/0002-fix-tests.patch".to_string(),
+            ])
+        );
+
+        assert!(MesonWrap::parse("invalid", "[provide]\nfoo = foo_dep\n").is_err());
+    }
+
+    #[test]
+    pub fn test_get_project_name() {
+        assert_eq!(
+            get_project_name("project('my-app', 'c',\n  version: '0.1.0')\n"),
+            Some("my-app".to_string())
+        );
+        assert_eq!(
+            get_project_name("project(\n  \"my-app\")"),
+            Some("my-app".to_string())
+        );
+        assert_eq!(get_project_name("executable('app', 'main.c')"), None);
+    }
+}
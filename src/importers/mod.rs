@@ -1,38 +1,282 @@
-use std::fs;
+use std::path;
 
 use flatpak_rs::module::FlatpakModule;
+use serde::{Deserialize, Serialize};
 
+pub mod bundler;
 pub mod cargo;
+pub mod cmake;
+pub mod external;
 pub mod goget;
+pub mod maven;
+pub mod merge;
+pub mod meson;
+pub mod npm;
+pub mod nuget;
+pub mod pip;
 pub mod vcpkg;
+pub mod yarn;
 
-pub enum PackageManager {
-    Cargo,
-    GoGet,
-    Vcpkg,
-}
-impl PackageManager {
-    pub fn detect_from_manifest_path(manifest_path: &str) -> Option<PackageManager> {
-        if manifest_path.ends_with("Cargo.toml") {
-            return Some(PackageManager::Cargo);
-        }
-        None
-    }
-
-    pub fn import_packages(&self, manifest_path: &str) -> FlatpakModule {
-        match &self {
-            PackageManager::Cargo => {
-                // This could be made more robust by replacing only the string at the
-                // end, but this is good enough nonetheless.
-                let cargo_lock_path = manifest_path.replace("Cargo.toml", "Cargo.lock");
-                let cargo_lock_content = match fs::read_to_string(&cargo_lock_path) {
-                    Ok(c) => c,
-                    Err(e) => panic!("Could not read Cargo.lock file at {}: {}", &cargo_lock_path, e),
-                };
-                return cargo::get_cargo_module(&cargo_lock_content);
+/// Options used when importing the packages of a project.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// The features to enable, for the package managers supporting them.
+    pub features: Vec<String>,
+    /// The packages of the workspace to build, for the package managers supporting them.
+    pub packages: Vec<String>,
+    /// The target triples to import the dependencies for, for the package managers supporting
+    /// them. The dependencies of all the platforms are imported when empty.
+    pub targets: Vec<String>,
+}
+
+/// Directories in which the dependencies of a project are usually downloaded or built, and
+/// which should not be scanned for manifests.
+pub const IGNORED_DIRS: [&str; 5] = ["node_modules", "target", "vendor", "_deps", "build"];
+
+/// The result of importing a dependency manifest.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ImportResult {
+    /// The module building the dependencies, with one source per package.
+    pub module: FlatpakModule,
+    /// The entries of the dependency manifest that could not be converted to sources,
+    /// and need to be handled manually.
+    pub unsupported_entries: Vec<String>,
+}
+impl ImportResult {
+    pub fn new(module: FlatpakModule) -> ImportResult {
+        ImportResult {
+            module,
+            unsupported_entries: vec![],
+        }
+    }
+}
+
+/// An importer converts the dependency manifest of an ecosystem to a Flatpak module.
+pub trait Importer {
+    /// The name of the importer, as used on the command line.
+    fn get_name(&self) -> String;
+
+    /// Whether the importer can handle the dependency manifest at this path.
+    fn file_path_matches(&self, manifest_path: &str) -> bool;
+
+    /// Parses the dependency manifest and generates the module building its dependencies.
+    fn import(&self, manifest_path: &str, options: &ImportOptions) -> Result<ImportResult, String>;
+}
+
+/// The importers available, in the order in which they are used for detection.
+pub struct ImporterRegistry {
+    importers: Vec<Box<dyn Importer>>,
+}
+impl Default for ImporterRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ImporterRegistry {
+    /// Creates a registry with the builtin importers, followed by the external importers
+    /// found in the PATH.
+    pub fn new() -> ImporterRegistry {
+        let mut registry = ImporterRegistry::with_builtin_importers();
+        for importer in external::get_external_importers() {
+            if registry.get_importer(&importer.get_name()).is_some() {
+                log::warn!(
+                    "Ignoring external importer {} since an importer with the same name already exists.",
+                    importer.get_path().display()
+                );
+                continue;
             }
-            PackageManager::Vcpkg => panic!("Not implemented yet."),
-            PackageManager::GoGet => panic!("Not implemented yet."),
+            registry.register(Box::new(importer));
         }
+        registry
+    }
+
+    pub fn with_builtin_importers() -> ImporterRegistry {
+        ImporterRegistry {
+            importers: vec![
+                Box::new(cargo::CargoImporter {}),
+                Box::new(cmake::CMakeImporter {}),
+                Box::new(goget::GoImporter {}),
+                Box::new(vcpkg::VCPKGImporter {}),
+                Box::new(maven::MavenImporter {}),
+                Box::new(meson::MesonImporter {}),
+                Box::new(npm::NpmImporter {}),
+                Box::new(nuget::NugetImporter {}),
+                Box::new(pip::PipImporter {}),
+                Box::new(bundler::BundlerImporter {}),
+                Box::new(yarn::YarnImporter {}),
+            ],
+        }
+    }
+
+    pub fn register(&mut self, importer: Box<dyn Importer>) {
+        self.importers.push(importer);
+    }
+
+    pub fn get_importer_names(&self) -> Vec<String> {
+        self.importers.iter().map(|i| i.get_name()).collect()
+    }
+
+    pub fn get_importer(&self, name: &str) -> Option<&dyn Importer> {
+        self.importers
+            .iter()
+            .find(|i| i.get_name() == name.to_lowercase())
+            .map(|i| i.as_ref())
+    }
+
+    pub fn detect_from_manifest_path(&self, manifest_path: &str) -> Option<&dyn Importer> {
+        self.importers
+            .iter()
+            .find(|i| i.file_path_matches(manifest_path))
+            .map(|i| i.as_ref())
+    }
+}
+
+/// Finds all the dependency manifests of a project. When a package manager has manifests in
+/// multiple directories, only the top-most ones are kept, since the nested manifests are usually
+/// part of the same project, like the members of a Cargo workspace.
+pub fn detect_manifests<'a>(
+    project_dir: &path::Path,
+    registry: &'a ImporterRegistry,
+) -> Result<Vec<(String, &'a dyn Importer)>, String> {
+    let mut file_paths = fpm_core::utils::get_all_paths(project_dir)?;
+    file_paths.retain(|p| p.is_file());
+    file_paths.sort_by_key(|p| (p.components().count(), p.to_path_buf()));
+
+    let mut manifests: Vec<(String, &dyn Importer)> = vec![];
+    for file_path in file_paths {
+        let relative_path = file_path.strip_prefix(project_dir).unwrap_or(&file_path);
+        let is_ignored = relative_path.parent().is_some_and(|d| {
+            d.iter().any(|c| {
+                let c = c.to_str().unwrap_or("");
+                c.starts_with('.') || IGNORED_DIRS.contains(&c)
+            })
+        });
+        if is_ignored {
+            continue;
+        }
+
+        let file_path_str = match file_path.to_str() {
+            Some(p) => p,
+            None => continue,
+        };
+        let importer = match registry.detect_from_manifest_path(file_path_str) {
+            Some(i) => i,
+            None => continue,
+        };
+        let manifest_dir = file_path.parent().unwrap_or(project_dir);
+        let is_nested = manifests.iter().any(|(m, i)| {
+            i.get_name() == importer.get_name()
+                && manifest_dir.starts_with(path::Path::new(m).parent().unwrap_or(project_dir))
+        });
+        if is_nested {
+            log::debug!(
+                "Ignoring nested {} manifest {}.",
+                importer.get_name(),
+                file_path_str
+            );
+            continue;
+        }
+        manifests.push((file_path_str.to_string(), importer));
+    }
+    Ok(manifests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    struct TestImporter {}
+    impl Importer for TestImporter {
+        fn get_name(&self) -> String {
+            "test".to_string()
+        }
+
+        fn file_path_matches(&self, manifest_path: &str) -> bool {
+            manifest_path.ends_with("test.lock")
+        }
+
+        fn import(&self, _manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+            let mut import_result = ImportResult::new(FlatpakModule::default());
+            import_result.unsupported_entries.push("foo".to_string());
+            Ok(import_result)
+        }
+    }
+
+    #[test]
+    pub fn test_importer_registry() {
+        let mut registry = ImporterRegistry::with_builtin_importers();
+        assert!(registry.get_importer("test").is_none());
+        assert_eq!(
+            registry
+                .detect_from_manifest_path("./Cargo.toml")
+                .unwrap()
+                .get_name(),
+            "cargo"
+        );
+        assert_eq!(registry.get_importer("Go").unwrap().get_name(), "go");
+
+        registry.register(Box::new(TestImporter {}));
+        let importer = registry.detect_from_manifest_path("./deps/test.lock").unwrap();
+        assert_eq!(importer.get_name(), "test");
+        let import_result = importer
+            .import("./deps/test.lock", &ImportOptions::default())
+            .unwrap();
+        assert_eq!(import_result.unsupported_entries, vec!["foo".to_string()]);
+    }
+
+    #[test]
+    pub fn test_detect_manifests() {
+        let project_dir =
+            std::env::temp_dir().join(format!("fpm-test-detect-manifests-{}", std::process::id()));
+        for dir in [
+            "crates/foo",
+            "web/node_modules/left-pad",
+            "subprojects",
+            "src/gui",
+        ] {
+            fs::create_dir_all(project_dir.join(dir)).unwrap();
+        }
+        for file in [
+            "Cargo.toml",
+            "crates/foo/Cargo.toml",
+            "web/package-lock.json",
+            "web/node_modules/left-pad/package-lock.json",
+            "meson.build",
+            "src/gui/meson.build",
+            "src/gui/requirements.txt",
+            "README.md",
+        ] {
+            fs::write(project_dir.join(file), "").unwrap();
+        }
+
+        let registry = ImporterRegistry::with_builtin_importers();
+        let manifests = detect_manifests(&project_dir, &registry).unwrap();
+        let _ = fs::remove_dir_all(&project_dir);
+
+        let manifests: Vec<(String, String)> = manifests
+            .into_iter()
+            .map(|(m, i)| {
+                (
+                    m.strip_prefix(project_dir.to_str().unwrap()).unwrap().to_string(),
+                    i.get_name(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            manifests,
+            vec![
+                ("/Cargo.toml".to_string(), "cargo".to_string()),
+                ("/meson.build".to_string(), "meson".to_string()),
+                ("/web/package-lock.json".to_string(), "npm".to_string()),
+                ("/src/gui/requirements.txt".to_string(), "pip".to_string()),
+            ]
+        );
     }
 }
@@ -0,0 +1,567 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use base64::Engine;
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+
+use serde::{Deserialize, Serialize};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const DEFAULT_CACHE_DESTINATION: &str = "flatpak-node/npm-cache";
+// The key used by npm when caching the packages downloaded from a registry.
+pub const CACHE_KEY_PREFIX: &str = "make-fetch-happen:request-cache:";
+
+/// Main structure for a npm lockfile (`package-lock.json`). All the lockfile
+/// versions are supported. Lockfile version 1 only has the dependencies tree, version 3
+/// only has the packages, and version 2 has both.
+/// See https://docs.npmjs.com/cli/v8/configuring-npm/package-lock-json
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NpmPackageLock {
+    pub name: String,
+
+    pub version: String,
+
+    pub lockfile_version: i64,
+
+    pub packages: BTreeMap<String, NpmLockPackage>,
+
+    pub dependencies: BTreeMap<String, NpmLockDependency>,
+}
+impl NpmPackageLock {
+    pub fn file_path_matches(path: &str) -> bool {
+        path.ends_with("package-lock.json") || path.ends_with("npm-shrinkwrap.json")
+    }
+
+    pub fn parse(manifest_path: &str, manifest_content: &str) -> Result<NpmPackageLock, String> {
+        if !NpmPackageLock::file_path_matches(manifest_path) {
+            return Err(format!("{} is not a npm lockfile.", manifest_path));
+        }
+        let package_lock: NpmPackageLock = match serde_json::from_str(manifest_content) {
+            Ok(m) => m,
+            Err(e) => {
+                return Err(format!("Failed to parse the npm lockfile: {}.", e));
+            }
+        };
+        if package_lock.lockfile_version < 1 || package_lock.lockfile_version > 3 {
+            return Err(format!(
+                "Unsupported npm lockfile version {}.",
+                package_lock.lockfile_version
+            ));
+        }
+        Ok(package_lock)
+    }
+
+    /// Gets the tarballs of all the packages downloaded from a registry, without duplicates.
+    /// The packages that are linked, bundled or fetched from a git repository are ignored.
+    pub fn get_resolved_packages(&self) -> Vec<NpmResolvedPackage> {
+        self.resolve_packages().0
+    }
+
+    /// Gets the packages which are not downloaded from a registry, like the git dependencies,
+    /// and have to be handled manually.
+    pub fn get_unsupported_packages(&self) -> Vec<String> {
+        self.resolve_packages().1
+    }
+
+    fn resolve_packages(&self) -> (Vec<NpmResolvedPackage>, Vec<String>) {
+        let mut resolved_packages: Vec<NpmResolvedPackage> = vec![];
+        let mut unsupported_packages: Vec<String> = vec![];
+
+        if !self.packages.is_empty() {
+            for (package_path, package) in &self.packages {
+                // This is the root project.
+                if package_path.is_empty() {
+                    continue;
+                }
+                if package.link == Some(true) || package.in_bundle == Some(true) {
+                    continue;
+                }
+                match NpmResolvedPackage::new(
+                    package_path,
+                    package.resolved.as_ref(),
+                    package.integrity.as_ref(),
+                ) {
+                    Ok(p) => resolved_packages.push(p),
+                    Err(e) => unsupported_packages.push(e),
+                }
+            }
+        } else {
+            let mut next_dependencies: Vec<(&String, &NpmLockDependency)> = self.dependencies.iter().collect();
+            while let Some((dependency_name, dependency)) = next_dependencies.pop() {
+                next_dependencies.extend(dependency.dependencies.iter());
+                if dependency.bundled == Some(true) {
+                    continue;
+                }
+                let resolved = dependency.resolved.as_ref().or(
+                    // The git dependencies only have their url in the version field.
+                    Some(&dependency.version).filter(|v| v.contains("://")),
+                );
+                match NpmResolvedPackage::new(dependency_name, resolved, dependency.integrity.as_ref()) {
+                    Ok(p) => resolved_packages.push(p),
+                    Err(e) => unsupported_packages.push(e),
+                }
+            }
+        }
+
+        resolved_packages.sort_by(|a, b| a.url.cmp(&b.url));
+        resolved_packages.dedup_by(|a, b| a.url == b.url);
+        unsupported_packages.sort();
+        unsupported_packages.dedup();
+        (resolved_packages, unsupported_packages)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NpmLockPackage {
+    pub version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_bundle: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dev: Option<bool>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NpmLockDependency {
+    pub version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundled: Option<bool>,
+
+    pub dependencies: BTreeMap<String, NpmLockDependency>,
+}
+
+/// A package tarball downloaded from a registry.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct NpmResolvedPackage {
+    pub url: String,
+    pub integrity: Integrity,
+}
+impl NpmResolvedPackage {
+    /// Resolves a package of the lockfile. When the package cannot be downloaded from a registry,
+    /// the error describes the unsupported entry.
+    fn new(
+        package_name: &str,
+        resolved: Option<&String>,
+        integrity: Option<&String>,
+    ) -> Result<NpmResolvedPackage, String> {
+        let url = match resolved {
+            Some(r) => r,
+            None => return Err(format!("{} (not resolved)", package_name)),
+        };
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(format!("{} ({})", package_name, url));
+        }
+        let integrity = match integrity.map(|i| Integrity::parse(i)) {
+            Some(Ok(i)) => i,
+            Some(Err(e)) => return Err(format!("{} (invalid integrity: {})", package_name, e)),
+            None => return Err(format!("{} (no integrity)", package_name)),
+        };
+        Ok(NpmResolvedPackage {
+            url: url.to_string(),
+            integrity,
+        })
+    }
+
+    /// Gets the source downloading the tarball directly into the content directory
+    /// of the npm cache.
+    /// See https://github.com/npm/cacache
+    pub fn get_content_source(&self) -> FlatpakSource {
+        let digest = &self.integrity.digest;
+        let mut content_source = FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(self.url.to_string()),
+            dest: Some(format!(
+                "{}/_cacache/content-v2/{}/{}/{}",
+                DEFAULT_CACHE_DESTINATION,
+                self.integrity.algorithm,
+                &digest[0..2],
+                &digest[2..4]
+            )),
+            dest_filename: Some(digest[4..].to_string()),
+            ..Default::default()
+        };
+        self.integrity.set_checksum(&mut content_source);
+        content_source
+    }
+
+    /// Gets the path and the content of the index entry mapping the tarball url to the
+    /// cached content.
+    pub fn get_index_entry(&self) -> (String, String) {
+        let key = format!("{}{}", CACHE_KEY_PREFIX, self.url);
+        let key_hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+        let index_path = format!(
+            "{}/_cacache/index-v5/{}/{}/{}",
+            DEFAULT_CACHE_DESTINATION,
+            &key_hash[0..2],
+            &key_hash[2..4],
+            &key_hash[4..]
+        );
+
+        let index_entry = serde_json::json!({
+            "key": key,
+            "integrity": self.integrity.to_sri(),
+            "time": 0,
+            "metadata": {
+                "url": self.url,
+                "reqHeaders": {},
+                "resHeaders": {},
+            },
+        })
+        .to_string();
+        let index_entry_hash = format!("{:x}", Sha1::digest(index_entry.as_bytes()));
+        (index_path, format!("\n{}\t{}", index_entry_hash, index_entry))
+    }
+}
+
+/// A Subresource Integrity hash, as used by npm and yarn.
+/// See https://w3c.github.io/webappsec-subresource-integrity/
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct Integrity {
+    pub algorithm: String,
+    /// The hex-encoded digest.
+    pub digest: String,
+}
+impl Integrity {
+    /// Parses an integrity string. When multiple hashes are provided, the strongest
+    /// one supported by flatpak-builder is used.
+    pub fn parse(integrity: &str) -> Result<Integrity, String> {
+        let mut best_integrity: Option<Integrity> = None;
+        for hash in integrity.split_whitespace() {
+            let (algorithm, digest) = match hash.split_once('-') {
+                Some((a, d)) => (a, d),
+                None => return Err(format!("Invalid integrity {}.", hash)),
+            };
+            if Integrity::get_strength(algorithm) == 0 {
+                log::debug!("Unsupported integrity algorithm {}.", algorithm);
+                continue;
+            }
+            // Integrity options are not used by npm.
+            let digest = digest.split('?').next().unwrap_or(digest);
+            let digest = match base64::engine::general_purpose::STANDARD.decode(digest) {
+                Ok(d) => d,
+                Err(e) => return Err(format!("Invalid integrity digest {}: {}.", digest, e)),
+            };
+            let digest: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+
+            let is_stronger = match &best_integrity {
+                Some(i) => Integrity::get_strength(algorithm) > Integrity::get_strength(&i.algorithm),
+                None => true,
+            };
+            if is_stronger {
+                best_integrity = Some(Integrity {
+                    algorithm: algorithm.to_string(),
+                    digest,
+                });
+            }
+        }
+        match best_integrity {
+            Some(i) => Ok(i),
+            None => Err(format!("No supported hash in integrity {}.", integrity)),
+        }
+    }
+
+    fn get_strength(algorithm: &str) -> i32 {
+        match algorithm {
+            "sha1" => 1,
+            "sha256" => 2,
+            "sha512" => 3,
+            _ => 0,
+        }
+    }
+
+    /// Gets the integrity in the Subresource Integrity format.
+    pub fn to_sri(&self) -> String {
+        let mut digest: Vec<u8> = vec![];
+        for i in (0..self.digest.len()).step_by(2) {
+            digest.push(u8::from_str_radix(&self.digest[i..i + 2], 16).unwrap_or(0));
+        }
+        format!(
+            "{}-{}",
+            self.algorithm,
+            base64::engine::general_purpose::STANDARD.encode(digest)
+        )
+    }
+
+    pub fn set_checksum(&self, source: &mut FlatpakSource) {
+        match self.algorithm.as_str() {
+            "sha1" => source.sha1 = Some(self.digest.to_string()),
+            "sha256" => source.sha256 = Some(self.digest.to_string()),
+            _ => source.sha512 = Some(self.digest.to_string()),
+        }
+    }
+}
+
+pub fn get_sources(package_lock: &NpmPackageLock) -> Vec<FlatpakSource> {
+    let mut sources: Vec<FlatpakSource> = vec![];
+    let mut index_commands: Vec<String> = vec![];
+    for resolved_package in package_lock.get_resolved_packages() {
+        sources.push(resolved_package.get_content_source());
+
+        let (index_path, index_entry) = resolved_package.get_index_entry();
+        let index_dir = index_path.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+        index_commands.push(format!(
+            "mkdir -p {} && printf '%s' '{}' >> {}",
+            index_dir,
+            index_entry.replace('\'', "'\\''"),
+            index_path
+        ));
+    }
+    if !index_commands.is_empty() {
+        sources.push(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(index_commands),
+            ..Default::default()
+        });
+    }
+    sources
+}
+
+/// Gets a module name from the name of a package. flatpak-builder builds a module in a
+/// directory named after it, so the scope of a scoped package, like `@scope/app`, cannot
+/// be used as is.
+pub fn get_module_name(package_name: &str) -> String {
+    package_name.trim_start_matches('@').replace('/', "-")
+}
+
+pub fn get_npm_module(package_lock: &NpmPackageLock) -> FlatpakModule {
+    let module_name = if package_lock.name.is_empty() {
+        "npm_sources".to_string()
+    } else {
+        get_module_name(&package_lock.name)
+    };
+
+    let mut build_options_env: BTreeMap<String, String> = BTreeMap::new();
+    build_options_env.insert(
+        "npm_config_cache".to_string(),
+        format!("/run/build/{}/{}", module_name, DEFAULT_CACHE_DESTINATION),
+    );
+    build_options_env.insert("npm_config_offline".to_string(), "true".to_string());
+
+    let mut npm_module = FlatpakModule {
+        name: module_name,
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        build_options: Some(FlatpakBuildOptions {
+            env: FlatpakBuildOptionsEnv::Dict(build_options_env),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    npm_module.build_commands.push("npm ci --offline".to_string());
+
+    npm_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    for source in get_sources(package_lock) {
+        npm_module.sources.push(FlatpakSourceItem::Description(source));
+    }
+    npm_module
+}
+
+pub struct NpmImporter {}
+impl Importer for NpmImporter {
+    fn get_name(&self) -> String {
+        "npm".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        NpmPackageLock::file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        let package_lock_content = match fs::read_to_string(manifest_path) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not read npm lockfile at {}: {}", manifest_path, e)),
+        };
+        let package_lock = NpmPackageLock::parse(manifest_path, &package_lock_content)?;
+        let mut import_result = ImportResult::new(get_npm_module(&package_lock));
+        import_result.unsupported_entries = package_lock.get_unsupported_packages();
+        Ok(import_result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_module_name() {
+        assert_eq!(get_module_name("my-app"), "my-app");
+        assert_eq!(get_module_name("@scope/app"), "scope-app");
+    }
+
+    #[test]
+    pub fn test_parse_integrity() {
+        let integrity = Integrity::parse(
+            "sha512-Lmd7Cdn1Akf1v1GAAivZYCO3JP2xB3RQSLBE3Kq2pkmzSCKcRjHEy4PDbPNw/jpFQXSJ8Ej2hg1QJ74ZCgSJxQ==",
+        )
+        .unwrap();
+        assert_eq!(integrity.algorithm, "sha512");
+        assert_eq!(integrity.digest.len(), 128);
+        assert!(integrity
+            .digest
+            .starts_with("2e677b09d9f50247f5bf5180022bd96023b724fdb1077450"));
+        assert_eq!(
+            integrity.to_sri(),
+            "sha512-Lmd7Cdn1Akf1v1GAAivZYCO3JP2xB3RQSLBE3Kq2pkmzSCKcRjHEy4PDbPNw/jpFQXSJ8Ej2hg1QJ74ZCgSJxQ=="
+        );
+
+        let integrity =
+            Integrity::parse("sha1-2KAlxaZ/FNFOD2bLv8bDpNE4rDs= sha512-Lmd7Cdn1Akf1v1GAAivZYCO3JP2xB3RQSLBE3Kq2pkmzSCKcRjHEy4PDbPNw/jpFQXSJ8Ej2hg1QJ74ZCgSJxQ==").unwrap();
+        assert_eq!(integrity.algorithm, "sha512");
+
+        let integrity = Integrity::parse("sha1-2KAlxaZ/FNFOD2bLv8bDpNE4rDs=").unwrap();
+        assert_eq!(integrity.digest, "d8a025c5a67f14d14e0f66cbbfc6c3a4d138ac3b");
+
+        assert!(Integrity::parse("md5-abcd").is_err());
+        assert!(Integrity::parse("invalid").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_lockfile_v1() {
+        let package_lock = NpmPackageLock::parse(
+            "package-lock.json",
+            r###"
+            {
+              "name": "my-app",
+              "version": "1.0.0",
+              "lockfileVersion": 1,
+              "requires": true,
+              "dependencies": {
+                "left-pad": {
+                  "version": "1.3.0",
+                  "resolved": "https://registry.npmjs.org/left-pad/-/left-pad-1.3.0.tgz",
+                  "integrity": "sha1-2KAlxaZ/FNFOD2bLv8bDpNE4rDs="
+                },
+                "express": {
+                  "version": "4.17.1",
+                  "resolved": "https://registry.npmjs.org/express/-/express-4.17.1.tgz",
+                  "integrity": "sha512-Lmd7Cdn1Akf1v1GAAivZYCO3JP2xB3RQSLBE3Kq2pkmzSCKcRjHEy4PDbPNw/jpFQXSJ8Ej2hg1QJ74ZCgSJxQ==",
+                  "dependencies": {
+                    "left-pad": {
+                      "version": "1.2.0",
+                      "resolved": "https://registry.npmjs.org/left-pad/-/left-pad-1.2.0.tgz",
+                      "integrity": "sha1-2KAlxaZ/FNFOD2bLv8bDpNE4rDs="
+                    }
+                  }
+                },
+                "my-fork": {
+                  "version": "git+https://github.com/louib/my-fork.git#abcdef",
+                  "from": "git+https://github.com/louib/my-fork.git"
+                }
+              }
+            }
+            "###,
+        )
+        .unwrap();
+
+        let resolved_packages = package_lock.get_resolved_packages();
+        assert_eq!(resolved_packages.len(), 3);
+        assert_eq!(
+            package_lock.get_unsupported_packages(),
+            vec!["my-fork (git+https://github.com/louib/my-fork.git#abcdef)"]
+        );
+        assert_eq!(
+            resolved_packages[0].url,
+            "https://registry.npmjs.org/express/-/express-4.17.1.tgz"
+        );
+    }
+
+    #[test]
+    pub fn test_parse_lockfile_v3() {
+        let package_lock = NpmPackageLock::parse(
+            "package-lock.json",
+            r###"
+            {
+              "name": "my-app",
+              "version": "1.0.0",
+              "lockfileVersion": 3,
+              "packages": {
+                "": {
+                  "name": "my-app",
+                  "version": "1.0.0"
+                },
+                "node_modules/express": {
+                  "version": "4.17.1",
+                  "resolved": "https://registry.npmjs.org/express/-/express-4.17.1.tgz",
+                  "integrity": "sha512-Lmd7Cdn1Akf1v1GAAivZYCO3JP2xB3RQSLBE3Kq2pkmzSCKcRjHEy4PDbPNw/jpFQXSJ8Ej2hg1QJ74ZCgSJxQ=="
+                },
+                "node_modules/local-lib": {
+                  "resolved": "packages/local-lib",
+                  "link": true
+                }
+              }
+            }
+            "###,
+        )
+        .unwrap();
+
+        let sources = get_sources(&package_lock);
+        assert_eq!(sources.len(), 2);
+        assert_eq!(
+            sources[0].dest,
+            Some("flatpak-node/npm-cache/_cacache/content-v2/sha512/2e/67".to_string())
+        );
+        assert_eq!(
+            sources[0].dest_filename,
+            Some(package_lock.get_resolved_packages()[0].integrity.digest[4..].to_string())
+        );
+        assert!(sources[0].sha512.is_some());
+
+        let index_commands = sources[1].commands.as_ref().unwrap();
+        assert_eq!(index_commands.len(), 1);
+        assert!(index_commands[0].starts_with("mkdir -p flatpak-node/npm-cache/_cacache/index-v5/"));
+    }
+
+    #[test]
+    pub fn test_parse_unsupported_lockfile_version() {
+        assert!(NpmPackageLock::parse("package-lock.json", r#"{"lockfileVersion": 4}"#).is_err());
+        assert!(NpmPackageLock::parse("package.json", r#"{"lockfileVersion": 3}"#).is_err());
+    }
+}
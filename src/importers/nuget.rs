@@ -0,0 +1,471 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use serde::{Deserialize, Serialize};
+
+use crate::importers::npm::Integrity;
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const NUGET_FLAT_CONTAINER_URL: &str = "https://api.nuget.org/v3-flatcontainer";
+pub const DEFAULT_DESTINATION: &str = "nuget-sources";
+/// The name of the generated NuGet configuration, which is distinct from the
+/// `nuget.config` file the project might already have.
+pub const NUGET_CONFIG_FILE_NAME: &str = "nuget.flatpak.config";
+/// The .NET version of the SDK extension used when it cannot be found from the target frameworks.
+pub const DEFAULT_DOTNET_VERSION: &str = "8";
+/// The runtime identifiers of the Flatpak architectures. The runtime packs of the project have
+/// to be in the lockfile for these runtime identifiers, so that they are available offline.
+pub const RUNTIME_IDENTIFIERS: [(&str, &str); 2] = [("x86_64", "linux-x64"), ("aarch64", "linux-arm64")];
+
+/// Main structure for a NuGet lockfile (`packages.lock.json`).
+/// See https://learn.microsoft.com/en-us/nuget/consume-packages/package-references-in-project-files#locking-dependencies
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NugetPackagesLock {
+    pub version: i64,
+
+    /// The packages for every target framework of the project.
+    pub dependencies: BTreeMap<String, BTreeMap<String, NugetLockPackage>>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NugetLockPackage {
+    pub r#type: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+
+    /// The base64-encoded sha512 of the package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+}
+
+/// The assets file generated by `dotnet restore` (`obj/project.assets.json`).
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NugetProjectAssets {
+    pub version: i64,
+
+    /// The libraries used by the project, indexed by `id/version`.
+    pub libraries: BTreeMap<String, NugetAssetsLibrary>,
+
+    /// The libraries used for every target framework and runtime identifier of the project.
+    pub targets: BTreeMap<String, serde_json::Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project: Option<serde_json::Value>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct NugetAssetsLibrary {
+    pub r#type: String,
+
+    /// The base64-encoded sha512 of the package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha512: Option<String>,
+}
+
+/// A package downloaded from the NuGet gallery.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub struct NugetPackage {
+    pub id: String,
+    pub version: String,
+    pub sha512: String,
+}
+impl NugetPackage {
+    pub fn new(id: &str, version: &str, content_hash: &str) -> Result<NugetPackage, String> {
+        let integrity = Integrity::parse(&format!("sha512-{}", content_hash))?;
+        Ok(NugetPackage {
+            id: id.to_string(),
+            version: version.to_string(),
+            sha512: integrity.digest,
+        })
+    }
+
+    /// Gets the file name of the package. The packages are stored in lower case
+    /// in the NuGet feeds.
+    pub fn get_filename(&self) -> String {
+        format!("{}.{}.nupkg", self.id.to_lowercase(), self.version.to_lowercase())
+    }
+
+    pub fn get_source(&self) -> FlatpakSource {
+        FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(format!(
+                "{}/{}/{}/{}",
+                NUGET_FLAT_CONTAINER_URL,
+                self.id.to_lowercase(),
+                self.version.to_lowercase(),
+                self.get_filename()
+            )),
+            sha512: Some(self.sha512.to_string()),
+            dest: Some(DEFAULT_DESTINATION.to_string()),
+            dest_filename: Some(self.get_filename()),
+            ..Default::default()
+        }
+    }
+}
+
+pub fn file_path_matches(path: &str) -> bool {
+    path.ends_with("packages.lock.json") || path.ends_with("project.assets.json")
+}
+
+/// Parses a `packages.lock.json` file. The packages which are not resolved are returned
+/// as unsupported entries.
+pub fn parse_packages_lock(lock_content: &str) -> Result<(Vec<NugetPackage>, Vec<String>), String> {
+    let packages_lock: NugetPackagesLock = match serde_json::from_str(lock_content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Failed to parse packages.lock.json: {}.", e)),
+    };
+
+    let mut packages: Vec<NugetPackage> = vec![];
+    let mut unsupported_entries: Vec<String> = vec![];
+    for (target_framework, target_packages) in &packages_lock.dependencies {
+        for (package_id, package) in target_packages {
+            // The other projects of the solution are not fetched from a feed.
+            if package.r#type == "Project" {
+                continue;
+            }
+            let (version, content_hash) = match (&package.resolved, &package.content_hash) {
+                (Some(v), Some(h)) => (v, h),
+                _ => {
+                    unsupported_entries.push(format!("{} ({}, not resolved)", package_id, target_framework));
+                    continue;
+                }
+            };
+            packages.push(NugetPackage::new(package_id, version, content_hash)?);
+        }
+    }
+    packages.sort_by_key(|p| p.get_filename());
+    packages.dedup_by(|a, b| a.get_filename() == b.get_filename());
+    Ok((packages, unsupported_entries))
+}
+
+pub fn parse_project_assets(assets_content: &str) -> Result<Vec<NugetPackage>, String> {
+    let project_assets: NugetProjectAssets = match serde_json::from_str(assets_content) {
+        Ok(a) => a,
+        Err(e) => return Err(format!("Failed to parse project.assets.json: {}.", e)),
+    };
+
+    let mut packages: Vec<NugetPackage> = vec![];
+    for (library_key, library) in &project_assets.libraries {
+        if library.r#type != "package" {
+            continue;
+        }
+        let (package_id, version) = match library_key.split_once('/') {
+            Some((i, v)) => (i, v),
+            None => return Err(format!("Invalid library {} in project.assets.json.", library_key)),
+        };
+        let content_hash = match &library.sha512 {
+            Some(h) => h,
+            None => return Err(format!("No sha512 for library {}.", library_key)),
+        };
+        packages.push(NugetPackage::new(package_id, version, content_hash)?);
+    }
+    packages.sort_by_key(|p| p.get_filename());
+    Ok(packages)
+}
+
+/// Gets the name of the project, from the assets file or from the project file next
+/// to the lockfile.
+fn get_project_name(manifest_path: &str, manifest_content: &str) -> Option<String> {
+    if manifest_path.ends_with("project.assets.json") {
+        let project_assets: NugetProjectAssets = serde_json::from_str(manifest_content).ok()?;
+        let project_name = project_assets.project?["restore"]["projectName"]
+            .as_str()?
+            .to_string();
+        return Some(project_name);
+    }
+    let project_dir = path::Path::new(manifest_path).parent()?;
+    let project_dir = if project_dir.as_os_str().is_empty() {
+        path::Path::new(".")
+    } else {
+        project_dir
+    };
+    for project_dir_entry in fs::read_dir(project_dir).ok()? {
+        let project_file_path = project_dir_entry.ok()?.path();
+        if project_file_path.extension().and_then(|e| e.to_str()) == Some("csproj") {
+            return project_file_path.file_stem()?.to_str().map(|n| n.to_string());
+        }
+    }
+    None
+}
+
+/// Gets the target frameworks of the lockfile or of the assets file, with their runtime
+/// identifier when there is one, for example `net8.0/linux-x64`.
+fn get_targets(manifest_path: &str, manifest_content: &str) -> Vec<String> {
+    if manifest_path.ends_with("project.assets.json") {
+        return match serde_json::from_str::<NugetProjectAssets>(manifest_content) {
+            Ok(a) => a.targets.into_keys().collect(),
+            Err(_) => vec![],
+        };
+    }
+    match serde_json::from_str::<NugetPackagesLock>(manifest_content) {
+        Ok(l) => l.dependencies.into_keys().collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Gets the major version of .NET from the target frameworks, for example `8` for `net8.0`.
+/// The .NET Framework and .NET Standard target frameworks are ignored.
+pub fn get_dotnet_version(targets: &[String]) -> Option<String> {
+    for target in targets {
+        let target_framework = target.split('/').next().unwrap_or("");
+        if let Some((major_version, _)) = target_framework
+            .strip_prefix("net")
+            .and_then(|v| v.split_once('.'))
+        {
+            if !major_version.is_empty() && major_version.chars().all(|c| c.is_ascii_digit()) {
+                return Some(major_version.to_string());
+            }
+        }
+    }
+    None
+}
+
+pub fn get_nuget_config_command() -> String {
+    format!(
+        "printf '%s\\n' '<?xml version=\"1.0\" encoding=\"utf-8\"?>' '<configuration>' '  <packageSources>' '    <clear />' '    <add key=\"flatpak\" value=\"./{}\" />' '  </packageSources>' '</configuration>' > {}",
+        DEFAULT_DESTINATION, NUGET_CONFIG_FILE_NAME
+    )
+}
+
+pub fn get_nuget_module(manifest_path: &str) -> Result<ImportResult, String> {
+    let manifest_content = match fs::read_to_string(manifest_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read {}: {}", manifest_path, e)),
+    };
+    let (packages, unsupported_entries) = if manifest_path.ends_with("project.assets.json") {
+        (parse_project_assets(&manifest_content)?, vec![])
+    } else {
+        parse_packages_lock(&manifest_content)?
+    };
+
+    let targets = get_targets(manifest_path, &manifest_content);
+    for (_, runtime_identifier) in RUNTIME_IDENTIFIERS {
+        if !targets
+            .iter()
+            .any(|t| t.ends_with(&format!("/{}", runtime_identifier)))
+        {
+            log::warn!(
+                "{} has no packages for runtime {}, add it to the RuntimeIdentifiers of the project so that its runtime packs are available offline.",
+                manifest_path,
+                runtime_identifier
+            );
+        }
+    }
+    let dotnet_version = get_dotnet_version(&targets).unwrap_or(DEFAULT_DOTNET_VERSION.to_string());
+    log::warn!(
+        "The org.freedesktop.Sdk.Extension.dotnet{} extension has to be added to the sdk-extensions of the Flatpak manifest.",
+        dotnet_version
+    );
+
+    let mut build_options_env: BTreeMap<String, String> = BTreeMap::new();
+    build_options_env.insert("DOTNET_CLI_TELEMETRY_OPTOUT".to_string(), "true".to_string());
+    build_options_env.insert("DOTNET_NOLOGO".to_string(), "true".to_string());
+
+    // The runtime identifier depends on the architecture the application is built for.
+    let mut arch_build_options: BTreeMap<String, FlatpakBuildOptions> = BTreeMap::new();
+    for (arch, runtime_identifier) in RUNTIME_IDENTIFIERS {
+        let mut arch_env: BTreeMap<String, String> = BTreeMap::new();
+        arch_env.insert("RUNTIME_IDENTIFIER".to_string(), runtime_identifier.to_string());
+        arch_build_options.insert(
+            arch.to_string(),
+            FlatpakBuildOptions {
+                env: FlatpakBuildOptionsEnv::Dict(arch_env),
+                ..Default::default()
+            },
+        );
+    }
+
+    let mut nuget_module = FlatpakModule {
+        name: get_project_name(manifest_path, &manifest_content).unwrap_or("dotnet-project".to_string()),
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        build_options: Some(FlatpakBuildOptions {
+            append_path: format!("/usr/lib/sdk/dotnet{}/bin", dotnet_version),
+            append_ld_library_path: format!("/usr/lib/sdk/dotnet{}/lib", dotnet_version),
+            env: FlatpakBuildOptionsEnv::Dict(build_options_env),
+            arch: arch_build_options,
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    nuget_module.build_commands.push(format!(
+        "dotnet restore -r ${{RUNTIME_IDENTIFIER}} --configfile {}",
+        NUGET_CONFIG_FILE_NAME
+    ));
+    nuget_module.build_commands.push(
+        "dotnet publish -c Release -r ${RUNTIME_IDENTIFIER} --self-contained --no-restore -o ${FLATPAK_DEST}/bin"
+            .to_string(),
+    );
+
+    nuget_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    for package in &packages {
+        nuget_module
+            .sources
+            .push(FlatpakSourceItem::Description(package.get_source()));
+    }
+    nuget_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(vec![get_nuget_config_command()]),
+            ..Default::default()
+        }));
+
+    let mut import_result = ImportResult::new(nuget_module);
+    import_result.unsupported_entries = unsupported_entries;
+    Ok(import_result)
+}
+
+pub struct NugetImporter {}
+impl Importer for NugetImporter {
+    fn get_name(&self) -> String {
+        "nuget".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        get_nuget_module(manifest_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_packages_lock() {
+        let (packages, unsupported_entries) = parse_packages_lock(
+            r###"
+            {
+              "version": 1,
+              "dependencies": {
+                "net6.0": {
+                  "Newtonsoft.Json": {
+                    "type": "Direct",
+                    "requested": "[13.0.1, )",
+                    "resolved": "13.0.1",
+                    "contentHash": "ppPFpBcvxdsfUonNcvITKqLl3bqxWbDCZIzDWHzjpdAHRFfZe0Dw9HmA0+za13IdyrgJwpkDTDA9fHaxOrt20A=="
+                  },
+                  "MyLibrary": {
+                    "type": "Project"
+                  },
+                  "MyPlugin": {
+                    "type": "Direct",
+                    "requested": "[1.0.0, )"
+                  }
+                },
+                "net7.0": {
+                  "Newtonsoft.Json": {
+                    "type": "Direct",
+                    "requested": "[13.0.1, )",
+                    "resolved": "13.0.1",
+                    "contentHash": "ppPFpBcvxdsfUonNcvITKqLl3bqxWbDCZIzDWHzjpdAHRFfZe0Dw9HmA0+za13IdyrgJwpkDTDA9fHaxOrt20A=="
+                  }
+                }
+              }
+            }
+            "###,
+        )
+        .unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].get_filename(), "newtonsoft.json.13.0.1.nupkg");
+        assert_eq!(packages[0].sha512.len(), 128);
+        assert_eq!(unsupported_entries, vec!["MyPlugin (net6.0, not resolved)"]);
+
+        let source = packages[0].get_source();
+        assert_eq!(
+            source.url,
+            Some(
+                "https://api.nuget.org/v3-flatcontainer/newtonsoft.json/13.0.1/newtonsoft.json.13.0.1.nupkg"
+                    .to_string()
+            )
+        );
+        assert_eq!(source.dest, Some(DEFAULT_DESTINATION.to_string()));
+    }
+
+    #[test]
+    pub fn test_get_dotnet_version() {
+        assert_eq!(
+            get_dotnet_version(&["net8.0/linux-x64".to_string()]),
+            Some("8".to_string())
+        );
+        assert_eq!(
+            get_dotnet_version(&["netstandard2.0".to_string(), "net6.0".to_string()]),
+            Some("6".to_string())
+        );
+        assert_eq!(get_dotnet_version(&["net48".to_string()]), None);
+    }
+
+    #[test]
+    pub fn test_parse_project_assets() {
+        let assets_content = r###"
+            {
+              "version": 3,
+              "libraries": {
+                "Newtonsoft.Json/13.0.1": {
+                  "sha512": "ppPFpBcvxdsfUonNcvITKqLl3bqxWbDCZIzDWHzjpdAHRFfZe0Dw9HmA0+za13IdyrgJwpkDTDA9fHaxOrt20A==",
+                  "type": "package",
+                  "path": "newtonsoft.json/13.0.1"
+                },
+                "MyLibrary/1.0.0": {
+                  "type": "project",
+                  "path": "../MyLibrary/MyLibrary.csproj"
+                }
+              },
+              "project": {
+                "version": "1.0.0",
+                "restore": {
+                  "projectName": "MyApp"
+                }
+              }
+            }
+            "###;
+        let packages = parse_project_assets(assets_content).unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].id, "Newtonsoft.Json");
+        assert_eq!(
+            get_project_name("obj/project.assets.json", assets_content),
+            Some("MyApp".to_string())
+        );
+    }
+}
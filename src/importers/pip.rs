@@ -0,0 +1,1134 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use serde::{Deserialize, Serialize};
+
+use crate::importers::{ImportOptions, ImportResult, Importer};
+
+pub const PYPI_URL: &str = "https://pypi.org/pypi";
+pub const DEFAULT_MODULE_NAME: &str = "python3-requirements";
+
+/// A pinned Python package, with the sha256 checksums of the distributions allowed
+/// to be installed for it.
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct PythonRequirement {
+    pub name: String,
+    pub version: String,
+    pub hashes: Vec<String>,
+    /// The distributions of the package, when they are listed in the lockfile.
+    pub distributions: Vec<PythonDistribution>,
+}
+impl PythonRequirement {
+    /// Gets the distribution to install for the requirement, fetching the list of distributions
+    /// from PyPI when the lockfile does not provide their urls.
+    pub fn get_distribution(&self) -> Result<PythonDistribution, String> {
+        let mut distributions = self.distributions.clone();
+        if distributions.is_empty() {
+            distributions = get_pypi_distributions(&self.name, &self.version)?;
+        }
+        if !self.hashes.is_empty() {
+            distributions.retain(|d| self.hashes.contains(&d.sha256));
+        }
+        match select_distribution(&distributions) {
+            Some(d) => Ok(d.clone()),
+            None => Err(format!(
+                "Could not find a pure Python wheel or a source distribution for {}=={}.",
+                self.name, self.version
+            )),
+        }
+    }
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+#[derive(PartialEq)]
+pub struct PythonDistribution {
+    pub filename: String,
+    pub url: String,
+    pub sha256: String,
+}
+impl PythonDistribution {
+    /// Whether the distribution is a wheel which can be installed on any platform with Python 3.
+    pub fn is_pure_wheel(&self) -> bool {
+        if !self.filename.ends_with("-none-any.whl") {
+            return false;
+        }
+        // The python tag comes before the abi and platform tags, and can contain multiple
+        // tags separated by dots, like `py2.py3`.
+        let python_tag = match self.filename.trim_end_matches(".whl").rsplit('-').nth(2) {
+            Some(t) => t,
+            None => return false,
+        };
+        python_tag
+            .split('.')
+            .any(|t| t.starts_with("py3") || t.starts_with("cp3"))
+    }
+
+    pub fn is_sdist(&self) -> bool {
+        self.filename.ends_with(".tar.gz") || self.filename.ends_with(".zip")
+    }
+}
+
+/// Selects the distribution to install. Pure Python wheels are preferred, since they don't
+/// need to be built, and platform-specific wheels and Python 2 wheels are never used.
+pub fn select_distribution(distributions: &[PythonDistribution]) -> Option<&PythonDistribution> {
+    if let Some(wheel) = distributions.iter().find(|d| d.is_pure_wheel()) {
+        return Some(wheel);
+    }
+    distributions.iter().find(|d| d.is_sdist())
+}
+
+/// Normalizes a package name as described in PEP 503.
+pub fn normalize_package_name(name: &str) -> String {
+    let mut normalized_name = String::new();
+    for c in name.trim().to_lowercase().chars() {
+        if c == '-' || c == '_' || c == '.' {
+            if !normalized_name.ends_with('-') {
+                normalized_name.push('-');
+            }
+        } else {
+            normalized_name.push(c);
+        }
+    }
+    normalized_name
+}
+
+fn parse_hash(hash: &str) -> Option<String> {
+    hash.trim()
+        .trim_matches('"')
+        .strip_prefix("sha256:")
+        .map(|h| h.to_string())
+}
+
+pub fn get_pypi_distributions(name: &str, version: &str) -> Result<Vec<PythonDistribution>, String> {
+    let release_url = format!("{}/{}/{}/json", PYPI_URL, name, version);
+    let release_metadata = crate::utils::fetch_json(&release_url)?;
+    let release_files = match release_metadata["urls"].as_array() {
+        Some(f) => f,
+        None => return Err(format!("No distributions for {}=={} on PyPI.", name, version)),
+    };
+    let mut distributions: Vec<PythonDistribution> = vec![];
+    for release_file in release_files {
+        let (filename, url, sha256) = match (
+            release_file["filename"].as_str(),
+            release_file["url"].as_str(),
+            release_file["digests"]["sha256"].as_str(),
+        ) {
+            (Some(f), Some(u), Some(s)) => (f, u, s),
+            _ => continue,
+        };
+        distributions.push(PythonDistribution {
+            filename: filename.to_string(),
+            url: url.to_string(),
+            sha256: sha256.to_string(),
+        });
+    }
+    Ok(distributions)
+}
+
+/// The Python versions of the flatpak runtimes, used to evaluate the environment markers.
+pub const PYTHON_VERSIONS: [&str; 5] = ["3.9", "3.10", "3.11", "3.12", "3.13"];
+/// The architectures supported by flatpak, as reported by `platform.machine()`.
+pub const PLATFORM_MACHINES: [&str; 2] = ["x86_64", "aarch64"];
+
+/// The result of evaluating an environment marker in all the environments a flatpak
+/// can be built for.
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+#[derive(PartialEq)]
+pub enum MarkerResult {
+    Always,
+    Never,
+    /// The result depends on the Python version or on the architecture.
+    Sometimes,
+}
+impl MarkerResult {
+    /// Combines the results of markers joined with `or`.
+    pub fn any(results: impl IntoIterator<Item = MarkerResult>) -> MarkerResult {
+        let mut combined_result = MarkerResult::Never;
+        for result in results {
+            match result {
+                MarkerResult::Always => return MarkerResult::Always,
+                MarkerResult::Sometimes => combined_result = MarkerResult::Sometimes,
+                MarkerResult::Never => {}
+            }
+        }
+        combined_result
+    }
+
+    /// Combines the results of markers joined with `and`.
+    pub fn all(results: impl IntoIterator<Item = MarkerResult>) -> MarkerResult {
+        let mut combined_result = MarkerResult::Always;
+        for result in results {
+            match result {
+                MarkerResult::Never => return MarkerResult::Never,
+                MarkerResult::Sometimes => combined_result = MarkerResult::Sometimes,
+                MarkerResult::Always => {}
+            }
+        }
+        combined_result
+    }
+}
+
+/// Gets the values of the environment marker variables in the environments a flatpak can be
+/// built for. The variables which depend on the build machine are not known.
+fn get_marker_environments() -> Vec<BTreeMap<&'static str, String>> {
+    let mut environments: Vec<BTreeMap<&str, String>> = vec![];
+    for python_version in PYTHON_VERSIONS {
+        for platform_machine in PLATFORM_MACHINES {
+            let mut environment: BTreeMap<&str, String> = BTreeMap::new();
+            environment.insert("os_name", "posix".to_string());
+            environment.insert("sys_platform", "linux".to_string());
+            environment.insert("platform_system", "Linux".to_string());
+            environment.insert("platform_machine", platform_machine.to_string());
+            environment.insert("platform_python_implementation", "CPython".to_string());
+            environment.insert("implementation_name", "cpython".to_string());
+            environment.insert("python_version", python_version.to_string());
+            environment.insert("python_full_version", format!("{}.0", python_version));
+            environment.insert("implementation_version", format!("{}.0", python_version));
+            environments.push(environment);
+        }
+    }
+    environments
+}
+
+/// Splits an environment marker into parentheses, quoted strings, operators and words.
+fn tokenize_marker(marker: &str) -> Result<Vec<String>, String> {
+    let mut tokens: Vec<String> = vec![];
+    let mut chars = marker.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c == '(' || c == ')' {
+            tokens.push(c.to_string());
+        } else if c == '"' || c == '\'' {
+            let mut token = c.to_string();
+            loop {
+                match chars.next() {
+                    Some(n) if n == c => break,
+                    Some(n) => token.push(n),
+                    None => return Err(format!("Unterminated string in marker {}", marker)),
+                }
+            }
+            tokens.push(token);
+        } else if "=!<>~".contains(c) {
+            let mut token = c.to_string();
+            while let Some(n) = chars.next_if(|n| "=!<>~".contains(*n)) {
+                token.push(n);
+            }
+            tokens.push(token);
+        } else {
+            let mut token = c.to_string();
+            while let Some(n) = chars.next_if(|n| n.is_alphanumeric() || *n == '_' || *n == '.') {
+                token.push(n);
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+/// Evaluates the environment markers of a requirement.
+/// See https://packaging.python.org/en/latest/specifications/dependency-specifiers/#environment-markers
+pub fn evaluate_marker(marker: &str) -> Result<MarkerResult, String> {
+    let tokens = tokenize_marker(marker)?;
+    let mut results: Vec<bool> = vec![];
+    for environment in get_marker_environments() {
+        let mut position = 0;
+        let result = evaluate_marker_or(&tokens, &mut position, &environment)?;
+        if position != tokens.len() {
+            return Err(format!("Invalid marker {}", marker));
+        }
+        results.push(result);
+    }
+    if results.iter().all(|r| *r) {
+        return Ok(MarkerResult::Always);
+    }
+    if results.iter().all(|r| !*r) {
+        return Ok(MarkerResult::Never);
+    }
+    Ok(MarkerResult::Sometimes)
+}
+
+fn evaluate_marker_or(
+    tokens: &[String],
+    position: &mut usize,
+    environment: &BTreeMap<&str, String>,
+) -> Result<bool, String> {
+    let mut result = evaluate_marker_and(tokens, position, environment)?;
+    while tokens.get(*position).is_some_and(|t| t == "or") {
+        *position += 1;
+        result |= evaluate_marker_and(tokens, position, environment)?;
+    }
+    Ok(result)
+}
+
+fn evaluate_marker_and(
+    tokens: &[String],
+    position: &mut usize,
+    environment: &BTreeMap<&str, String>,
+) -> Result<bool, String> {
+    let mut result = evaluate_marker_atom(tokens, position, environment)?;
+    while tokens.get(*position).is_some_and(|t| t == "and") {
+        *position += 1;
+        result &= evaluate_marker_atom(tokens, position, environment)?;
+    }
+    Ok(result)
+}
+
+fn evaluate_marker_atom(
+    tokens: &[String],
+    position: &mut usize,
+    environment: &BTreeMap<&str, String>,
+) -> Result<bool, String> {
+    if tokens.get(*position).is_some_and(|t| t == "(") {
+        *position += 1;
+        let result = evaluate_marker_or(tokens, position, environment)?;
+        if tokens.get(*position).is_none_or(|t| t != ")") {
+            return Err("Unbalanced parentheses in marker".to_string());
+        }
+        *position += 1;
+        return Ok(result);
+    }
+
+    let left = get_marker_value(tokens.get(*position), environment)?;
+    let mut operator = match tokens.get(*position + 1) {
+        Some(o) => o.to_string(),
+        None => return Err("Incomplete marker".to_string()),
+    };
+    *position += 2;
+    if operator == "not" && tokens.get(*position).is_some_and(|t| t == "in") {
+        operator = "not in".to_string();
+        *position += 1;
+    }
+    let right = get_marker_value(tokens.get(*position), environment)?;
+    *position += 1;
+    compare_marker_values(&left, &operator, &right)
+}
+
+fn get_marker_value(token: Option<&String>, environment: &BTreeMap<&str, String>) -> Result<String, String> {
+    let token = match token {
+        Some(t) => t,
+        None => return Err("Incomplete marker".to_string()),
+    };
+    if let Some(literal) = token.strip_prefix(['"', '\'']) {
+        return Ok(literal.to_string());
+    }
+    match environment.get(token.as_str()) {
+        Some(v) => Ok(v.to_string()),
+        None => Err(format!("unsupported marker variable {}", token)),
+    }
+}
+
+fn parse_marker_version(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|p| p.parse::<u64>().ok()).collect()
+}
+
+fn compare_marker_values(left: &str, operator: &str, right: &str) -> Result<bool, String> {
+    match operator {
+        "in" => return Ok(right.contains(left)),
+        "not in" => return Ok(!right.contains(left)),
+        "===" => return Ok(left == right),
+        _ => {}
+    }
+    // The wildcards are only allowed with the equality operators.
+    if let Some(prefix) = right.strip_suffix(".*") {
+        if let (Some(left_version), Some(prefix)) = (parse_marker_version(left), parse_marker_version(prefix)) {
+            let matches = left_version.starts_with(&prefix);
+            match operator {
+                "==" => return Ok(matches),
+                "!=" => return Ok(!matches),
+                _ => {}
+            }
+        }
+    }
+
+    // The values are compared as versions when they are both valid versions.
+    let (left_version, right_version) = match (parse_marker_version(left), parse_marker_version(right)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => {
+            return match operator {
+                "==" => Ok(left == right),
+                "!=" => Ok(left != right),
+                _ => Err(format!("cannot compare {} and {} with {}", left, right, operator)),
+            }
+        }
+    };
+    let length = left_version.len().max(right_version.len());
+    let pad = |v: &[u64]| -> Vec<u64> {
+        let mut v = v.to_vec();
+        v.resize(length, 0);
+        v
+    };
+    let ordering = pad(&left_version).cmp(&pad(&right_version));
+    match operator {
+        "==" => Ok(ordering.is_eq()),
+        "!=" => Ok(ordering.is_ne()),
+        "<" => Ok(ordering.is_lt()),
+        "<=" => Ok(ordering.is_le()),
+        ">" => Ok(ordering.is_gt()),
+        ">=" => Ok(ordering.is_ge()),
+        "~=" => {
+            // `~= 3.8` means `>= 3.8, == 3.*`.
+            if right_version.len() < 2 {
+                return Err(format!("invalid compatible release {}", right));
+            }
+            let prefix = &right_version[..right_version.len() - 1];
+            Ok(ordering.is_ge() && left_version.starts_with(prefix))
+        }
+        _ => Err(format!("unsupported marker operator {}", operator)),
+    }
+}
+
+pub fn is_requirements_file(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    file_name.starts_with("requirements") && file_name.ends_with(".txt")
+}
+
+pub fn file_path_matches(path: &str) -> bool {
+    is_requirements_file(path)
+        || path.ends_with("pyproject.toml")
+        || path.ends_with("poetry.lock")
+        || path.ends_with("uv.lock")
+}
+
+/// The requirements parsed from a manifest, and the entries of the manifest which cannot
+/// be converted to requirements.
+pub type ParsedRequirements = (Vec<PythonRequirement>, Vec<String>);
+
+/// Parses a pip requirements file. All the requirements must be pinned to a specific version.
+/// The options, the requirements using a direct reference and the requirements whose environment
+/// markers depend on the Python version or the architecture are returned as unsupported entries.
+/// See https://pip.pypa.io/en/stable/reference/requirements-file-format/
+pub fn parse_requirements(requirements_content: &str) -> Result<ParsedRequirements, String> {
+    let mut requirements: Vec<PythonRequirement> = vec![];
+    let mut unsupported_entries: Vec<String> = vec![];
+
+    // Lines ending with a backslash are continued on the next line.
+    let requirements_content = requirements_content.replace("\\\n", " ");
+    for line in requirements_content.lines() {
+        let line = match line.split_once(" #") {
+            Some((l, _)) => l,
+            None => line,
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('-') || line.contains('@') || line.contains("://") {
+            unsupported_entries.push(line.to_string());
+            continue;
+        }
+
+        let (requirement_spec, requirement_options) = match line.split_once(" --") {
+            Some((r, o)) => (r, format!("--{}", o)),
+            None => (line, "".to_string()),
+        };
+        let (specifier, marker) = match requirement_spec.split_once(';') {
+            Some((s, m)) => (s.replace(' ', ""), Some(m.trim())),
+            None => (requirement_spec.replace(' ', ""), None),
+        };
+        if let Some(marker) = marker {
+            match evaluate_marker(marker) {
+                Ok(MarkerResult::Always) => {}
+                Ok(MarkerResult::Never) => {
+                    log::info!("Ignoring {}, which is not used in a flatpak.", specifier);
+                    continue;
+                }
+                Ok(MarkerResult::Sometimes) => {
+                    unsupported_entries.push(format!(
+                        "{} (depends on the Python version or architecture)",
+                        line
+                    ));
+                    continue;
+                }
+                Err(e) => {
+                    unsupported_entries.push(format!("{} ({})", line, e));
+                    continue;
+                }
+            }
+        }
+        let (name, version) = match specifier.split_once("==") {
+            Some((n, v)) => (n, v),
+            None => return Err(format!("Requirement {} is not pinned to a version.", specifier)),
+        };
+        // Extras are ignored.
+        let name = name.split('[').next().unwrap_or(name);
+
+        let mut requirement = PythonRequirement {
+            name: normalize_package_name(name),
+            version: version.to_string(),
+            ..Default::default()
+        };
+        for requirement_option in requirement_options.split_whitespace() {
+            if let Some(hash) = requirement_option.strip_prefix("--hash=") {
+                match parse_hash(hash) {
+                    Some(h) => requirement.hashes.push(h),
+                    None => log::warn!("Unsupported hash {} for {}.", hash, requirement.name),
+                }
+            }
+        }
+        requirements.push(requirement);
+    }
+    Ok((requirements, unsupported_entries))
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct PoetryLock {
+    pub package: Vec<PoetryLockPackage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<PoetryLockMetadata>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct PoetryLockPackage {
+    pub name: String,
+
+    pub version: String,
+
+    pub files: Vec<PoetryLockFile>,
+
+    /// The environment markers of the package, as a single marker or as a marker per
+    /// dependency group.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markers: Option<toml::Value>,
+}
+impl PoetryLockPackage {
+    pub fn get_marker_result(&self) -> MarkerResult {
+        let markers: Vec<&str> = match &self.markers {
+            Some(toml::Value::String(m)) => vec![m],
+            Some(toml::Value::Table(t)) => t.values().filter_map(|m| m.as_str()).collect(),
+            _ => return MarkerResult::Always,
+        };
+        MarkerResult::any(
+            markers
+                .iter()
+                .map(|m| evaluate_marker(m).unwrap_or(MarkerResult::Sometimes)),
+        )
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct PoetryLockMetadata {
+    /// The files of the packages, used before poetry 1.2.
+    pub files: BTreeMap<String, Vec<PoetryLockFile>>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct PoetryLockFile {
+    pub file: String,
+
+    pub hash: String,
+}
+
+pub fn parse_poetry_lock(lock_content: &str) -> Result<ParsedRequirements, String> {
+    let poetry_lock: PoetryLock = match toml::from_str(lock_content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Failed to parse poetry.lock: {}.", e)),
+    };
+    let mut requirements: Vec<PythonRequirement> = vec![];
+    let mut unsupported_entries: Vec<String> = vec![];
+    for package in &poetry_lock.package {
+        let mut files = package.files.clone();
+        if files.is_empty() {
+            if let Some(metadata) = &poetry_lock.metadata {
+                files = metadata.files.get(&package.name).cloned().unwrap_or_default();
+            }
+        }
+        match package.get_marker_result() {
+            MarkerResult::Always => {}
+            MarkerResult::Never => {
+                log::info!("Ignoring {}, which is not used in a flatpak.", package.name);
+                continue;
+            }
+            MarkerResult::Sometimes => {
+                unsupported_entries.push(format!(
+                    "{}=={} (depends on the Python version or architecture)",
+                    package.name, package.version
+                ));
+                continue;
+            }
+        }
+        // This is a package from a local path, a url or a git repository.
+        if files.is_empty() {
+            unsupported_entries.push(format!("{}=={} (no hashes)", package.name, package.version));
+            continue;
+        }
+        requirements.push(PythonRequirement {
+            name: normalize_package_name(&package.name),
+            version: package.version.to_string(),
+            hashes: files.iter().filter_map(|f| parse_hash(&f.hash)).collect(),
+            ..Default::default()
+        });
+    }
+    Ok((requirements, unsupported_entries))
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct UvLock {
+    pub package: Vec<UvLockPackage>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct UvLockPackage {
+    pub name: String,
+
+    pub version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sdist: Option<UvLockDistribution>,
+
+    pub wheels: Vec<UvLockDistribution>,
+
+    pub dependencies: Vec<UvLockDependency>,
+
+    pub optional_dependencies: BTreeMap<String, Vec<UvLockDependency>>,
+
+    /// The markers of the environments this version of the package is used in, when
+    /// different versions are used depending on the environment.
+    pub resolution_markers: Vec<String>,
+}
+impl UvLockPackage {
+    /// Gets the dependencies of the package, including the dependencies of all its extras.
+    pub fn get_dependencies(&self) -> impl Iterator<Item = &UvLockDependency> {
+        self.dependencies
+            .iter()
+            .chain(self.optional_dependencies.values().flatten())
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct UvLockDependency {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct UvLockDistribution {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+impl UvLockDistribution {
+    pub fn to_python_distribution(&self) -> Option<PythonDistribution> {
+        let url = self.url.as_ref()?;
+        let sha256 = parse_hash(self.hash.as_ref()?)?;
+        let filename = url.rsplit('/').next()?;
+        Some(PythonDistribution {
+            filename: filename.to_string(),
+            url: url.to_string(),
+            sha256,
+        })
+    }
+}
+
+/// Gets the environments in which the packages of a uv lockfile are used, by following the
+/// dependencies from the packages which are not a dependency of another package, like the
+/// project itself. The dev dependencies are not followed, since they are not needed to build
+/// the project. The packages which are never used are not returned.
+pub fn get_uv_package_marker_results(uv_lock: &UvLock) -> BTreeMap<String, MarkerResult> {
+    let dependency_names: Vec<String> = uv_lock
+        .package
+        .iter()
+        .flat_map(|p| p.get_dependencies())
+        .map(|d| normalize_package_name(&d.name))
+        .collect();
+    let mut roots: Vec<String> = uv_lock
+        .package
+        .iter()
+        .map(|p| normalize_package_name(&p.name))
+        .filter(|n| !dependency_names.contains(n))
+        .collect();
+    if roots.is_empty() {
+        roots = uv_lock
+            .package
+            .iter()
+            .map(|p| normalize_package_name(&p.name))
+            .collect();
+    }
+
+    let mut marker_results: BTreeMap<String, MarkerResult> = BTreeMap::new();
+    let mut next_packages: Vec<String> = vec![];
+    for root in roots {
+        marker_results.insert(root.to_string(), MarkerResult::Always);
+        next_packages.push(root);
+    }
+    while let Some(package_name) = next_packages.pop() {
+        let package_result = marker_results[&package_name];
+        let packages = uv_lock
+            .package
+            .iter()
+            .filter(|p| normalize_package_name(&p.name) == package_name);
+        for dependency in packages.flat_map(|p| p.get_dependencies()) {
+            let dependency_result = match &dependency.marker {
+                Some(m) => evaluate_marker(m).unwrap_or(MarkerResult::Sometimes),
+                None => MarkerResult::Always,
+            };
+            let dependency_result = MarkerResult::all([package_result, dependency_result]);
+            if dependency_result == MarkerResult::Never {
+                continue;
+            }
+            let dependency_name = normalize_package_name(&dependency.name);
+            let previous_result = marker_results.get(&dependency_name).copied();
+            let result = MarkerResult::any(previous_result.into_iter().chain([dependency_result]));
+            if previous_result != Some(result) {
+                marker_results.insert(dependency_name.to_string(), result);
+                next_packages.push(dependency_name);
+            }
+        }
+    }
+    marker_results
+}
+
+pub fn parse_uv_lock(lock_content: &str) -> Result<ParsedRequirements, String> {
+    let uv_lock: UvLock = match toml::from_str(lock_content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Failed to parse uv.lock: {}.", e)),
+    };
+    let marker_results = get_uv_package_marker_results(&uv_lock);
+    let mut requirements: Vec<PythonRequirement> = vec![];
+    let mut unsupported_entries: Vec<String> = vec![];
+    for package in &uv_lock.package {
+        let mut marker_result = marker_results
+            .get(&normalize_package_name(&package.name))
+            .copied()
+            .unwrap_or(MarkerResult::Never);
+        if !package.resolution_markers.is_empty() {
+            let resolution_result = MarkerResult::any(
+                package
+                    .resolution_markers
+                    .iter()
+                    .map(|m| evaluate_marker(m).unwrap_or(MarkerResult::Sometimes)),
+            );
+            marker_result = MarkerResult::all([marker_result, resolution_result]);
+        }
+        match marker_result {
+            MarkerResult::Always => {}
+            MarkerResult::Never => {
+                log::info!("Ignoring {}, which is not used in a flatpak.", package.name);
+                continue;
+            }
+            MarkerResult::Sometimes => {
+                unsupported_entries.push(format!(
+                    "{}=={} (depends on the Python version or architecture)",
+                    package.name, package.version
+                ));
+                continue;
+            }
+        }
+
+        let mut distributions: Vec<PythonDistribution> = package
+            .wheels
+            .iter()
+            .filter_map(|w| w.to_python_distribution())
+            .collect();
+        if let Some(sdist) = package.sdist.as_ref().and_then(|s| s.to_python_distribution()) {
+            distributions.push(sdist);
+        }
+        // This is the project itself, or a package from a local path or a git repository.
+        if distributions.is_empty() {
+            unsupported_entries.push(format!(
+                "{}=={} (no distributions)",
+                package.name, package.version
+            ));
+            continue;
+        }
+        requirements.push(PythonRequirement {
+            name: normalize_package_name(&package.name),
+            version: package.version.to_string(),
+            hashes: distributions.iter().map(|d| d.sha256.to_string()).collect(),
+            distributions,
+        });
+    }
+    Ok((requirements, unsupported_entries))
+}
+
+/// Gets the requirements from a manifest. For `pyproject.toml` files, the lockfile next to the
+/// manifest is used, since the manifest itself does not pin the dependencies.
+pub fn get_requirements(manifest_path: &str) -> Result<ParsedRequirements, String> {
+    let mut manifest_path = path::PathBuf::from(manifest_path);
+    if manifest_path.ends_with("pyproject.toml") {
+        manifest_path = match ["uv.lock", "poetry.lock"]
+            .iter()
+            .map(|l| manifest_path.with_file_name(l))
+            .find(|l| l.is_file())
+        {
+            Some(l) => l,
+            None => {
+                return Err(format!(
+                    "No uv.lock or poetry.lock file next to {}.",
+                    manifest_path.display()
+                ))
+            }
+        };
+    }
+
+    let manifest_content = match fs::read_to_string(&manifest_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read {}: {}", manifest_path.display(), e)),
+    };
+    if manifest_path.ends_with("uv.lock") {
+        return parse_uv_lock(&manifest_content);
+    }
+    if manifest_path.ends_with("poetry.lock") {
+        return parse_poetry_lock(&manifest_content);
+    }
+    parse_requirements(&manifest_content)
+}
+
+pub fn get_pip_module(requirements: &[PythonRequirement]) -> Result<FlatpakModule, String> {
+    let mut pip_module = FlatpakModule {
+        name: DEFAULT_MODULE_NAME.to_string(),
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        ..Default::default()
+    };
+
+    let mut pinned_requirements: Vec<String> = vec![];
+    for requirement in requirements {
+        let distribution = requirement.get_distribution()?;
+        pip_module
+            .sources
+            .push(FlatpakSourceItem::Description(FlatpakSource {
+                r#type: Some(FlatpakSourceType::File),
+                url: Some(distribution.url),
+                sha256: Some(distribution.sha256),
+                dest_filename: Some(distribution.filename),
+                ..Default::default()
+            }));
+        pinned_requirements.push(format!("\"{}=={}\"", requirement.name, requirement.version));
+    }
+
+    pip_module.build_commands.push(format!(
+        "pip3 install --exists-action=i --no-index --find-links=\"file://${{PWD}}\" --prefix=${{FLATPAK_DEST}} --no-build-isolation {}",
+        pinned_requirements.join(" ")
+    ));
+    Ok(pip_module)
+}
+
+pub struct PipImporter {}
+impl Importer for PipImporter {
+    fn get_name(&self) -> String {
+        "pip".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        let (requirements, unsupported_entries) = get_requirements(manifest_path)?;
+        let mut import_result = ImportResult::new(get_pip_module(&requirements)?);
+        import_result.unsupported_entries = unsupported_entries;
+        Ok(import_result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_normalize_package_name() {
+        assert_eq!(normalize_package_name("Django"), "django");
+        assert_eq!(normalize_package_name("zope.interface"), "zope-interface");
+        assert_eq!(normalize_package_name("typing__extensions"), "typing-extensions");
+    }
+
+    #[test]
+    pub fn test_parse_requirements() {
+        let (requirements, unsupported_entries) = parse_requirements(
+            r###"
+# This is a comment.
+--index-url https://pypi.org/simple
+my-fork @ git+https://github.com/louib/my-fork.git
+requests[security]==2.25.1 \
+    --hash=sha256:c210084e36a42ae6b9219e00e48287def368a26d03a048ddad7bfee44f75871e \
+    --hash=sha256:27973dd4a904a4f13b263a19c866c13b92a39ed1c964655f025f3f8d3d75b804
+six==1.16.0 ; python_version >= "3.6"  # via requests
+pywin32==306 ; sys_platform == "win32"
+tomli==2.0.1 ; python_version < "3.11"
+"###,
+        )
+        .unwrap();
+        assert_eq!(requirements.len(), 2);
+        assert_eq!(requirements[0].name, "requests");
+        assert_eq!(requirements[0].version, "2.25.1");
+        assert_eq!(requirements[0].hashes.len(), 2);
+        assert_eq!(requirements[1].name, "six");
+        assert_eq!(requirements[1].version, "1.16.0");
+        assert!(requirements[1].hashes.is_empty());
+        assert_eq!(
+            unsupported_entries,
+            vec![
+                "--index-url https://pypi.org/simple",
+                "my-fork @ git+https://github.com/louib/my-fork.git",
+                "tomli==2.0.1 ; python_version < \"3.11\" (depends on the Python version or architecture)"
+            ]
+        );
+
+        assert!(parse_requirements("requests>=2.0").is_err());
+    }
+
+    #[test]
+    pub fn test_parse_poetry_lock() {
+        let (requirements, unsupported_entries) = parse_poetry_lock(
+            r###"
+[[package]]
+name = "six"
+version = "1.16.0"
+description = "Python 2 and 3 compatibility utilities"
+optional = false
+python-versions = ">=2.7, !=3.0.*, !=3.1.*, !=3.2.*"
+files = [
+    {file = "six-1.16.0-py2.py3-none-any.whl", hash = "sha256:8abb2f1d86890a2dfb989f9a77cfcfd3e47c2a354b01111771326f8aa26e0254"},
+    {file = "six-1.16.0.tar.gz", hash = "sha256:1e61c37477a1626458e36f7b1d82aa5c9b094fa4802892072e49de9c60c4c926"},
+]
+
+[[package]]
+name = "Local_Package"
+version = "0.1.0"
+"###,
+        )
+        .unwrap();
+        assert_eq!(requirements.len(), 1);
+        assert_eq!(requirements[0].name, "six");
+        assert_eq!(requirements[0].hashes.len(), 2);
+        assert_eq!(unsupported_entries, vec!["Local_Package==0.1.0 (no hashes)"]);
+    }
+
+    #[test]
+    pub fn test_parse_uv_lock() {
+        let (requirements, unsupported_entries) = parse_uv_lock(
+            r###"
+version = 1
+requires-python = ">=3.8"
+
+[[package]]
+name = "my-project"
+version = "0.1.0"
+source = { editable = "." }
+
+[[package]]
+name = "six"
+version = "1.16.0"
+source = { registry = "https://pypi.org/simple" }
+sdist = { url = "https://files.pythonhosted.org/packages/71/39/171f1c67cd00715f190ba0b100d606d440a28c93c7714febeca8b79af85e/six-1.16.0.tar.gz", hash = "sha256:1e61c37477a1626458e36f7b1d82aa5c9b094fa4802892072e49de9c60c4c926", size = 34041 }
+wheels = [
+    { url = "https://files.pythonhosted.org/packages/d9/5a/e7c31adbe875f2abbb91bd84cf2dc52d792b5a01506781dbcf25c91daf11/six-1.16.0-py2.py3-none-any.whl", hash = "sha256:8abb2f1d86890a2dfb989f9a77cfcfd3e47c2a354b01111771326f8aa26e0254", size = 11053 },
+]
+"###,
+        )
+        .unwrap();
+        assert_eq!(requirements.len(), 1);
+        assert_eq!(requirements[0].distributions.len(), 2);
+        assert_eq!(unsupported_entries, vec!["my-project==0.1.0 (no distributions)"]);
+
+        let distribution = requirements[0].get_distribution().unwrap();
+        assert_eq!(distribution.filename, "six-1.16.0-py2.py3-none-any.whl");
+
+        let pip_module = get_pip_module(&requirements).unwrap();
+        assert_eq!(pip_module.sources.len(), 1);
+        assert!(pip_module.build_commands[0].ends_with("\"six==1.16.0\""));
+    }
+
+    #[test]
+    pub fn test_evaluate_marker() {
+        assert_eq!(
+            evaluate_marker("python_version >= \"3.6\" and os_name == 'posix'"),
+            Ok(MarkerResult::Always)
+        );
+        assert_eq!(
+            evaluate_marker(
+                "sys_platform == \"win32\" or (python_version < '3' and platform_system != 'Linux')"
+            ),
+            Ok(MarkerResult::Never)
+        );
+        assert_eq!(
+            evaluate_marker("platform_machine == 'x86_64'"),
+            Ok(MarkerResult::Sometimes)
+        );
+        assert_eq!(
+            evaluate_marker("python_version ~= '3.9'"),
+            Ok(MarkerResult::Always)
+        );
+        assert_eq!(
+            evaluate_marker("python_full_version == '3.12.*'"),
+            Ok(MarkerResult::Sometimes)
+        );
+        assert_eq!(
+            evaluate_marker("'linux' in sys_platform"),
+            Ok(MarkerResult::Always)
+        );
+        assert!(evaluate_marker("extra == 'socks'").is_err());
+        assert!(evaluate_marker("python_version >= '3.6' and").is_err());
+    }
+
+    #[test]
+    pub fn test_select_distribution() {
+        let get_distribution = |filename: &str| PythonDistribution {
+            filename: filename.to_string(),
+            ..Default::default()
+        };
+        let distributions = vec![
+            get_distribution("futures-3.0.5-py2-none-any.whl"),
+            get_distribution("futures-3.0.5.tar.gz"),
+        ];
+        assert_eq!(
+            select_distribution(&distributions).unwrap().filename,
+            "futures-3.0.5.tar.gz"
+        );
+        assert!(get_distribution("six-1.16.0-py2.py3-none-any.whl").is_pure_wheel());
+        assert!(get_distribution("attrs-23.1.0-py3-none-any.whl").is_pure_wheel());
+        assert!(!get_distribution("numpy-1.26.0-cp312-cp312-manylinux_2_17_x86_64.whl").is_pure_wheel());
+    }
+
+    #[test]
+    pub fn test_lockfile_markers() {
+        let (requirements, unsupported_entries) = parse_poetry_lock(
+            r###"
+[[package]]
+name = "colorama"
+version = "0.4.6"
+markers = "sys_platform == \"win32\""
+files = [
+    {file = "colorama-0.4.6-py2.py3-none-any.whl", hash = "sha256:4f1d9991f5acc0ca119f9d443620b77f9d6b33703e51011c16baf57afb285fc6"},
+]
+
+[[package]]
+name = "tomli"
+version = "2.0.1"
+markers = {main = "python_version < \"3.11\""}
+files = [
+    {file = "tomli-2.0.1-py3-none-any.whl", hash = "sha256:939de3e7a6161af0c887ef91b7d41a53e7c5a1ca976325f429cb46ea9bc30ecc"},
+]
+"###,
+        )
+        .unwrap();
+        assert!(requirements.is_empty());
+        assert_eq!(
+            unsupported_entries,
+            vec!["tomli==2.0.1 (depends on the Python version or architecture)"]
+        );
+
+        let (requirements, unsupported_entries) = parse_uv_lock(
+            r###"
+version = 1
+
+[[package]]
+name = "my-project"
+version = "0.1.0"
+source = { editable = "." }
+dependencies = [
+    { name = "colorama", marker = "sys_platform == 'win32'" },
+    { name = "six" },
+]
+
+[package.optional-dependencies]
+toml = [
+    { name = "tomli", marker = "python_full_version < '3.11'" },
+]
+
+[[package]]
+name = "colorama"
+version = "0.4.6"
+source = { registry = "https://pypi.org/simple" }
+dependencies = [
+    { name = "six" },
+]
+wheels = [
+    { url = "https://files.pythonhosted.org/packages/d1/d6/3965ed04c63042e047cb6a3e6ed1a63a35087b6a609aa3a15ed8ac56c221/colorama-0.4.6-py2.py3-none-any.whl", hash = "sha256:4f1d9991f5acc0ca119f9d443620b77f9d6b33703e51011c16baf57afb285fc6" },
+]
+
+[[package]]
+name = "six"
+version = "1.16.0"
+source = { registry = "https://pypi.org/simple" }
+wheels = [
+    { url = "https://files.pythonhosted.org/packages/d9/5a/e7c31adbe875f2abbb91bd84cf2dc52d792b5a01506781dbcf25c91daf11/six-1.16.0-py2.py3-none-any.whl", hash = "sha256:8abb2f1d86890a2dfb989f9a77cfcfd3e47c2a354b01111771326f8aa26e0254" },
+]
+
+[[package]]
+name = "tomli"
+version = "2.0.1"
+source = { registry = "https://pypi.org/simple" }
+wheels = [
+    { url = "https://files.pythonhosted.org/packages/97/75/10a9ebee3fd790d20926a90a2547f0bf78f371b2f13aa822c759680ca7b9/tomli-2.0.1-py3-none-any.whl", hash = "sha256:939de3e7a6161af0c887ef91b7d41a53e7c5a1ca976325f429cb46ea9bc30ecc" },
+]
+"###,
+        )
+        .unwrap();
+        // colorama is only used on Windows.
+        assert_eq!(requirements.len(), 1);
+        assert_eq!(requirements[0].name, "six");
+        assert_eq!(
+            unsupported_entries,
+            vec![
+                "my-project==0.1.0 (no distributions)",
+                "tomli==2.0.1 (depends on the Python version or architecture)"
+            ]
+        );
+    }
+}
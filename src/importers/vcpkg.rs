@@ -1,5 +1,38 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::path;
+use std::process::Command;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::FlatpakModule;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
 use serde::{Deserialize, Serialize};
 
+use crate::importers::cmake;
+use crate::importers::{ImportOptions, ImportResult, Importer};
+use crate::version::SemanticVersion;
+
+pub const VCPKG_REGISTRY_URL: &str = "https://github.com/microsoft/vcpkg.git";
+pub const VCPKG_ROOT_ENV_VAR: &str = "VCPKG_ROOT";
+pub const DEFAULT_DESTINATION: &str = "vcpkg";
+pub const DEFAULT_DOWNLOADS_DESTINATION: &str = "vcpkg/downloads";
+pub const VCPKG_RAW_URL: &str = "https://raw.githubusercontent.com/microsoft/vcpkg";
+pub const VCPKG_TOOL_REPOSITORY_URL: &str = "https://github.com/microsoft/vcpkg-tool.git";
+pub const VCPKG_TOOL_RAW_URL: &str = "https://raw.githubusercontent.com/microsoft/vcpkg-tool";
+pub const VCPKG_TOOL_METADATA_PATH: &str = "scripts/vcpkg-tool-metadata.txt";
+pub const VCPKG_TOOL_DESTINATION: &str = "vcpkg-tool";
+/// The triplets of the architectures supported by Flatpak, against which the `platform`
+/// expressions of the dependencies are evaluated.
+pub const LINUX_TRIPLETS: [&str; 2] = ["x64-linux", "arm64-linux"];
+/// The feature which only selects the dependencies of a port which are not in a feature.
+pub const CORE_FEATURE: &str = "core";
+/// The feature which selects the default features of a port.
+pub const DEFAULT_FEATURE: &str = "default";
+
 /// Main structure for a vcpkg manifest.
 /// See https://vcpkg.readthedocs.io/en/latest/specifications/manifests/
 #[derive(Clone)]
@@ -9,7 +42,32 @@ use serde::{Deserialize, Serialize};
 #[derive(Default)]
 #[serde(rename_all = "kebab-case")]
 #[serde(default)]
-pub struct VCPKGManifest {}
+pub struct VCPKGManifest {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_string: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_semver: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_version: Option<i64>,
+
+    pub dependencies: Vec<VCPKGDependency>,
+
+    pub default_features: Vec<VCPKGFeatureReference>,
+
+    pub features: BTreeMap<String, VCPKGFeature>,
+
+    pub overrides: Vec<VCPKGOverride>,
+
+    /// The commit of the vcpkg registry used to resolve the versions of the ports.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub builtin_baseline: Option<String>,
+}
 impl VCPKGManifest {
     pub fn file_path_matches(path: &str) -> bool {
         path.ends_with("vcpkg.json")
@@ -19,7 +77,7 @@ impl VCPKGManifest {
         let mut vcpkg_manifest: VCPKGManifest = VCPKGManifest::default();
 
         if VCPKGManifest::file_path_matches(&manifest_path.to_lowercase()) {
-            vcpkg_manifest = match serde_json::from_str(&manifest_content) {
+            vcpkg_manifest = match serde_json::from_str(manifest_content) {
                 Ok(m) => m,
                 Err(e) => {
                     return Err(format!("Failed to parse the vcpkg manifest: {}.", e));
@@ -29,6 +87,875 @@ impl VCPKGManifest {
 
         Ok(vcpkg_manifest)
     }
+
+    /// Gets the version of the port, whichever version scheme it uses.
+    pub fn get_version(&self) -> Option<String> {
+        if let Some(v) = &self.version {
+            return Some(v.to_string());
+        }
+        if let Some(v) = &self.version_semver {
+            return Some(v.to_string());
+        }
+        if let Some(v) = &self.version_date {
+            return Some(v.to_string());
+        }
+        if let Some(v) = &self.version_string {
+            return Some(v.to_string());
+        }
+        None
+    }
+
+    /// Gets the names of the default features enabled on Linux.
+    pub fn get_default_features(&self) -> Result<BTreeSet<String>, String> {
+        let mut default_features: BTreeSet<String> = BTreeSet::new();
+        for feature in &self.default_features {
+            if is_linux_platform(feature.get_platform())? {
+                default_features.insert(feature.get_name());
+            }
+        }
+        Ok(default_features)
+    }
+
+    /// Gets the dependencies required on Linux by the given features. The dependencies which
+    /// are not in a feature are included with the `core` feature, and the `default` feature
+    /// is replaced with the default features of the manifest.
+    pub fn get_dependencies(&self, features: &BTreeSet<String>) -> Result<Vec<VCPKGDependency>, String> {
+        let mut feature_names: BTreeSet<String> = features.clone();
+        if feature_names.remove(DEFAULT_FEATURE) {
+            feature_names.extend(self.get_default_features()?);
+        }
+
+        let mut dependencies: Vec<&VCPKGDependency> = vec![];
+        if feature_names.remove(CORE_FEATURE) {
+            dependencies.extend(self.dependencies.iter());
+        }
+        for feature_name in &feature_names {
+            match self.features.get(feature_name) {
+                Some(f) => dependencies.extend(f.dependencies.iter()),
+                None => log::warn!(
+                    "Feature {} is not defined in the vcpkg manifest of {}.",
+                    feature_name,
+                    self.name
+                ),
+            };
+        }
+
+        let mut linux_dependencies: Vec<VCPKGDependency> = vec![];
+        for dependency in dependencies {
+            if is_linux_platform(dependency.get_platform())? {
+                linux_dependencies.push(dependency.clone());
+            }
+        }
+        Ok(linux_dependencies)
+    }
+
+    pub fn get_override(&self, port_name: &str) -> Option<&VCPKGOverride> {
+        self.overrides.iter().find(|o| o.name == port_name)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[serde(untagged)]
+/// A dependency can be either the name of a port, or a full description
+/// of the dependency.
+pub enum VCPKGDependency {
+    Name(String),
+    Description(VCPKGDependencyDescription),
+}
+impl VCPKGDependency {
+    pub fn get_name(&self) -> String {
+        match self {
+            VCPKGDependency::Name(n) => n.to_string(),
+            VCPKGDependency::Description(d) => d.name.to_string(),
+        }
+    }
+
+    pub fn get_platform(&self) -> Option<&str> {
+        match self {
+            VCPKGDependency::Name(_) => None,
+            VCPKGDependency::Description(d) => d.platform.as_deref(),
+        }
+    }
+
+    pub fn get_minimum_version(&self) -> Option<&str> {
+        match self {
+            VCPKGDependency::Name(_) => None,
+            VCPKGDependency::Description(d) => d.minimum_version.as_deref(),
+        }
+    }
+
+    /// Gets the features of the port requested on Linux by the dependency, with the
+    /// `default` feature unless the default features are disabled.
+    pub fn get_features(&self) -> Result<BTreeSet<String>, String> {
+        let mut features: BTreeSet<String> = BTreeSet::new();
+        features.insert(CORE_FEATURE.to_string());
+        let description = match self {
+            VCPKGDependency::Name(_) => {
+                features.insert(DEFAULT_FEATURE.to_string());
+                return Ok(features);
+            }
+            VCPKGDependency::Description(d) => d,
+        };
+        if description.default_features != Some(false) {
+            features.insert(DEFAULT_FEATURE.to_string());
+        }
+        for feature in &description.features {
+            if is_linux_platform(feature.get_platform())? {
+                features.insert(feature.get_name());
+            }
+        }
+        Ok(features)
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[serde(untagged)]
+/// A feature can be either the name of the feature, or the name of the feature
+/// with the platforms on which it is enabled.
+pub enum VCPKGFeatureReference {
+    Name(String),
+    Description(VCPKGFeatureReferenceDescription),
+}
+impl VCPKGFeatureReference {
+    pub fn get_name(&self) -> String {
+        match self {
+            VCPKGFeatureReference::Name(n) => n.to_string(),
+            VCPKGFeatureReference::Description(d) => d.name.to_string(),
+        }
+    }
+
+    pub fn get_platform(&self) -> Option<&str> {
+        match self {
+            VCPKGFeatureReference::Name(_) => None,
+            VCPKGFeatureReference::Description(d) => d.platform.as_deref(),
+        }
+    }
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct VCPKGFeatureReferenceDescription {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct VCPKGDependencyDescription {
+    pub name: String,
+
+    pub features: Vec<VCPKGFeatureReference>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_features: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+
+    #[serde(rename = "version>=")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimum_version: Option<String>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct VCPKGFeature {
+    pub description: String,
+
+    pub dependencies: Vec<VCPKGDependency>,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct VCPKGOverride {
+    pub name: String,
+
+    pub version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port_version: Option<i64>,
+}
+
+/// Gets the identifiers of a triplet which are true in the platform expressions.
+/// See https://learn.microsoft.com/en-us/vcpkg/reference/vcpkg-json#platform-expression
+fn get_triplet_identifiers(triplet: &str) -> Vec<&str> {
+    let mut identifiers: Vec<&str> = vec!["linux", "native", "static"];
+    match triplet.split('-').next() {
+        Some("x64") => identifiers.push("x64"),
+        Some("arm64") => identifiers.extend(["arm64", "arm"]),
+        _ => {}
+    }
+    identifiers
+}
+
+/// Evaluates a platform expression, like `!windows & (x64 | arm64)`, for a triplet.
+/// The comma is handled like an or, and the unknown identifiers are false.
+pub fn evaluate_platform_expression(expression: &str, triplet: &str) -> Result<bool, String> {
+    let mut tokens: Vec<String> = vec![];
+    let mut identifier = String::new();
+    for c in expression.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            identifier.push(c);
+            continue;
+        }
+        if !identifier.is_empty() {
+            tokens.push(std::mem::take(&mut identifier));
+        }
+        match c {
+            '!' | '&' | '|' | ',' | '(' | ')' => tokens.push(c.to_string()),
+            c if c.is_whitespace() => {}
+            _ => return Err(format!("Invalid platform expression {}.", expression)),
+        }
+    }
+    if !identifier.is_empty() {
+        tokens.push(identifier);
+    }
+    let tokens: Vec<&str> = tokens
+        .iter()
+        .map(|t| match t.as_str() {
+            "and" => "&",
+            "or" | "," => "|",
+            "not" => "!",
+            t => t,
+        })
+        .collect();
+
+    let identifiers = get_triplet_identifiers(triplet);
+    let mut position = 0;
+    let result = evaluate_platform_or(&tokens, &mut position, &identifiers);
+    match result {
+        Some(r) if position == tokens.len() => Ok(r),
+        _ => Err(format!("Invalid platform expression {}.", expression)),
+    }
+}
+
+fn evaluate_platform_or(tokens: &[&str], position: &mut usize, identifiers: &[&str]) -> Option<bool> {
+    let mut result = evaluate_platform_and(tokens, position, identifiers)?;
+    while tokens.get(*position) == Some(&"|") {
+        *position += 1;
+        result |= evaluate_platform_and(tokens, position, identifiers)?;
+    }
+    Some(result)
+}
+
+fn evaluate_platform_and(tokens: &[&str], position: &mut usize, identifiers: &[&str]) -> Option<bool> {
+    let mut result = evaluate_platform_unary(tokens, position, identifiers)?;
+    while tokens.get(*position) == Some(&"&") {
+        *position += 1;
+        result &= evaluate_platform_unary(tokens, position, identifiers)?;
+    }
+    Some(result)
+}
+
+fn evaluate_platform_unary(tokens: &[&str], position: &mut usize, identifiers: &[&str]) -> Option<bool> {
+    let token = *tokens.get(*position)?;
+    *position += 1;
+    match token {
+        "!" => Some(!evaluate_platform_unary(tokens, position, identifiers)?),
+        "(" => {
+            let result = evaluate_platform_or(tokens, position, identifiers)?;
+            if tokens.get(*position) != Some(&")") {
+                return None;
+            }
+            *position += 1;
+            Some(result)
+        }
+        "&" | "|" | ")" => None,
+        identifier => Some(identifiers.contains(&identifier)),
+    }
+}
+
+/// Checks if a dependency or a feature with the given platform expression is used on
+/// any of the Linux triplets.
+pub fn is_linux_platform(platform: Option<&str>) -> Result<bool, String> {
+    let platform = match platform {
+        Some(p) if !p.trim().is_empty() => p,
+        _ => return Ok(true),
+    };
+    for triplet in LINUX_TRIPLETS {
+        if evaluate_platform_expression(platform, triplet)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Extracts the pinned downloads from a port's `portfile.cmake`. Only the
+/// `vcpkg_from_github`, `vcpkg_from_gitlab` and `vcpkg_download_distfile`
+/// helpers are supported for now.
+pub fn get_portfile_sources(portfile_content: &str, port_version: &str) -> Vec<FlatpakSource> {
+    let mut sources: Vec<FlatpakSource> = vec![];
+
+    for (function_name, args) in cmake::get_cmake_function_calls(portfile_content) {
+        let args: Vec<String> = args
+            .iter()
+            .map(|a| a.replace("${VERSION}", port_version))
+            .collect();
+
+        if function_name == "vcpkg_from_github" || function_name == "vcpkg_from_gitlab" {
+            let repo = cmake::get_cmake_arg_value(&args, "REPO");
+            let git_ref = cmake::get_cmake_arg_value(&args, "REF");
+            let sha512 = cmake::get_cmake_arg_value(&args, "SHA512");
+            let (repo, git_ref, sha512) = match (repo, git_ref, sha512) {
+                (Some(repo), Some(git_ref), Some(sha512)) => (repo, git_ref, sha512),
+                _ => {
+                    log::warn!(
+                        "Could not find a pinned REF and SHA512 in {} call.",
+                        function_name
+                    );
+                    continue;
+                }
+            };
+
+            let url = if function_name == "vcpkg_from_github" {
+                format!("https://api.github.com/repos/{}/tarball/{}", repo, git_ref)
+            } else {
+                let gitlab_url = match cmake::get_cmake_arg_value(&args, "GITLAB_URL") {
+                    Some(u) => u,
+                    None => {
+                        log::warn!("Missing GITLAB_URL in vcpkg_from_gitlab call for {}.", repo);
+                        continue;
+                    }
+                };
+                let repo_name = repo.split('/').next_back().unwrap_or(&repo).to_string();
+                format!(
+                    "{}/{}/-/archive/{}/{}-{}.tar.gz",
+                    gitlab_url, repo, git_ref, repo_name, git_ref
+                )
+            };
+
+            // This is the name vcpkg expects to find in its downloads directory.
+            let dest_filename = format!("{}-{}.tar.gz", repo, git_ref).replace('/', "-");
+            sources.push(FlatpakSource {
+                r#type: Some(FlatpakSourceType::File),
+                url: Some(url),
+                sha512: Some(sha512.to_lowercase()),
+                dest: Some(DEFAULT_DOWNLOADS_DESTINATION.to_string()),
+                dest_filename: Some(dest_filename),
+                ..Default::default()
+            });
+        } else if function_name == "vcpkg_download_distfile" {
+            let url = cmake::get_cmake_arg_value(&args, "URLS");
+            let filename = cmake::get_cmake_arg_value(&args, "FILENAME");
+            let sha512 = cmake::get_cmake_arg_value(&args, "SHA512");
+            let (url, filename, sha512) = match (url, filename, sha512) {
+                (Some(url), Some(filename), Some(sha512)) => (url, filename, sha512),
+                _ => {
+                    log::warn!("Could not find a pinned URL and SHA512 in vcpkg_download_distfile call.");
+                    continue;
+                }
+            };
+            sources.push(FlatpakSource {
+                r#type: Some(FlatpakSourceType::File),
+                url: Some(url),
+                sha512: Some(sha512.to_lowercase()),
+                dest: Some(DEFAULT_DOWNLOADS_DESTINATION.to_string()),
+                dest_filename: Some(filename),
+                ..Default::default()
+            });
+        }
+    }
+
+    sources
+}
+
+/// Gets the path of a local git clone of the vcpkg registry, if any.
+pub fn get_vcpkg_root() -> Option<String> {
+    match env::var(VCPKG_ROOT_ENV_VAR) {
+        Ok(r) => {
+            if path::Path::new(&r).join(".git").exists() {
+                return Some(r);
+            }
+            log::warn!(
+                "{} is set to {} but it is not a git clone.",
+                VCPKG_ROOT_ENV_VAR,
+                r
+            );
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+/// Splits a version constraint like `7.1.3#1` into the version and the port version.
+pub fn parse_version_constraint(constraint: &str) -> (String, i64) {
+    match constraint.split_once('#') {
+        Some((version, port_version)) => (version.to_string(), port_version.parse().unwrap_or(0)),
+        None => (constraint.to_string(), 0),
+    }
+}
+
+/// Compares two versions. The semantic versions are compared with the precedence rules of
+/// semver, and the other versions component by component, the numeric components as numbers
+/// and the other components as strings.
+pub fn compare_versions(version: &str, other_version: &str) -> Ordering {
+    let semantic_versions = (
+        SemanticVersion::parse(version),
+        SemanticVersion::parse(other_version),
+    );
+    if let (Some(semantic_version), Some(other_semantic_version)) = semantic_versions {
+        return semantic_version.compare(&other_semantic_version);
+    }
+    let components = version.split(['.', '-']);
+    let mut other_components = other_version.split(['.', '-']);
+    for component in components {
+        let other_component = match other_components.next() {
+            Some(c) => c,
+            None => return Ordering::Greater,
+        };
+        let ordering = match (component.parse::<u64>(), other_component.parse::<u64>()) {
+            (Ok(n), Ok(other_n)) => n.cmp(&other_n),
+            _ => component.cmp(other_component),
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    match other_components.next() {
+        Some(_) => Ordering::Less,
+        None => Ordering::Equal,
+    }
+}
+
+/// An entry of the versions database of the registry.
+/// See https://learn.microsoft.com/en-us/vcpkg/reference/registries#versions-database
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "kebab-case")]
+#[serde(default)]
+pub struct VCPKGPortVersion {
+    pub git_tree: String,
+
+    #[serde(flatten)]
+    pub manifest: VCPKGManifest,
+}
+
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(default)]
+pub struct VCPKGPortVersions {
+    pub versions: Vec<VCPKGPortVersion>,
+}
+
+/// Reads the files of the vcpkg registry at the builtin baseline. The files are read from the
+/// git clone of `VCPKG_ROOT` when it contains the baseline, and downloaded from GitHub otherwise.
+pub struct VCPKGRegistry {
+    pub baseline: String,
+    pub vcpkg_root: Option<String>,
+}
+impl VCPKGRegistry {
+    pub fn new(baseline: &str) -> VCPKGRegistry {
+        let vcpkg_root = get_vcpkg_root().filter(|vcpkg_root| {
+            let has_baseline = Command::new("git")
+                .arg("-C")
+                .arg(vcpkg_root)
+                .arg("cat-file")
+                .arg("-e")
+                .arg(format!("{}^{{commit}}", baseline))
+                .status()
+                .is_ok_and(|s| s.success());
+            if !has_baseline {
+                log::warn!(
+                    "The vcpkg registry at {} does not contain the baseline {}, reading it from GitHub.",
+                    vcpkg_root,
+                    baseline
+                );
+            }
+            has_baseline
+        });
+        VCPKGRegistry {
+            baseline: baseline.to_string(),
+            vcpkg_root,
+        }
+    }
+
+    /// Reads a file of the registry at the baseline, or a file of a git tree listed in the
+    /// versions database.
+    pub fn read_file(&self, git_tree: Option<&str>, file_path: &str) -> Result<String, String> {
+        let vcpkg_root = match &self.vcpkg_root {
+            Some(r) => r,
+            None => {
+                if git_tree.is_some() {
+                    return Err(format!(
+                        "Reading {} from a version other than the baseline requires a git clone of the vcpkg registry in {}.",
+                        file_path, VCPKG_ROOT_ENV_VAR
+                    ));
+                }
+                return crate::utils::fetch(&format!("{}/{}/{}", VCPKG_RAW_URL, self.baseline, file_path));
+            }
+        };
+
+        let object_name = format!("{}:{}", git_tree.unwrap_or(&self.baseline), file_path);
+        let output = match Command::new("git")
+            .arg("-C")
+            .arg(vcpkg_root)
+            .arg("show")
+            .arg(&object_name)
+            .output()
+        {
+            Ok(o) => o,
+            Err(e) => return Err(format!("Could not run git: {}", e)),
+        };
+        if !output.status.success() {
+            return Err(format!(
+                "Could not read {} in {}: {}",
+                object_name,
+                vcpkg_root,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Gets the git tree of a specific version of a port from the versions database.
+    pub fn get_port_git_tree(
+        &self,
+        port_name: &str,
+        version: &str,
+        port_version: i64,
+    ) -> Result<String, String> {
+        let first_letter = port_name.chars().next().unwrap_or('_');
+        let versions_path = format!("versions/{}-/{}.json", first_letter, port_name);
+        let port_versions: VCPKGPortVersions =
+            match serde_json::from_str(&self.read_file(None, &versions_path)?) {
+                Ok(v) => v,
+                Err(e) => return Err(format!("Could not parse {}: {}", versions_path, e)),
+            };
+        match port_versions.versions.into_iter().find(|v| {
+            v.manifest.get_version().as_deref() == Some(version)
+                && v.manifest.port_version.unwrap_or(0) == port_version
+        }) {
+            Some(v) => Ok(v.git_tree),
+            None => Err(format!(
+                "Could not find version {}#{} of port {} in the vcpkg registry.",
+                version, port_version, port_name
+            )),
+        }
+    }
+
+    /// Gets the release of vcpkg-tool used by the baseline.
+    pub fn get_tool_release_tag(&self) -> Result<String, String> {
+        let metadata = self.read_file(None, VCPKG_TOOL_METADATA_PATH)?;
+        match metadata
+            .lines()
+            .find_map(|l| l.trim().strip_prefix("VCPKG_TOOL_RELEASE_TAG="))
+        {
+            Some(t) => Ok(t.trim().to_string()),
+            None => Err(format!(
+                "Could not find the vcpkg-tool release in {}.",
+                VCPKG_TOOL_METADATA_PATH
+            )),
+        }
+    }
+}
+
+/// Gets the pinned sources for every port required by the vcpkg manifest, including
+/// the transitive dependencies. Like vcpkg, the version selected for a port is the highest
+/// of the baseline and the `version>=` constraints, unless the port is overridden.
+pub fn get_port_sources(
+    vcpkg_manifest: &VCPKGManifest,
+    registry: &VCPKGRegistry,
+) -> Result<Vec<FlatpakSource>, String> {
+    let mut port_sources: BTreeMap<String, Vec<FlatpakSource>> = BTreeMap::new();
+    let mut port_manifests: BTreeMap<String, VCPKGManifest> = BTreeMap::new();
+    let mut port_features: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut selected_versions: BTreeMap<String, (String, i64)> = BTreeMap::new();
+    let mut baseline_versions: BTreeMap<String, (String, i64)> = BTreeMap::new();
+    let mut minimum_versions: BTreeMap<String, (String, i64)> = BTreeMap::new();
+
+    // The default features of the project are installed with its dependencies.
+    let project_features = BTreeSet::from([CORE_FEATURE.to_string(), DEFAULT_FEATURE.to_string()]);
+    let mut next_dependencies: Vec<VCPKGDependency> = vcpkg_manifest.get_dependencies(&project_features)?;
+
+    while let Some(dependency) = next_dependencies.pop() {
+        let port_name = dependency.get_name();
+        // Those are helper ports that do not download anything.
+        if port_name.starts_with("vcpkg-") {
+            continue;
+        }
+
+        if let Some(minimum_version) = dependency.get_minimum_version() {
+            let minimum_version = parse_version_constraint(minimum_version);
+            let is_higher = match minimum_versions.get(&port_name) {
+                Some(v) => compare_port_versions(&minimum_version, v) == Ordering::Greater,
+                None => true,
+            };
+            if is_higher {
+                minimum_versions.insert(port_name.to_string(), minimum_version);
+            }
+        }
+
+        let mut git_tree: Option<String> = None;
+        let selected_version = match vcpkg_manifest.get_override(&port_name) {
+            Some(o) => {
+                let version = (o.version.to_string(), o.port_version.unwrap_or(0));
+                git_tree = Some(registry.get_port_git_tree(&port_name, &version.0, version.1)?);
+                version
+            }
+            None => {
+                if !baseline_versions.contains_key(&port_name) {
+                    let port_manifest_path = format!("ports/{}/vcpkg.json", port_name);
+                    let port_manifest =
+                        VCPKGManifest::parse("vcpkg.json", &registry.read_file(None, &port_manifest_path)?)?;
+                    let baseline_version = (
+                        port_manifest.get_version().unwrap_or_default(),
+                        port_manifest.port_version.unwrap_or(0),
+                    );
+                    baseline_versions.insert(port_name.to_string(), baseline_version);
+                }
+                let baseline_version = &baseline_versions[&port_name];
+                match minimum_versions.get(&port_name) {
+                    Some(v) if compare_port_versions(v, baseline_version) == Ordering::Greater => {
+                        git_tree = Some(registry.get_port_git_tree(&port_name, &v.0, v.1)?);
+                        v.clone()
+                    }
+                    _ => baseline_version.clone(),
+                }
+            }
+        };
+
+        let requested_features = dependency.get_features()?;
+        let previous_features = port_features.entry(port_name.to_string()).or_default();
+        if selected_versions.get(&port_name) == Some(&selected_version) {
+            // Only the dependencies of the features which were not requested yet are added.
+            let new_features: BTreeSet<String> = requested_features
+                .difference(previous_features)
+                .cloned()
+                .collect();
+            if new_features.is_empty() {
+                continue;
+            }
+            previous_features.extend(new_features.iter().cloned());
+            next_dependencies.extend(port_manifests[&port_name].get_dependencies(&new_features)?);
+            continue;
+        }
+        previous_features.extend(requested_features);
+        log::info!(
+            "Selected version {}#{} of port {}.",
+            selected_version.0,
+            selected_version.1,
+            port_name
+        );
+
+        let port_dir = match &git_tree {
+            Some(_) => String::new(),
+            None => format!("ports/{}/", port_name),
+        };
+        let port_manifest_content =
+            registry.read_file(git_tree.as_deref(), &format!("{}vcpkg.json", port_dir))?;
+        let port_manifest = VCPKGManifest::parse("vcpkg.json", &port_manifest_content)?;
+        let portfile_content =
+            registry.read_file(git_tree.as_deref(), &format!("{}portfile.cmake", port_dir))?;
+        let sources = get_portfile_sources(&portfile_content, &selected_version.0);
+        if sources.is_empty() {
+            log::info!("Port {} has no pinned downloads.", port_name);
+        }
+
+        // The sources of a version that was replaced by a higher constraint are dropped,
+        // and all the features requested so far are resolved again with the new version.
+        next_dependencies.extend(port_manifest.get_dependencies(previous_features)?);
+        port_sources.insert(port_name.to_string(), sources);
+        port_manifests.insert(port_name.to_string(), port_manifest);
+        selected_versions.insert(port_name, selected_version);
+    }
+
+    Ok(port_sources.into_values().flatten().collect())
+}
+
+fn compare_port_versions(version: &(String, i64), other_version: &(String, i64)) -> Ordering {
+    compare_versions(&version.0, &other_version.0).then(version.1.cmp(&other_version.1))
+}
+
+/// Gets the sources of vcpkg-tool at the release used by the baseline, with the dependencies
+/// it would otherwise download with FetchContent, and the options to build it.
+pub fn get_tool_sources(
+    registry: &VCPKGRegistry,
+    module_name: &str,
+) -> Result<(Vec<FlatpakSource>, Vec<String>), String> {
+    let release_tag = registry.get_tool_release_tag()?;
+    let tag_ref = format!("refs/tags/{}", release_tag);
+    let commit = match crate::utils::get_git_remote_refs(VCPKG_TOOL_REPOSITORY_URL)?.remove(&tag_ref) {
+        Some(c) => c,
+        None => {
+            return Err(format!(
+                "Could not find the vcpkg-tool release {} in {}.",
+                release_tag, VCPKG_TOOL_REPOSITORY_URL
+            ))
+        }
+    };
+
+    let mut sources: Vec<FlatpakSource> = vec![FlatpakSource {
+        r#type: Some(FlatpakSourceType::Git),
+        url: Some(VCPKG_TOOL_REPOSITORY_URL.to_string()),
+        tag: Some(release_tag),
+        commit: Some(commit.to_string()),
+        dest: Some(VCPKG_TOOL_DESTINATION.to_string()),
+        ..Default::default()
+    }];
+    let mut config_opts: Vec<String> = vec!["-DFETCHCONTENT_FULLY_DISCONNECTED=ON".to_string()];
+
+    let cmake_lists_content =
+        crate::utils::fetch(&format!("{}/{}/CMakeLists.txt", VCPKG_TOOL_RAW_URL, commit))?;
+    let mut cmake_contents: Vec<String> = vec![];
+    for (function_name, args) in cmake::get_cmake_function_calls(&cmake_lists_content) {
+        let included_file = match args.first() {
+            Some(f) if function_name == "include" && f.ends_with(".cmake") => f,
+            _ => continue,
+        };
+        cmake_contents.push(crate::utils::fetch(&format!(
+            "{}/{}/{}",
+            VCPKG_TOOL_RAW_URL, commit, included_file
+        ))?);
+    }
+    cmake_contents.push(cmake_lists_content);
+
+    for cmake_content in cmake_contents {
+        for (function_name, args) in cmake::get_cmake_function_calls(&cmake_content) {
+            let external_content = match cmake::CMakeExternalContent::from_function_call(&function_name, &args)
+            {
+                Some(c) => c,
+                None => continue,
+            };
+            if let Some(config_opt) = external_content.get_config_opt(module_name) {
+                sources.push(external_content.get_source()?);
+                config_opts.push(config_opt);
+            }
+        }
+    }
+    Ok((sources, config_opts))
+}
+
+pub fn get_vcpkg_module(vcpkg_manifest: &VCPKGManifest) -> Result<FlatpakModule, String> {
+    let baseline = match &vcpkg_manifest.builtin_baseline {
+        Some(b) => b.to_string(),
+        None => {
+            return Err(
+                "The vcpkg manifest has no builtin-baseline, so the ports cannot be pinned.".to_string(),
+            )
+        }
+    };
+    let registry = VCPKGRegistry::new(&baseline);
+
+    let mut vcpkg_module = FlatpakModule {
+        name: if vcpkg_manifest.name.is_empty() {
+            "vcpkg_sources".to_string()
+        } else {
+            vcpkg_manifest.name.to_string()
+        },
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        ..Default::default()
+    };
+
+    // vcpkg-tool is built from source, because bootstrap-vcpkg.sh downloads it.
+    let (tool_sources, tool_config_opts) = get_tool_sources(&registry, &vcpkg_module.name)?;
+    vcpkg_module.build_commands.push(format!(
+        "cmake -S {} -B {}/build -G Ninja -DCMAKE_BUILD_TYPE=Release -DBUILD_TESTING=OFF {}",
+        VCPKG_TOOL_DESTINATION,
+        VCPKG_TOOL_DESTINATION,
+        tool_config_opts.join(" ")
+    ));
+    vcpkg_module
+        .build_commands
+        .push(format!("cmake --build {}/build", VCPKG_TOOL_DESTINATION));
+    vcpkg_module.build_commands.push(format!(
+        "cp {}/build/vcpkg {}/vcpkg",
+        VCPKG_TOOL_DESTINATION, DEFAULT_DESTINATION
+    ));
+    // Without VCPKG_FORCE_SYSTEM_BINARIES, vcpkg downloads its own CMake and Ninja.
+    vcpkg_module.build_commands.push(format!(
+        "VCPKG_FORCE_SYSTEM_BINARIES=1 VCPKG_DISABLE_METRICS=1 ./{}/vcpkg install --x-manifest-root=. --x-install-root=./vcpkg_installed --downloads-root=./{}",
+        DEFAULT_DESTINATION, DEFAULT_DOWNLOADS_DESTINATION
+    ));
+    vcpkg_module
+        .build_commands
+        .push("cp -a ./vcpkg_installed/*-linux/. ${FLATPAK_DEST}/".to_string());
+
+    vcpkg_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Dir),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    vcpkg_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            url: Some(VCPKG_REGISTRY_URL.to_string()),
+            commit: Some(baseline),
+            dest: Some(DEFAULT_DESTINATION.to_string()),
+            ..Default::default()
+        }));
+    for source in tool_sources {
+        vcpkg_module.sources.push(FlatpakSourceItem::Description(source));
+    }
+    for source in get_port_sources(vcpkg_manifest, &registry)? {
+        vcpkg_module.sources.push(FlatpakSourceItem::Description(source));
+    }
+
+    Ok(vcpkg_module)
+}
+
+pub struct VCPKGImporter {}
+impl Importer for VCPKGImporter {
+    fn get_name(&self) -> String {
+        "vcpkg".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        VCPKGManifest::file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        let vcpkg_manifest_content = match fs::read_to_string(manifest_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read vcpkg manifest at {}: {}",
+                    manifest_path, e
+                ))
+            }
+        };
+        let vcpkg_manifest = VCPKGManifest::parse(manifest_path, &vcpkg_manifest_content)?;
+        Ok(ImportResult::new(get_vcpkg_module(&vcpkg_manifest)?))
+    }
 }
 
 #[cfg(test)]
@@ -36,5 +963,315 @@ mod manifest_tests {
     use super::*;
 
     #[test]
-    pub fn test_parse_single_source() {}
+    pub fn test_parse_single_source() {
+        let vcpkg_manifest = VCPKGManifest::parse(
+            "vcpkg.json",
+            r###"
+            {
+              "name": "my-application",
+              "version": "0.15.2",
+              "dependencies": [
+                "boost-system",
+                { "name": "wil", "platform": "windows" },
+                {
+                  "name": "cpprestsdk",
+                  "default-features": false
+                },
+                {
+                  "name": "fmt",
+                  "version>=": "7.1.3#1"
+                }
+              ],
+              "default-features": ["png", { "name": "jpeg", "platform": "osx" }],
+              "features": {
+                "jpeg": {
+                  "description": "JPEG support",
+                  "dependencies": ["libjpeg-turbo"]
+                },
+                "png": {
+                  "description": "PNG support",
+                  "dependencies": ["libpng"]
+                },
+                "tests": {
+                  "description": "Build the tests",
+                  "dependencies": ["gtest"]
+                }
+              },
+              "overrides": [
+                { "name": "fmt", "version": "6.0.0" }
+              ],
+              "builtin-baseline": "3426db05b996481ca31e95fff3734cf23e0f51bc"
+            }
+            "###,
+        )
+        .unwrap();
+
+        assert_eq!(vcpkg_manifest.name, "my-application");
+        assert_eq!(vcpkg_manifest.get_version(), Some("0.15.2".to_string()));
+        assert_eq!(vcpkg_manifest.dependencies.len(), 4);
+        assert_eq!(vcpkg_manifest.features.len(), 3);
+        assert_eq!(vcpkg_manifest.get_override("fmt").unwrap().version, "6.0.0");
+        assert_eq!(
+            vcpkg_manifest.builtin_baseline,
+            Some("3426db05b996481ca31e95fff3734cf23e0f51bc".to_string())
+        );
+        let get_dependency_names = |features: &[&str]| -> Vec<String> {
+            let features = features.iter().map(|f| f.to_string()).collect();
+            vcpkg_manifest
+                .get_dependencies(&features)
+                .unwrap()
+                .iter()
+                .map(|d| d.get_name())
+                .collect()
+        };
+        assert_eq!(
+            get_dependency_names(&["core", "default"]),
+            vec!["boost-system", "cpprestsdk", "fmt", "libpng"]
+        );
+        assert_eq!(get_dependency_names(&["tests"]), vec!["gtest"]);
+        assert_eq!(
+            vcpkg_manifest.dependencies[2].get_features().unwrap(),
+            BTreeSet::from(["core".to_string()])
+        );
+    }
+
+    #[test]
+    pub fn test_evaluate_platform_expression() {
+        assert!(evaluate_platform_expression("linux", "x64-linux").unwrap());
+        assert!(!evaluate_platform_expression("!linux", "x64-linux").unwrap());
+        assert!(evaluate_platform_expression("!windows & !osx", "x64-linux").unwrap());
+        assert!(evaluate_platform_expression("windows | (linux & arm64)", "arm64-linux").unwrap());
+        assert!(!evaluate_platform_expression("windows | (linux & arm64)", "x64-linux").unwrap());
+        assert!(evaluate_platform_expression("not uwp and not (x86 or arm)", "x64-linux").unwrap());
+        assert!(evaluate_platform_expression("windows, linux", "x64-linux").unwrap());
+        assert!(evaluate_platform_expression("(linux", "x64-linux").is_err());
+        assert!(evaluate_platform_expression("linux &", "x64-linux").is_err());
+        assert!(is_linux_platform(None).unwrap());
+        assert!(is_linux_platform(Some("arm & linux")).unwrap());
+        assert!(!is_linux_platform(Some("android")).unwrap());
+    }
+
+    #[test]
+    pub fn test_get_portfile_sources() {
+        let portfile_content = r###"
+            # Some comment (with parenthesis
+            vcpkg_from_github(
+                OUT_SOURCE_PATH SOURCE_PATH
+                REPO fmtlib/fmt
+                REF "${VERSION}"
+                SHA512 A4BA2F2F5E6D4C0B3D0C4A3A1E7D2F2F5E6D4C0B3D0C4A3A1E7D2F2F5E6D4C0B
+                HEAD_REF master
+                PATCHES
+                    fix-write-batch.patch
+            )
+            vcpkg_download_distfile(ARCHIVE
+                URLS "https://zlib.net/zlib-${VERSION}.tar.gz"
+                FILENAME "zlib-${VERSION}.tar.gz"
+                SHA512 0123456789abcdef
+            )
+            vcpkg_cmake_configure(SOURCE_PATH "${SOURCE_PATH}")
+        "###;
+        let sources = get_portfile_sources(portfile_content, "10.1.1");
+        assert_eq!(sources.len(), 2);
+        assert_eq!(
+            sources[0].url,
+            Some("https://api.github.com/repos/fmtlib/fmt/tarball/10.1.1".to_string())
+        );
+        assert_eq!(
+            sources[0].dest_filename,
+            Some("fmtlib-fmt-10.1.1.tar.gz".to_string())
+        );
+        assert_eq!(
+            sources[0].sha512,
+            Some("a4ba2f2f5e6d4c0b3d0c4a3a1e7d2f2f5e6d4c0b3d0c4a3a1e7d2f2f5e6d4c0b".to_string())
+        );
+        assert_eq!(
+            sources[1].url,
+            Some("https://zlib.net/zlib-10.1.1.tar.gz".to_string())
+        );
+        assert_eq!(sources[1].dest_filename, Some("zlib-10.1.1.tar.gz".to_string()));
+    }
+
+    #[test]
+    pub fn test_compare_versions() {
+        assert_eq!(parse_version_constraint("7.1.3#1"), ("7.1.3".to_string(), 1));
+        assert_eq!(parse_version_constraint("7.1.3"), ("7.1.3".to_string(), 0));
+        assert_eq!(compare_versions("10.1.1", "9.1.0"), Ordering::Greater);
+        assert_eq!(compare_versions("1.2", "1.2.0"), Ordering::Less);
+        assert_eq!(compare_versions("2023-01-05", "2023-01-05"), Ordering::Equal);
+        assert_eq!(compare_versions("1.2.0", "1.10.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.0-rc.1", "1.2.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.2.0-rc.2", "1.2.0-rc.10"), Ordering::Less);
+    }
+
+    #[test]
+    pub fn test_get_port_sources() {
+        let vcpkg_root = std::env::temp_dir().join(format!("fpm-test-vcpkg-registry-{}", std::process::id()));
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&vcpkg_root)
+                .args(["-c", "user.name=fpm", "-c", "user.email=fpm@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(status.status.success());
+            String::from_utf8_lossy(&status.stdout).trim().to_string()
+        };
+        let write_port = |port_name: &str, version: &str, dependencies: &str| {
+            let port_dir = vcpkg_root.join("ports").join(port_name);
+            fs::create_dir_all(&port_dir).unwrap();
+            fs::write(
+                port_dir.join("vcpkg.json"),
+                format!(
+                    r#"{{"name": "{}", "version": "{}", "dependencies": [{}]}}"#,
+                    port_name, version, dependencies
+                ),
+            )
+            .unwrap();
+            fs::write(
+                port_dir.join("portfile.cmake"),
+                format!(
+                    "vcpkg_download_distfile(ARCHIVE URLS \"https://example.com/{}-${{VERSION}}.tar.gz\" FILENAME \"{}-${{VERSION}}.tar.gz\" SHA512 abcd)",
+                    port_name, port_name
+                ),
+            )
+            .unwrap();
+        };
+        fs::create_dir_all(&vcpkg_root).unwrap();
+        git(&["init", "-q"]);
+
+        write_port("zlib", "1.2.0", "");
+        write_port("libpng", "1.6.0", r#""zlib""#);
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "Initial ports"]);
+        let zlib_tree = git(&["rev-parse", "HEAD:ports/zlib"]);
+        let libpng_tree = git(&["rev-parse", "HEAD:ports/libpng"]);
+
+        write_port("zlib", "1.3.0", "");
+        write_port("libpng", "1.6.1", r#"{"name": "zlib", "version>=": "1.3.1"}"#);
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "Update ports"]);
+        let new_zlib_tree = git(&["rev-parse", "HEAD:ports/zlib"]);
+
+        // Version 1.3.1 of zlib is only in the versions database.
+        write_port("zlib", "1.3.1", "");
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "Update zlib"]);
+        let newest_zlib_tree = git(&["rev-parse", "HEAD:ports/zlib"]);
+        write_port("zlib", "1.3.0", "");
+        write_port("bzip2", "1.0.8", "");
+        write_port("freetype", "2.13.0", "");
+        fs::write(
+            vcpkg_root.join("ports/freetype/vcpkg.json"),
+            r#"{
+                "name": "freetype",
+                "version": "2.13.0",
+                "dependencies": [{"name": "brotli", "platform": "windows"}],
+                "default-features": ["png"],
+                "features": {
+                    "png": {"description": "PNG support", "dependencies": ["libpng"]},
+                    "bzip2": {"description": "bzip2 support", "dependencies": ["bzip2"]}
+                }
+            }"#,
+        )
+        .unwrap();
+        fs::create_dir_all(vcpkg_root.join("versions/z-")).unwrap();
+        fs::create_dir_all(vcpkg_root.join("versions/l-")).unwrap();
+        fs::write(
+            vcpkg_root.join("versions/z-/zlib.json"),
+            format!(
+                r#"{{"versions": [
+                    {{"git-tree": "{}", "version": "1.3.1", "port-version": 0}},
+                    {{"git-tree": "{}", "version": "1.3.0", "port-version": 0}},
+                    {{"git-tree": "{}", "version": "1.2.0", "port-version": 0}}
+                ]}}"#,
+                newest_zlib_tree, new_zlib_tree, zlib_tree
+            ),
+        )
+        .unwrap();
+        fs::write(
+            vcpkg_root.join("versions/l-/libpng.json"),
+            format!(
+                r#"{{"versions": [{{"git-tree": "{}", "version": "1.6.0", "port-version": 0}}]}}"#,
+                libpng_tree
+            ),
+        )
+        .unwrap();
+        git(&["add", "-A"]);
+        git(&["commit", "-q", "-m", "Add the versions database"]);
+        let baseline = git(&["rev-parse", "HEAD"]);
+        let registry = VCPKGRegistry {
+            baseline: baseline.to_string(),
+            vcpkg_root: Some(vcpkg_root.to_str().unwrap().to_string()),
+        };
+
+        let get_urls = |manifest_content: &str| -> Result<Vec<String>, String> {
+            let vcpkg_manifest = VCPKGManifest::parse("vcpkg.json", manifest_content).unwrap();
+            Ok(get_port_sources(&vcpkg_manifest, &registry)?
+                .into_iter()
+                .map(|s| s.url.unwrap())
+                .collect())
+        };
+
+        // The constraint of libpng selects a version of zlib above the baseline.
+        assert_eq!(
+            get_urls(r#"{"dependencies": ["libpng"]}"#).unwrap(),
+            vec![
+                "https://example.com/libpng-1.6.1.tar.gz",
+                "https://example.com/zlib-1.3.1.tar.gz",
+            ]
+        );
+        // The overrides win over the constraints.
+        assert_eq!(
+            get_urls(r#"{"dependencies": ["libpng"], "overrides": [{"name": "libpng", "version": "1.6.0"}, {"name": "zlib", "version": "1.2.0"}]}"#)
+                .unwrap(),
+            vec![
+                "https://example.com/libpng-1.6.0.tar.gz",
+                "https://example.com/zlib-1.2.0.tar.gz",
+            ]
+        );
+        assert!(
+            get_urls(r#"{"dependencies": ["zlib"], "overrides": [{"name": "zlib", "version": "0.1"}]}"#)
+                .is_err()
+        );
+        assert!(get_urls(r#"{"dependencies": ["unknown-port"]}"#).is_err());
+
+        // The default features are followed, and the dependencies of other platforms are ignored.
+        assert_eq!(
+            get_urls(r#"{"dependencies": ["freetype"]}"#).unwrap(),
+            vec![
+                "https://example.com/freetype-2.13.0.tar.gz",
+                "https://example.com/libpng-1.6.1.tar.gz",
+                "https://example.com/zlib-1.3.1.tar.gz",
+            ]
+        );
+        assert_eq!(
+            get_urls(r#"{"dependencies": [{"name": "freetype", "default-features": false}]}"#).unwrap(),
+            vec!["https://example.com/freetype-2.13.0.tar.gz"]
+        );
+        // The features requested by all the dependencies on a port are resolved.
+        assert_eq!(
+            get_urls(
+                r#"{"dependencies": [
+                    {"name": "freetype", "default-features": false, "features": ["bzip2"]},
+                    {"name": "freetype", "default-features": false, "platform": "linux"},
+                    {"name": "freetype", "features": [{"name": "png", "platform": "windows"}], "platform": "windows"}
+                ]}"#
+            )
+            .unwrap(),
+            vec![
+                "https://example.com/bzip2-1.0.8.tar.gz",
+                "https://example.com/freetype-2.13.0.tar.gz",
+            ]
+        );
+
+        fs::remove_dir_all(&vcpkg_root).unwrap();
+    }
+
+    #[test]
+    pub fn test_get_module_requires_baseline() {
+        let vcpkg_manifest = VCPKGManifest::parse("vcpkg.json", r#"{"name": "app"}"#).unwrap();
+        assert!(get_vcpkg_module(&vcpkg_manifest).is_err());
+    }
 }
@@ -0,0 +1,761 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path;
+
+use flatpak_rs::build_system::FlatpakBuildSystem;
+use flatpak_rs::module::{FlatpakBuildOptions, FlatpakBuildOptionsEnv, FlatpakModule};
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem, FlatpakSourceType};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+use crate::importers::npm::{get_module_name, Integrity};
+use crate::importers::{ImportOptions, ImportResult, Importer};
+use crate::version::SemanticVersion;
+
+pub const NPM_REGISTRY_URL: &str = "https://registry.npmjs.org";
+pub const DEFAULT_MIRROR_DESTINATION: &str = "flatpak-node/yarn-mirror";
+pub const DEFAULT_TARBALLS_DESTINATION: &str = "flatpak-node/yarn-tarballs";
+pub const DEFAULT_CACHE_DESTINATION: &str = "flatpak-node/yarn-cache";
+pub const BERRY_METADATA_KEY: &str = "__metadata";
+pub const BERRY_CACHE_SCRIPT_PATH: &str = "flatpak-node/yarn-cache.py";
+/// The script converting a package tarball to an archive of the yarn berry cache, called with
+/// the tarball, the package name and the archive. Like yarn, the first directory of the tarball
+/// is replaced with `node_modules/<package name>`, and the files use the timestamp of yarn.
+pub const BERRY_CACHE_SCRIPT: [&str; 23] = [
+    "import sys, tarfile, zipfile",
+    "tarball_path, package_name, zip_path = sys.argv[1:4]",
+    "date_time = (1984, 6, 22, 21, 50, 0)",
+    "directories = set()",
+    "def add_directory(zip_file, directory):",
+    "    if directory in directories:",
+    "        return",
+    "    directories.add(directory)",
+    "    info = zipfile.ZipInfo(directory, date_time)",
+    "    info.external_attr = 0o40755 << 16",
+    "    zip_file.writestr(info, b\"\")",
+    "with tarfile.open(tarball_path) as tarball, zipfile.ZipFile(zip_path, \"w\") as zip_file:",
+    "    for member in tarball.getmembers():",
+    "        components = member.name.split(\"/\")",
+    "        components = [c for c in components if c not in (\"\", \".\")][1:]",
+    "        if not components or not member.isfile():",
+    "            continue",
+    "        path_components = [\"node_modules\"] + package_name.split(\"/\") + components",
+    "        for index in range(1, len(path_components)):",
+    "            add_directory(zip_file, \"/\".join(path_components[:index]) + \"/\")",
+    "        info = zipfile.ZipInfo(\"/\".join(path_components), date_time)",
+    "        info.external_attr = (0o100755 if member.mode & 0o111 else 0o100644) << 16",
+    "        zip_file.writestr(info, tarball.extractfile(member).read())",
+];
+
+/// A package tarball resolved from a yarn lockfile, or from the npm registry for berry lockfiles.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct YarnResolvedPackage {
+    pub name: String,
+    pub url: String,
+    pub integrity: Integrity,
+}
+impl YarnResolvedPackage {
+    /// Gets the file name used for the tarball in the offline mirror. This is the same
+    /// name yarn uses when populating an offline mirror itself.
+    pub fn get_mirror_filename(&self) -> String {
+        let url_path = self.url.split(['?', '#']).next().unwrap_or(&self.url);
+        let basename = url_path.rsplit('/').next().unwrap_or(url_path);
+        match self.name.strip_prefix('@') {
+            Some(scoped_name) => match scoped_name.split_once('/') {
+                Some((scope, _)) => format!("@{}-{}", scope, basename),
+                None => basename.to_string(),
+            },
+            None => basename.to_string(),
+        }
+    }
+
+    pub fn get_source(&self) -> FlatpakSource {
+        let mut source = FlatpakSource {
+            r#type: Some(FlatpakSourceType::File),
+            url: Some(self.url.to_string()),
+            dest: Some(DEFAULT_MIRROR_DESTINATION.to_string()),
+            dest_filename: Some(self.get_mirror_filename()),
+            ..Default::default()
+        };
+        self.integrity.set_checksum(&mut source);
+        source
+    }
+}
+
+/// An entry of a yarn classic (v1) lockfile.
+/// See https://classic.yarnpkg.com/lang/en/docs/yarn-lock/
+#[derive(Clone)]
+#[derive(Debug)]
+#[derive(Default)]
+pub struct YarnLockEntry {
+    /// The package specifications resolved by this entry, for example `left-pad@^1.0.0`.
+    pub specs: Vec<String>,
+    pub version: String,
+    pub resolved: Option<String>,
+    pub integrity: Option<String>,
+}
+impl YarnLockEntry {
+    /// Gets the name of the package from the first specification of the entry.
+    pub fn get_name(&self) -> String {
+        let spec = match self.specs.first() {
+            Some(s) => s,
+            None => return "".to_string(),
+        };
+        // The first character is skipped because of scoped packages.
+        match spec.char_indices().skip(1).find(|(_, c)| *c == '@') {
+            Some((i, _)) => spec[..i].to_string(),
+            None => spec.to_string(),
+        }
+    }
+
+    /// Resolves the tarball of the entry. When the package is not downloaded from a registry,
+    /// for example with git or local dependencies, the error describes the unsupported entry.
+    pub fn resolve(&self) -> Result<YarnResolvedPackage, String> {
+        let name = self.get_name();
+        let resolved = match &self.resolved {
+            Some(r) => r,
+            None => return Err(format!("{}@{} (not resolved)", name, self.version)),
+        };
+        if !resolved.starts_with("https://") && !resolved.starts_with("http://") {
+            return Err(format!("{}@{} ({})", name, self.version, resolved));
+        }
+
+        // The sha1 of the tarball is appended to the resolved url.
+        let (url, url_hash) = match resolved.split_once('#') {
+            Some((u, h)) => (u.to_string(), Some(h.to_string())),
+            None => (resolved.to_string(), None),
+        };
+        let integrity = match (&self.integrity, url_hash) {
+            (Some(i), _) => match Integrity::parse(i) {
+                Ok(i) => i,
+                Err(e) => return Err(format!("{}@{} (invalid integrity: {})", name, self.version, e)),
+            },
+            (None, Some(h)) => Integrity {
+                algorithm: "sha1".to_string(),
+                digest: h,
+            },
+            (None, None) => return Err(format!("{}@{} (no checksum)", name, self.version)),
+        };
+
+        Ok(YarnResolvedPackage { name, url, integrity })
+    }
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches('"').to_string()
+}
+
+/// Parses a yarn classic (v1) lockfile. This is not a YAML file, but a custom format
+/// using indentation for the nested values.
+pub fn parse_classic_lockfile(lockfile_content: &str) -> Result<Vec<YarnLockEntry>, String> {
+    let mut entries: Vec<YarnLockEntry> = vec![];
+    let mut current_entry: Option<YarnLockEntry> = None;
+
+    for (line_number, line) in lockfile_content.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        if !line.starts_with(' ') {
+            let specs = match line.strip_suffix(':') {
+                Some(s) => s,
+                None => {
+                    return Err(format!(
+                        "Invalid yarn lockfile entry at line {}.",
+                        line_number + 1
+                    ))
+                }
+            };
+            if let Some(entry) = current_entry.take() {
+                entries.push(entry);
+            }
+            current_entry = Some(YarnLockEntry {
+                specs: specs.split(", ").map(unquote).collect(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        // Only the fields of the entry are used, not the nested values like the dependencies.
+        if line.starts_with("    ") {
+            continue;
+        }
+        let entry = match current_entry.as_mut() {
+            Some(e) => e,
+            None => return Err(format!("Unexpected indentation at line {}.", line_number + 1)),
+        };
+        let (field_name, field_value) = match line.trim().split_once(' ') {
+            Some((n, v)) => (n, unquote(v)),
+            None => continue,
+        };
+        match field_name {
+            "version" => entry.version = field_value,
+            "resolved" => entry.resolved = Some(field_value),
+            "integrity" => entry.integrity = Some(field_value),
+            _ => {}
+        }
+    }
+    if let Some(entry) = current_entry.take() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Whether the lockfile was written by yarn berry (v2+), which is a YAML file.
+pub fn is_berry_lockfile(lockfile_content: &str) -> bool {
+    lockfile_content
+        .lines()
+        .any(|l| l.starts_with(BERRY_METADATA_KEY))
+}
+
+/// An entry of a yarn berry (v2+) lockfile.
+/// See https://yarnpkg.com/advanced/lexicon#locator
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[derive(Serialize)]
+#[derive(Debug)]
+#[derive(Default)]
+#[serde(rename_all = "camelCase")]
+#[serde(default)]
+pub struct YarnBerryLockEntry {
+    /// The locator of the package, for example `left-pad@npm:1.3.0`.
+    pub resolution: String,
+
+    /// The checksum of the archive in the yarn cache, not of the package tarball.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language_name: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link_type: Option<String>,
+}
+impl YarnBerryLockEntry {
+    /// Splits the locator of the entry into the package name and the reference.
+    pub fn get_locator(&self) -> Option<(String, String)> {
+        // The first character is skipped because of scoped packages.
+        let (separator_index, _) = self.resolution.char_indices().skip(1).find(|(_, c)| *c == '@')?;
+        Some((
+            self.resolution[..separator_index].to_string(),
+            self.resolution[separator_index + 1..].to_string(),
+        ))
+    }
+
+    /// Gets the version of a package fetched from the npm registry.
+    pub fn get_npm_version(&self) -> Option<String> {
+        let (_, reference) = self.get_locator()?;
+        reference.strip_prefix("npm:").map(|v| v.to_string())
+    }
+
+    /// Whether yarn produces the package itself during the install, without downloading it.
+    /// This is the case for the workspaces, the local packages, and the patches applied
+    /// to other packages.
+    pub fn is_local(&self) -> bool {
+        let reference = match self.get_locator() {
+            Some((_, r)) => r,
+            None => return false,
+        };
+        ["workspace:", "patch:", "link:", "portal:", "file:"]
+            .iter()
+            .any(|p| reference.starts_with(p))
+    }
+}
+
+/// Computes a hash the way yarn does, which is the sha512 of the concatenated values.
+fn get_yarn_hash(values: &[&str]) -> String {
+    format!("{:x}", Sha512::digest(values.concat().as_bytes()))
+}
+
+/// Gets the name of the archive of a package in the yarn berry cache, when the cache is not
+/// a mirror of the global cache. This is `<slug of the locator>-<cache key>.zip`.
+pub fn get_berry_cache_filename(name: &str, reference: &str, cache_key: &str) -> String {
+    let (scope, package_name) = match name.strip_prefix('@').and_then(|n| n.split_once('/')) {
+        Some((scope, package_name)) => (scope, package_name),
+        None => ("", name),
+    };
+    let ident_hash = get_yarn_hash(&[scope, package_name]);
+    let locator_hash = get_yarn_hash(&[&ident_hash, reference]);
+
+    let (protocol, selector) = match reference.split_once(':') {
+        Some((p, s)) => (p, s),
+        None => ("exotic", reference),
+    };
+    let human_reference = match SemanticVersion::parse(selector) {
+        Some(v) if v.pre_release.is_empty() => format!("{}-{}.{}.{}", protocol, v.major, v.minor, v.patch),
+        Some(v) => format!(
+            "{}-{}.{}.{}-{}",
+            protocol, v.major, v.minor, v.patch, v.pre_release
+        ),
+        None => protocol.to_string(),
+    };
+    format!(
+        "{}-{}-{}-{}.zip",
+        name.replacen('/', "-", 1),
+        human_reference,
+        &locator_hash[..10],
+        cache_key
+    )
+}
+
+/// Parses a yarn berry (v2+) lockfile, which is a YAML file. Returns the cache key of the
+/// lockfile and its entries.
+pub fn parse_berry_lockfile(lockfile_content: &str) -> Result<(String, Vec<YarnBerryLockEntry>), String> {
+    let lockfile: BTreeMap<String, serde_yaml::Value> = match serde_yaml::from_str(lockfile_content) {
+        Ok(l) => l,
+        Err(e) => return Err(format!("Failed to parse the yarn lockfile: {}.", e)),
+    };
+    let mut cache_key: Option<String> = None;
+    let mut entries: Vec<YarnBerryLockEntry> = vec![];
+    for (entry_key, entry_value) in lockfile {
+        if entry_key == BERRY_METADATA_KEY {
+            // The cache key is a number with the older versions of yarn.
+            cache_key = match &entry_value["cacheKey"] {
+                serde_yaml::Value::Number(n) => Some(n.to_string()),
+                serde_yaml::Value::String(s) => Some(s.to_string()),
+                _ => None,
+            };
+            continue;
+        }
+        match serde_yaml::from_value(entry_value) {
+            Ok(e) => entries.push(e),
+            Err(e) => return Err(format!("Invalid yarn lockfile entry {}: {}.", entry_key, e)),
+        };
+    }
+    match cache_key {
+        Some(k) => Ok((k, entries)),
+        None => Err("The yarn lockfile has no cache key.".to_string()),
+    }
+}
+
+/// Gets the tarball url and the integrity of a package from the npm registry. The checksums
+/// from berry lockfiles are computed on the archives of the yarn cache, and cannot be used
+/// by flatpak-builder to validate the tarballs.
+pub fn get_registry_package(name: &str, version: &str) -> Result<YarnResolvedPackage, String> {
+    let package_url = format!("{}/{}/{}", NPM_REGISTRY_URL, name, version);
+    let package_metadata = crate::utils::fetch_json(&package_url)?;
+    let tarball_url = match package_metadata["dist"]["tarball"].as_str() {
+        Some(t) => t,
+        None => return Err(format!("No tarball for package {}@{}.", name, version)),
+    };
+    let integrity = match package_metadata["dist"]["integrity"].as_str() {
+        Some(i) => Integrity::parse(i)?,
+        None => match package_metadata["dist"]["shasum"].as_str() {
+            Some(s) => Integrity {
+                algorithm: "sha1".to_string(),
+                digest: s.to_string(),
+            },
+            None => return Err(format!("No checksum for package {}@{}.", name, version)),
+        },
+    };
+    Ok(YarnResolvedPackage {
+        name: name.to_string(),
+        url: tarball_url.to_string(),
+        integrity,
+    })
+}
+
+/// Gets the commands converting the package tarballs to the archives of the yarn berry cache.
+/// The packages are given with the name of their archive in the cache.
+pub fn get_berry_cache_commands(packages: &[(String, String)]) -> Vec<String> {
+    let mut commands: Vec<String> = vec![
+        format!(
+            "printf '%s\\n' '{}' > {}",
+            BERRY_CACHE_SCRIPT.join("' '"),
+            BERRY_CACHE_SCRIPT_PATH
+        ),
+        format!("mkdir -p {}", DEFAULT_CACHE_DESTINATION),
+    ];
+    for (name, cache_filename) in packages {
+        commands.push(format!(
+            "python3 {} {}/{}.tgz {} {}/{}",
+            BERRY_CACHE_SCRIPT_PATH,
+            DEFAULT_TARBALLS_DESTINATION,
+            cache_filename,
+            name,
+            DEFAULT_CACHE_DESTINATION,
+            cache_filename
+        ));
+    }
+    commands
+}
+
+/// Builds the module of a yarn berry project. The package tarballs are downloaded from the npm
+/// registry and converted to the archives yarn expects in its cache during the build. Since
+/// those archives are not byte for byte the ones yarn produces, the checksums of the lockfile
+/// are not validated by yarn, and the tarballs are validated by flatpak-builder instead.
+pub fn get_berry_module(lockfile_path: &str, lockfile_content: &str) -> Result<ImportResult, String> {
+    let (cache_key, entries) = parse_berry_lockfile(lockfile_content)?;
+
+    let module_name = get_project_name(lockfile_path).unwrap_or("yarn_sources".to_string());
+    let mut build_options_env: BTreeMap<String, String> = BTreeMap::new();
+    // The cache is not shared with the global cache, so the archives are named after the cache key.
+    for (name, value) in [
+        ("YARN_ENABLE_GLOBAL_CACHE", "false"),
+        ("YARN_ENABLE_MIRROR", "false"),
+        ("YARN_ENABLE_NETWORK", "false"),
+        ("YARN_CHECKSUM_BEHAVIOR", "ignore"),
+    ] {
+        build_options_env.insert(name.to_string(), value.to_string());
+    }
+    build_options_env.insert(
+        "YARN_CACHE_FOLDER".to_string(),
+        format!("/run/build/{}/{}", module_name, DEFAULT_CACHE_DESTINATION),
+    );
+    let mut yarn_module = FlatpakModule {
+        name: module_name,
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        build_options: Some(FlatpakBuildOptions {
+            env: FlatpakBuildOptionsEnv::Dict(build_options_env),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    yarn_module
+        .build_commands
+        .push("yarn install --immutable".to_string());
+    yarn_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+
+    let mut cache_packages: Vec<(String, String)> = vec![];
+    let mut unsupported_entries: Vec<String> = vec![];
+    for entry in entries {
+        if entry.is_local() {
+            continue;
+        }
+        let (name, reference, version) = match (entry.get_locator(), entry.get_npm_version()) {
+            (Some((name, reference)), Some(version)) => (name, reference, version),
+            _ => {
+                unsupported_entries.push(entry.resolution.to_string());
+                continue;
+            }
+        };
+        let package = match get_registry_package(&name, &version) {
+            Ok(p) => p,
+            Err(e) => {
+                log::warn!(
+                    "Could not get package {}@{} from the npm registry: {}",
+                    name,
+                    version,
+                    e
+                );
+                unsupported_entries.push(entry.resolution.to_string());
+                continue;
+            }
+        };
+        let cache_filename = get_berry_cache_filename(&name, &reference, &cache_key);
+        let mut source = package.get_source();
+        source.dest = Some(DEFAULT_TARBALLS_DESTINATION.to_string());
+        source.dest_filename = Some(format!("{}.tgz", cache_filename));
+        yarn_module.sources.push(FlatpakSourceItem::Description(source));
+        cache_packages.push((name, cache_filename));
+    }
+    yarn_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(get_berry_cache_commands(&cache_packages)),
+            ..Default::default()
+        }));
+
+    let mut import_result = ImportResult::new(yarn_module);
+    import_result.unsupported_entries = unsupported_entries;
+    Ok(import_result)
+}
+
+pub fn get_classic_packages(lockfile_content: &str) -> Result<Vec<YarnResolvedPackage>, String> {
+    let mut packages: Vec<YarnResolvedPackage> = parse_classic_lockfile(lockfile_content)?
+        .iter()
+        .filter_map(|e| e.resolve().ok())
+        .collect();
+    packages.sort_by(|a, b| a.url.cmp(&b.url));
+    packages.dedup_by(|a, b| a.url == b.url);
+    Ok(packages)
+}
+
+/// Gets the entries of the lockfile which are not downloaded from a registry, and have to be
+/// handled manually.
+pub fn get_unsupported_packages(lockfile_content: &str) -> Result<Vec<String>, String> {
+    Ok(parse_classic_lockfile(lockfile_content)?
+        .iter()
+        .filter_map(|e| e.resolve().err())
+        .collect())
+}
+
+/// Gets the name of the project from the `package.json` file next to the lockfile, as a
+/// module name.
+fn get_project_name(lockfile_path: &str) -> Option<String> {
+    let package_json_path = path::Path::new(lockfile_path).with_file_name("package.json");
+    let package_json_content = fs::read_to_string(package_json_path).ok()?;
+    let package_json: serde_json::Value = serde_json::from_str(&package_json_content).ok()?;
+    package_json["name"].as_str().map(get_module_name)
+}
+
+pub fn file_path_matches(path: &str) -> bool {
+    path.ends_with("yarn.lock")
+}
+
+pub fn get_yarn_module(lockfile_path: &str, lockfile_content: &str) -> Result<ImportResult, String> {
+    if is_berry_lockfile(lockfile_content) {
+        return get_berry_module(lockfile_path, lockfile_content);
+    }
+    let packages = get_classic_packages(lockfile_content)?;
+
+    let module_name = get_project_name(lockfile_path).unwrap_or("yarn_sources".to_string());
+    let mirror_dir = format!("/run/build/{}/{}", module_name, DEFAULT_MIRROR_DESTINATION);
+    let offline_config_commands: Vec<String> = vec![
+        format!("echo 'yarn-offline-mirror \"{}\"' >> .yarnrc", mirror_dir),
+        "echo 'yarn-offline-mirror-pruning false' >> .yarnrc".to_string(),
+    ];
+
+    let mut yarn_module = FlatpakModule {
+        name: module_name,
+        buildsystem: Some(FlatpakBuildSystem::Simple),
+        ..Default::default()
+    };
+    yarn_module
+        .build_commands
+        .push("yarn install --offline --frozen-lockfile".to_string());
+
+    yarn_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Git),
+            path: Some("./".to_string()),
+            ..Default::default()
+        }));
+    for package in packages {
+        yarn_module
+            .sources
+            .push(FlatpakSourceItem::Description(package.get_source()));
+    }
+    yarn_module
+        .sources
+        .push(FlatpakSourceItem::Description(FlatpakSource {
+            r#type: Some(FlatpakSourceType::Shell),
+            commands: Some(offline_config_commands),
+            ..Default::default()
+        }));
+
+    let mut import_result = ImportResult::new(yarn_module);
+    import_result.unsupported_entries = get_unsupported_packages(lockfile_content)?;
+    Ok(import_result)
+}
+
+pub struct YarnImporter {}
+impl Importer for YarnImporter {
+    fn get_name(&self) -> String {
+        "yarn".to_string()
+    }
+
+    fn file_path_matches(&self, manifest_path: &str) -> bool {
+        file_path_matches(manifest_path)
+    }
+
+    fn import(&self, manifest_path: &str, _options: &ImportOptions) -> Result<ImportResult, String> {
+        let lockfile_content = match fs::read_to_string(manifest_path) {
+            Ok(c) => c,
+            Err(e) => {
+                return Err(format!(
+                    "Could not read yarn lockfile at {}: {}",
+                    manifest_path, e
+                ))
+            }
+        };
+        get_yarn_module(manifest_path, &lockfile_content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_parse_classic_lockfile() {
+        let lockfile_content = r###"
+# THIS IS AN AUTOGENERATED FILE. DO NOT EDIT THIS FILE DIRECTLY.
+# yarn lockfile v1
+
+
+"@babel/code-frame@^7.0.0", "@babel/code-frame@^7.10.4":
+  version "7.12.13"
+  resolved "https://registry.yarnpkg.com/@babel/code-frame/-/code-frame-7.12.13.tgz#dcfc826beef65e75c50e21d3837d7d95798dd658"
+  integrity sha512-HV1Cm0Q3ZrpCR93tkWOYiuYIgLxZXZFVG2VgK+MBWjUqZTundupbfx2aXarXuw5Ko5aMcjtJgbSs4vUGBS5v6g==
+  dependencies:
+    "@babel/highlight" "^7.12.13"
+
+left-pad@^1.3.0:
+  version "1.3.0"
+  resolved "https://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz#5b8a3a7765dfe001261dde915589e782f8c94d1e"
+
+my-fork@louib/my-fork:
+  version "1.0.0"
+  resolved "git+https://github.com/louib/my-fork.git#abcdef"
+"###;
+        assert!(!is_berry_lockfile(lockfile_content));
+        let entries = parse_classic_lockfile(lockfile_content).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].specs.len(), 2);
+        assert_eq!(entries[0].get_name(), "@babel/code-frame");
+        assert_eq!(entries[0].version, "7.12.13");
+
+        let packages = get_classic_packages(lockfile_content).unwrap();
+        assert_eq!(packages.len(), 2);
+        assert_eq!(
+            get_unsupported_packages(lockfile_content).unwrap(),
+            vec!["my-fork@1.0.0 (git+https://github.com/louib/my-fork.git#abcdef)"]
+        );
+        assert_eq!(packages[0].integrity.algorithm, "sha512");
+        assert_eq!(packages[0].get_mirror_filename(), "@babel-code-frame-7.12.13.tgz");
+        assert_eq!(
+            packages[1].url,
+            "https://registry.yarnpkg.com/left-pad/-/left-pad-1.3.0.tgz"
+        );
+        assert_eq!(packages[1].integrity.algorithm, "sha1");
+        assert_eq!(
+            packages[1].integrity.digest,
+            "5b8a3a7765dfe001261dde915589e782f8c94d1e"
+        );
+
+        let source = packages[1].get_source();
+        assert_eq!(source.dest, Some(DEFAULT_MIRROR_DESTINATION.to_string()));
+        assert_eq!(source.dest_filename, Some("left-pad-1.3.0.tgz".to_string()));
+        assert_eq!(
+            source.sha1,
+            Some("5b8a3a7765dfe001261dde915589e782f8c94d1e".to_string())
+        );
+    }
+
+    #[test]
+    pub fn test_parse_berry_lockfile() {
+        let lockfile_content = r###"
+# This file is generated by running "yarn install" inside your project.
+# Manual changes might be lost - proceed with caution!
+
+__metadata:
+  version: 8
+  cacheKey: 10c0
+
+"@babel/code-frame@npm:^7.22.13":
+  version: 7.22.13
+  resolution: "@babel/code-frame@npm:7.22.13"
+  checksum: 10c0/f4cc8ae1000265677daf4845083b72f88d00d311adb1a93c94eb4b07bf0ed6828a81ae4ac43ee7d476775000b93a28a9cddec18fbdc5796212d8dcccd5de72bd
+  languageName: node
+  linkType: hard
+
+"lodash@npm:^4.17.21":
+  version: 4.17.21
+  resolution: "lodash@npm:4.17.21"
+  checksum: 10c0/d8cbea072bb08655bb4c989da418994b073a608dffa608b09ac04b43a791b12aeae7cd7ad919aa4c925f33b48490b5cfe6c1f71d827956071dae2e7bb3a6b74c
+  languageName: node
+  linkType: hard
+
+"my-app@workspace:.":
+  version: 0.0.0-use.local
+  resolution: "my-app@workspace:."
+  languageName: unknown
+  linkType: soft
+
+"my-fork@https://github.com/louib/my-fork.git#commit=abcdef":
+  version: 1.0.0
+  resolution: "my-fork@https://github.com/louib/my-fork.git#commit=abcdef"
+  languageName: node
+  linkType: hard
+
+"resolve@patch:resolve@npm%3A^1.22.1#optional!builtin<compat/resolve>":
+  version: 1.22.8
+  resolution: "resolve@patch:resolve@npm%3A1.22.8#optional!builtin<compat/resolve>::version=1.22.8&hash=c3c19d"
+  languageName: node
+  linkType: hard
+"###;
+        assert!(is_berry_lockfile(lockfile_content));
+        let (cache_key, entries) = parse_berry_lockfile(lockfile_content).unwrap();
+        assert_eq!(cache_key, "10c0");
+        assert_eq!(entries.len(), 5);
+        assert_eq!(
+            entries[0].get_locator(),
+            Some(("@babel/code-frame".to_string(), "npm:7.22.13".to_string()))
+        );
+        assert_eq!(entries[1].get_npm_version(), Some("4.17.21".to_string()));
+        let local_entries: Vec<bool> = entries.iter().map(|e| e.is_local()).collect();
+        assert_eq!(local_entries, vec![false, false, true, false, true]);
+        assert_eq!(entries[3].get_npm_version(), None);
+
+        // The hashes of the locators are the ones computed by yarn.
+        assert_eq!(
+            get_berry_cache_filename("lodash", "npm:4.17.21", &cache_key),
+            "lodash-npm-4.17.21-6382451519-10c0.zip"
+        );
+        assert_eq!(
+            get_berry_cache_filename("@babel/code-frame", "npm:7.22.13", "8"),
+            "@babel-code-frame-npm-7.22.13-2782581d20-8.zip"
+        );
+
+        // The cache key is required to name the archives of the cache.
+        assert!(parse_berry_lockfile("__metadata:\n  version: 8\n").is_err());
+    }
+
+    #[test]
+    pub fn test_get_berry_cache_commands() {
+        let build_dir = std::env::temp_dir().join(format!("fpm-test-yarn-cache-{}", std::process::id()));
+        let cache_filename = "@scope-package-npm-1.0.0-0123456789-10c0.zip".to_string();
+        let package_dir = build_dir.join("package");
+        fs::create_dir_all(package_dir.join("lib")).unwrap();
+        fs::write(package_dir.join("package.json"), "{}").unwrap();
+        fs::write(package_dir.join("lib/index.js"), "").unwrap();
+        fs::create_dir_all(build_dir.join(DEFAULT_TARBALLS_DESTINATION)).unwrap();
+        let tar_output = std::process::Command::new("tar")
+            .arg("czf")
+            .arg(format!("{}/{}.tgz", DEFAULT_TARBALLS_DESTINATION, cache_filename))
+            .arg("package")
+            .current_dir(&build_dir)
+            .output()
+            .unwrap();
+        assert!(tar_output.status.success());
+
+        let commands = get_berry_cache_commands(&[("@scope/package".to_string(), cache_filename.to_string())]);
+        let cache_output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(commands.join(" && "))
+            .current_dir(&build_dir)
+            .output()
+            .unwrap();
+        let list_output = std::process::Command::new("python3")
+            .args(["-m", "zipfile", "-l"])
+            .arg(format!("{}/{}", DEFAULT_CACHE_DESTINATION, cache_filename))
+            .current_dir(&build_dir)
+            .output()
+            .unwrap();
+        let _ = fs::remove_dir_all(&build_dir);
+
+        assert!(
+            cache_output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&cache_output.stderr)
+        );
+        let mut archive_paths: Vec<String> = String::from_utf8_lossy(&list_output.stdout)
+            .lines()
+            .skip(1)
+            .filter_map(|l| l.split_whitespace().next().map(|p| p.to_string()))
+            .collect();
+        archive_paths.sort();
+        assert_eq!(
+            archive_paths,
+            vec![
+                "node_modules/",
+                "node_modules/@scope/",
+                "node_modules/@scope/package/",
+                "node_modules/@scope/package/lib/",
+                "node_modules/@scope/package/lib/index.js",
+                "node_modules/@scope/package/package.json",
+            ]
+        );
+    }
+}
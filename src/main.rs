@@ -4,26 +4,34 @@ use std::env;
 use std::fs;
 use std::path;
 use std::process::{Command, Stdio};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 // TODO tune built-in attributes
 // From https://doc.rust-lang.org/reference/items/modules.html#attributes-on-modules
 // The built-in attributes that have meaning on a module are cfg, deprecated, doc,
 // the lint check attributes, path, and no_implicit_prelude.
 // Modules also accept macro attributes.
-#[macro_use]
 extern crate clap;
 
 use clap::{AppSettings, Parser, Subcommand};
 
 use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::format::FlatpakManifestFormat;
 use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
-use flatpak_rs::source::FlatpakSource;
+use flatpak_rs::source::{FlatpakSource, FlatpakSourceItem};
+
+use crate::utils::FileChange;
 
 // This might need to become a regex at some point, to allow fpm to manage multiple module
 // manifests at the same time.
 const FPM_MODULES_MANIFEST_PATH: &str = "fpm-modules.yaml";
 
+// The name of the file in which the imported modules are written, without the extension.
+const GENERATED_MODULE_FILE_NAME: &str = "generated-sources";
+
+mod build_log;
 mod config;
+mod dev_build;
 mod importers;
 mod utils;
 mod version;
@@ -53,6 +61,27 @@ enum SubCommand {
         /// Also install the application described by the Flatpak manifest.
         #[clap(long, short)]
         install: bool,
+        /// Stop the build before this module. A module name prefix matching a single module
+        /// can be used.
+        #[clap(long, value_name = "MODULE", conflicts_with_all = &["install", "only"])]
+        stop_at: Option<String>,
+        /// Build all the modules up to and including this module, without finishing the build.
+        /// Despite its name, the modules before it are also built, or reused from the build
+        /// cache when they did not change. A module name prefix matching a single module can
+        /// be used.
+        #[clap(long, value_name = "MODULE", conflicts_with = "install")]
+        only: Option<String>,
+        /// Build the dependencies once, then rebuild the main module incrementally from the
+        /// working tree with `flatpak build`. The main module is built out of tree, so the
+        /// autotools projects which cannot be built out of tree are not supported.
+        #[clap(long, conflicts_with_all = &["install", "stop-at", "only"])]
+        dev: bool,
+    },
+    /// List the modules of a Flatpak manifest, in build order. Can be used to complete the
+    /// module names of `make --stop-at` and `make --only`.
+    Modules {
+        /// The path of the Flatpak manifest. Defaults to the manifest of the current workspace.
+        manifest_file_path: Option<String>,
     },
     /// Checkout a workspace.
     Checkout {
@@ -75,7 +104,13 @@ enum SubCommand {
         command: Option<String>,
     },
     /// Remove the build directories and build artifacts.
-    Clean {},
+    Clean {
+        /// The name of the workspace to clean. Defaults to the current workspace.
+        workspace: Option<String>,
+        /// Clean all the workspaces.
+        #[clap(long, short, conflicts_with = "workspace")]
+        all: bool,
+    },
     /// List the available Flatpak workspaces.
     Ls {
         /// Parse the project's files to detect build environments.
@@ -89,15 +124,64 @@ enum SubCommand {
         package_name: String,
         /// The path of the Flatpak manifest to install the package into.
         manifest_file_path: Option<String>,
+        /// Print the changes to the Flatpak manifest without applying them.
+        #[clap(long)]
+        dry_run: bool,
+        /// Install the first search result without asking for confirmation, and apply the changes
+        /// to the Flatpak manifest without asking either.
+        #[clap(long, short)]
+        yes: bool,
     },
-    /// Import packages from a language-specific package manager.
-    #[clap(setting(AppSettings::ArgRequiredElseHelp))]
+    /// Import packages from a language-specific package manager. When no manifest file is
+    /// given, the dependency manifests found in the repository are imported.
+    ///
+    /// Maven projects are imported from the local repository populated with
+    /// `mvn -Dmaven.repo.local=.flatpak-maven-repository dependency:go-offline package`.
+    /// Other package managers can be supported with external importers, which are executables
+    /// named `fpm-importer-<name>` in the `PATH`.
     Import {
         /// Path of the manifest file.
-        manifest_file_path: String,
+        manifest_file_path: Option<String>,
+        /// Path of the file in which to write the generated module. Defaults to
+        /// generated-sources.json or generated-sources.yaml next to the Flatpak manifest.
+        /// Can only be used when importing a single manifest.
+        #[clap(long, short)]
+        output: Option<String>,
+        /// Comma-separated list of features to enable, for the package managers supporting them.
+        #[clap(long, use_value_delimiter = true)]
+        features: Vec<String>,
+        /// Packages of the workspace to build, for the package managers supporting them.
+        /// Can be specified multiple times.
+        #[clap(long = "package", short)]
+        packages: Vec<String>,
+        /// Only import the dependencies used on this target triple, for example
+        /// x86_64-unknown-linux-gnu, for the package managers supporting it. Can be specified
+        /// multiple times.
+        #[clap(long = "target")]
+        targets: Vec<String>,
+        /// Import all the detected manifests without asking for confirmation.
+        #[clap(long, short)]
+        all: bool,
+        /// Only import the manifests of this package manager, for example `cargo` or `npm`.
+        /// Can be specified multiple times.
+        #[clap(long = "package-manager")]
+        package_managers: Vec<String>,
+        /// Print the changes to the Flatpak manifest and to the generated files without
+        /// applying them.
+        #[clap(long)]
+        dry_run: bool,
+        /// Apply the changes without asking for confirmation. Implies `--all` when no manifest
+        /// file is given.
+        #[clap(long, short)]
+        yes: bool,
     },
     /// Show the current build status for the repository.
     Status {},
+    /// Show the build history of a workspace.
+    Log {
+        /// The name of the workspace. Defaults to the current workspace.
+        workspace: Option<String>,
+    },
     /// Print statistics of the database.
     Stats {},
 }
@@ -183,7 +267,7 @@ fn main() {
                 );
             }
         }
-        SubCommand::Ls { parse } => {
+        SubCommand::Ls { parse: _ } => {
             let git_cache_dir = path::Path::new(fpm_core::utils::DEFAULT_GIT_CACHE_DIR);
             if !git_cache_dir.is_dir() {
                 panic!("This does not seem like a git project (.git/ was not found).");
@@ -233,34 +317,126 @@ fn main() {
                 println!("Use `checkout` to select a workspace.");
             }
         }
-        SubCommand::Clean {} => {
-            let flatpak_build_cache_dir = path::Path::new(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_CACHE_DIR);
-            if flatpak_build_cache_dir.is_dir() {
-                println!("Removing {}.", fpm_core::utils::DEFAULT_FLATPAK_BUILDER_CACHE_DIR);
-                fs::remove_dir_all(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_CACHE_DIR).unwrap();
+        SubCommand::Clean { workspace, all } => {
+            let mut dirs_to_remove: Vec<path::PathBuf> = vec![];
+            if *all {
+                // The directories used before each workspace had its own build directories.
+                dirs_to_remove.push(path::PathBuf::from(
+                    fpm_core::utils::DEFAULT_FLATPAK_BUILDER_CACHE_DIR,
+                ));
+                dirs_to_remove.push(path::PathBuf::from(
+                    fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR,
+                ));
+                for workspace_name in crate::config::get_cached_workspace_names() {
+                    dirs_to_remove.push(crate::config::get_workspace_build_dir(&workspace_name));
+                    dirs_to_remove.push(crate::config::get_workspace_state_dir(&workspace_name));
+                    dirs_to_remove.push(crate::config::get_workspace_dev_build_dir(&workspace_name));
+                }
+            } else {
+                let workspace_name = match workspace {
+                    Some(w) => w.as_str(),
+                    None => config.get_current_workspace_name(),
+                };
+                dirs_to_remove.push(crate::config::get_workspace_build_dir(workspace_name));
+                dirs_to_remove.push(crate::config::get_workspace_state_dir(workspace_name));
+                dirs_to_remove.push(crate::config::get_workspace_dev_build_dir(workspace_name));
             }
 
-            let flatpak_build_output_dir = path::Path::new(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR);
-            if flatpak_build_output_dir.is_dir() {
-                println!(
-                    "Removing {}.",
-                    fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR
-                );
-                fs::remove_dir_all(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR).unwrap();
+            for dir in dirs_to_remove {
+                if dir.is_dir() {
+                    println!("Removing {}.", dir.display());
+                    fs::remove_dir_all(&dir).unwrap();
+                }
             }
         }
         SubCommand::Make {
             manifest_file_path,
             install,
+            stop_at,
+            only,
+            dev,
         } => {
             let manifest_path = get_manifest_file_path(manifest_file_path.as_ref()).unwrap();
             log::info!("Using Flatpak manifest at {}", manifest_path);
 
-            if let Err(e) = FlatpakApplication::load_from_file(manifest_path.to_string()) {
-                panic!("Could not parse Flatpak manifest at {}: {}", &manifest_path, e);
+            let flatpak_application = match FlatpakApplication::load_from_file(manifest_path.to_string()) {
+                Ok(a) => a,
+                Err(e) => panic!("Could not parse Flatpak manifest at {}: {}", &manifest_path, e),
+            };
+
+            let module_names = get_module_names(&flatpak_application, &manifest_path);
+            let mut stop_at_module: Option<String> = None;
+            if let Some(stop_at) = stop_at {
+                match utils::complete_module_name(&module_names, stop_at) {
+                    Ok(m) => stop_at_module = Some(m),
+                    Err(e) => panic!("{}", e),
+                };
+            }
+            if let Some(only) = only {
+                let module_name = match utils::complete_module_name(&module_names, only) {
+                    Ok(m) => m,
+                    Err(e) => panic!("{}", e),
+                };
+                // flatpak-builder can only stop before a module, so the build is stopped at
+                // the module following the one to build.
+                let module_index = module_names.iter().position(|m| m == &module_name).unwrap();
+                stop_at_module = module_names.get(module_index + 1).cloned();
+            }
+
+            let manifest_sha256 = match utils::get_manifest_sha256(&manifest_path) {
+                Ok(s) => s,
+                Err(e) => panic!("Could not compute the checksum of {}: {}", &manifest_path, e),
+            };
+            let workspace_name = config.get_current_workspace_name().to_string();
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let start = Instant::now();
+            let build_result = match dev {
+                true => build_dev_flatpak_application(&flatpak_application, &manifest_path, &workspace_name),
+                false => build_flatpak_application(
+                    &manifest_path,
+                    *install,
+                    &workspace_name,
+                    stop_at_module.as_deref(),
+                    only.is_some(),
+                ),
+            };
+
+            let mut build_record = crate::config::BuildRecord {
+                timestamp,
+                manifest_path: manifest_path.to_string(),
+                manifest_sha256,
+                git_head: utils::get_git_head(),
+                duration_ms: start.elapsed().as_millis() as u64,
+                ..Default::default()
+            };
+            if let Ok(output) = &build_result {
+                build_record.success = output.success;
+                build_record.exit_code = output.exit_code;
+                build_record.log_path = Some(output.log_path.to_str().unwrap_or("").to_string());
+            }
+            config.add_build_record(&workspace_name, build_record);
+            if let Err(e) = crate::config::write_config(&config) {
+                log::warn!("Could not record the build: {}", e);
             }
 
-            build_flatpak_application(&manifest_path, *install).unwrap();
+            match build_result {
+                Ok(output) if !output.success => panic!("{}", output.get_build_error()),
+                Ok(_) => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        SubCommand::Modules { manifest_file_path } => {
+            let manifest_path = get_manifest_file_path(manifest_file_path.as_ref()).unwrap();
+            let flatpak_application = match FlatpakApplication::load_from_file(manifest_path.to_string()) {
+                Ok(a) => a,
+                Err(e) => panic!("Could not parse Flatpak manifest at {}: {}", &manifest_path, e),
+            };
+            for module_name in get_module_names(&flatpak_application, &manifest_path) {
+                println!("{}", module_name);
+            }
         }
         SubCommand::Run {
             manifest_file_path,
@@ -278,13 +454,16 @@ fn main() {
 
             run_flatpak_application(
                 &manifest_path,
-                &command.as_ref().unwrap_or(&flatpak_application.command.unwrap()),
+                config.get_current_workspace_name(),
+                command.as_ref().unwrap_or(&flatpak_application.command.unwrap()),
             )
             .unwrap();
         }
         SubCommand::Install {
             package_name,
             manifest_file_path,
+            dry_run,
+            yes,
         } => {
             if package_name.len() < 4 {
                 panic!("Module name is too short");
@@ -295,9 +474,11 @@ fn main() {
             let mut module_to_install: Option<FlatpakModule> = None;
             for module in modules {
                 println!("{}", module.dump().unwrap());
-                let answer =
-                    fpm_core::utils::ask_yes_no_question("Is this the module you want to install".to_string());
-                if answer {
+                if *yes
+                    || fpm_core::utils::ask_yes_no_question(
+                        "Is this the module you want to install".to_string(),
+                    )
+                {
                     module_to_install = Some(module.clone());
                     break;
                 }
@@ -318,36 +499,53 @@ fn main() {
                     .modules
                     .insert(0, FlatpakModuleItem::Description(module));
 
-                let manifest_dump = flatpak_manifest.dump().unwrap();
-
-                match fs::write(path::Path::new(&manifest_path), manifest_dump) {
-                    Ok(content) => content,
-                    Err(e) => {
-                        panic!("could not write file {}: {}.", manifest_path, e);
-                    }
-                };
+                let manifest_change = FileChange::new(&manifest_path, flatpak_manifest.dump().unwrap());
+                if let Err(e) = utils::apply_file_changes(&[manifest_change], *dry_run, *yes) {
+                    panic!("{}", e);
+                }
             }
         }
         SubCommand::Stats {} => {
             let db = fpm_core::db::Database::get_database();
             println!("{}", db.get_stats());
         }
-        SubCommand::Import { manifest_file_path } => {
+        SubCommand::Import {
+            manifest_file_path,
+            output,
+            features,
+            packages,
+            targets,
+            all,
+            package_managers,
+            dry_run,
+            yes,
+        } => {
             let flatpak_manifest_path = get_manifest_file_path(None).unwrap();
 
-            let package_manager = match importers::PackageManager::detect_from_manifest_path(manifest_file_path)
-            {
-                Some(p) => p,
-                None => {
-                    panic!(
-                        "Could not detect package manager associated with {}.",
-                        manifest_file_path
-                    );
+            let importer_registry = importers::ImporterRegistry::new();
+            let manifests = match manifest_file_path {
+                Some(manifest_file_path) => {
+                    match importer_registry.detect_from_manifest_path(manifest_file_path) {
+                        Some(i) => vec![(manifest_file_path.to_string(), i)],
+                        None => {
+                            panic!(
+                                "Could not detect package manager associated with {}.",
+                                manifest_file_path
+                            );
+                        }
+                    }
                 }
+                None => select_dependency_manifests(&importer_registry, package_managers, *all || *yes),
             };
+            if manifests.len() > 1 && output.is_some() {
+                panic!("The output path can only be used when importing a single manifest.");
+            }
 
-            let module = package_manager.import_packages(manifest_file_path);
-
+            let import_options = importers::ImportOptions {
+                features: features.clone(),
+                packages: packages.clone(),
+                targets: targets.clone(),
+            };
             let mut flatpak_application =
                 match FlatpakApplication::load_from_file(flatpak_manifest_path.to_string()) {
                     Ok(a) => a,
@@ -358,20 +556,56 @@ fn main() {
                         );
                     }
                 };
-
-            // When we support TOML, we should store the packages in a separate file instead.
-            flatpak_application
-                .modules
-                .push(FlatpakModuleItem::Description(module));
+            let mut imported_modules: Vec<FlatpakModule> = vec![];
+            let mut file_changes: Vec<FileChange> = vec![];
+            for (manifest_path, importer) in &manifests {
+                let import_result = match importer.import(manifest_path, &import_options) {
+                    Ok(r) => r,
+                    Err(e) => panic!(
+                        "Could not import {} with {}: {}",
+                        manifest_path,
+                        importer.get_name(),
+                        e
+                    ),
+                };
+                if !import_result.unsupported_entries.is_empty() {
+                    println!(
+                        "The following entries of {} are not supported and need to be added manually:",
+                        manifest_path
+                    );
+                    for entry in &import_result.unsupported_entries {
+                        println!("  {}", entry);
+                    }
+                }
+                let (module, module_change) = add_imported_module(
+                    &flatpak_manifest_path,
+                    manifest_path,
+                    import_result,
+                    output.as_ref(),
+                    manifests.len() > 1,
+                    &mut flatpak_application,
+                    &imported_modules,
+                );
+                imported_modules.push(module);
+                file_changes.push(module_change);
+            }
+            file_changes.push(FileChange::new(
+                &flatpak_manifest_path,
+                flatpak_application.dump().unwrap(),
+            ));
+            if let Err(e) = utils::apply_file_changes(&file_changes, *dry_run, *yes) {
+                panic!("{}", e);
+            }
         }
         SubCommand::Status {} => {
-            let current_workspace = match config.current_workspace {
-                Some(workspace) => workspace,
+            let current_workspace = match &config.current_workspace {
+                Some(workspace) => workspace.to_string(),
                 None => "".to_string(),
             };
 
-            if current_workspace.len() == 0 {
+            if current_workspace.is_empty() {
                 println!("Not in a workspace. Call `ls` to list the workspaces and manifest files.");
+                print_build_status(&config, crate::config::DEFAULT_WORKSPACE_NAME);
                 return;
             }
 
@@ -381,43 +615,187 @@ fn main() {
 
             let manifest_file_path = config.workspaces.get(&current_workspace).unwrap();
             println!("Workspace {} using {}.", current_workspace, manifest_file_path);
+            print_build_status(&config, &current_workspace);
+        }
+        SubCommand::Log { workspace } => {
+            let workspace_name = match workspace {
+                Some(w) => w.as_str(),
+                None => config.get_current_workspace_name(),
+            };
+            let builds = config.get_builds(workspace_name);
+            if builds.is_empty() {
+                println!("Workspace {} was never built.", workspace_name);
+                return;
+            }
+
+            let mut last_successful_build: Option<&crate::config::BuildRecord> = None;
+            let mut build_lines: Vec<String> = vec![];
+            for build in builds {
+                let mut build_line = format_build_record(build);
+                if last_successful_build.is_some_and(|b| b.manifest_sha256 != build.manifest_sha256) {
+                    build_line.push_str(" (manifest changed)");
+                }
+                if build.success {
+                    last_successful_build = Some(build);
+                }
+                build_lines.push(build_line);
+            }
+            for build_line in build_lines.iter().rev() {
+                println!("{}", build_line);
+            }
         }
     }
 }
 
-fn build_flatpak_application(manifest_path: &str, install: bool) -> Result<(), String> {
+/// Formats a build record on a single line, for the build history.
+fn format_build_record(build: &crate::config::BuildRecord) -> String {
+    let status = match (build.success, build.exit_code) {
+        (true, _) => "success".to_string(),
+        (false, Some(exit_code)) => format!("failed ({})", exit_code),
+        (false, None) => "failed".to_string(),
+    };
+    let git_head = match &build.git_head {
+        Some(h) => h.chars().take(8).collect(),
+        None => "-".to_string(),
+    };
+    format!(
+        "{}  {:<12} {:>8}  {:<8}  {}  {}",
+        utils::format_timestamp(build.timestamp),
+        status,
+        utils::format_duration(build.duration_ms),
+        git_head,
+        build.manifest_path,
+        build.log_path.as_deref().unwrap_or("-"),
+    )
+}
+
+/// Prints the last build of a workspace, and whether the manifest changed since the last
+/// successful build.
+fn print_build_status(config: &crate::config::WorkspaceConfig, workspace_name: &str) {
+    let last_build = match config.get_builds(workspace_name).last() {
+        Some(b) => b,
+        None => {
+            println!("Workspace {} was never built.", workspace_name);
+            return;
+        }
+    };
+    println!(
+        "Last build {} on {} after {}.",
+        if last_build.success { "succeeded" } else { "failed" },
+        utils::format_timestamp(last_build.timestamp),
+        utils::format_duration(last_build.duration_ms)
+    );
+    if let Some(log_path) = &last_build.log_path {
+        println!("Build log at {}.", log_path);
+    }
+
+    let last_successful_build = match config.get_last_successful_build(workspace_name) {
+        Some(b) => b,
+        None => {
+            println!("Workspace {} was never built successfully.", workspace_name);
+            return;
+        }
+    };
+    match utils::get_manifest_sha256(&last_successful_build.manifest_path) {
+        Ok(s) if s == last_successful_build.manifest_sha256 => {
+            println!("The manifest did not change since the last successful build.")
+        }
+        Ok(_) => println!("The manifest changed since the last successful build."),
+        Err(e) => println!(
+            "Could not check if the manifest changed since the last successful build: {}",
+            e
+        ),
+    }
+}
+
+/// Gets the names of the modules of a Flatpak manifest, in build order.
+fn get_module_names(flatpak_application: &FlatpakApplication, manifest_path: &str) -> Vec<String> {
+    let manifest_dir = path::Path::new(manifest_path)
+        .parent()
+        .unwrap_or(path::Path::new("./"));
+    utils::get_module_names_in_build_order(&flatpak_application.modules, manifest_dir)
+}
+
+/// Builds a Flatpak application with flatpak-builder. The build is stopped before the
+/// `stop_at` module when one is given, and is not finished when `build_only` is set.
+fn build_flatpak_application(
+    manifest_path: &str,
+    install: bool,
+    workspace_name: &str,
+    stop_at: Option<&str>,
+    build_only: bool,
+) -> Result<build_log::LoggedOutput, String> {
+    // The build directory is cleaned, so the dev build has to start over.
+    dev_build::reset_dev_build(workspace_name)?;
+
     let mut command = Command::new("flatpak-builder");
-    let mut command = command.arg("--user").arg("--force-clean");
+    command.arg("--user").arg("--force-clean");
+    command
+        .arg("--state-dir")
+        .arg(crate::config::get_workspace_state_dir(workspace_name));
 
     if install {
-        let mut command = command.arg("--install");
+        command.arg("--install");
+    }
+    if build_only {
+        command.arg("--build-only");
     }
-    let mut command = command
-        .arg(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR)
+    if let Some(stop_at) = stop_at {
+        command.arg(format!("--stop-at={}", stop_at));
+    }
+    command
+        .arg(crate::config::get_workspace_build_dir(workspace_name))
         .arg(manifest_path);
 
-    let output = command.stdout(Stdio::piped()).spawn().unwrap();
-    let output = match output.wait_with_output() {
-        Ok(o) => o,
-        Err(e) => return Err(e.to_string()),
-    };
-    if !output.status.success() {
-        return Err("Could not run flatpak build.".to_string());
+    let log_path = build_log::get_new_log_path(&crate::config::get_workspace_logs_dir(workspace_name))?;
+    log::info!("Writing the build log to {}.", log_path.display());
+    build_log::run_logged_command(&mut command, &log_path)
+}
+
+/// Builds the dependencies of a Flatpak application with flatpak-builder if they changed, and
+/// then builds its main module from the working tree.
+fn build_dev_flatpak_application(
+    flatpak_application: &FlatpakApplication,
+    manifest_path: &str,
+    workspace_name: &str,
+) -> Result<build_log::LoggedOutput, String> {
+    let dev_build = dev_build::DevBuild::new(flatpak_application, manifest_path, workspace_name)?;
+    if dev_build.needs_dependencies_build() {
+        println!("Building the dependencies of module {}.", dev_build.module.name);
+        let output = build_flatpak_application(
+            manifest_path,
+            false,
+            workspace_name,
+            Some(&dev_build.module.name),
+            false,
+        )?;
+        if !output.success {
+            return Ok(output);
+        }
+        dev_build.set_dependencies_built()?;
     }
-    Ok(())
+    dev_build.build()
 }
 
-fn run_flatpak_application(manifest_file_path: &str, flatpak_command: &str) -> Result<(), String> {
-    if !path::Path::new(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR).is_dir() {
+fn run_flatpak_application(
+    manifest_file_path: &str,
+    workspace_name: &str,
+    flatpak_command: &str,
+) -> Result<(), String> {
+    let build_dir = crate::config::get_workspace_build_dir(workspace_name);
+    if !build_dir.is_dir() {
         return Err("The application has not been build yet. Run `fpm make` first.".to_string());
     }
 
     let mut command = Command::new("flatpak-builder");
-    let mut command = command.arg("--run");
+    command.arg("--run");
+    command
+        .arg("--state-dir")
+        .arg(crate::config::get_workspace_state_dir(workspace_name));
 
-    let mut command = command.arg(fpm_core::utils::DEFAULT_FLATPAK_BUILDER_OUTPUT_DIR);
-    let mut command = command.arg(manifest_file_path);
-    let mut command = command.arg(flatpak_command);
+    command.arg(build_dir);
+    command.arg(manifest_file_path);
+    command.arg(flatpak_command);
 
     let output = command.stdout(Stdio::piped()).spawn().unwrap();
     let output = match output.wait_with_output() {
@@ -430,9 +808,284 @@ fn run_flatpak_application(manifest_file_path: &str, flatpak_command: &str) -> R
     Ok(())
 }
 
+/// Finds the dependency manifests of the repository, and asks which ones to import
+/// unless all of them were requested.
+fn select_dependency_manifests<'a>(
+    importer_registry: &'a importers::ImporterRegistry,
+    importer_names: &[String],
+    select_all: bool,
+) -> Vec<(String, &'a dyn importers::Importer)> {
+    for importer_name in importer_names {
+        if importer_registry.get_importer(importer_name).is_none() {
+            panic!(
+                "Unknown package manager {}. Supported package managers are {}.",
+                importer_name,
+                importer_registry.get_importer_names().join(", ")
+            );
+        }
+    }
+
+    let mut manifests = match importers::detect_manifests(path::Path::new("./"), importer_registry) {
+        Ok(m) => m,
+        Err(e) => panic!("Could not detect the dependency manifests: {}", e),
+    };
+    if !importer_names.is_empty() {
+        manifests.retain(|(_, i)| importer_names.iter().any(|n| n.to_lowercase() == i.get_name()));
+    }
+    if manifests.is_empty() {
+        panic!("Could not find any dependency manifest in the repository.");
+    }
+
+    println!("Found {} dependency manifest(s):", manifests.len());
+    for (manifest_path, importer) in &manifests {
+        println!("{} ({})", manifest_path, importer.get_name());
+    }
+    if select_all {
+        return manifests;
+    }
+    manifests
+        .into_iter()
+        .filter(|(manifest_path, _)| fpm_core::utils::ask_yes_no_question(format!("Import {}", manifest_path)))
+        .collect()
+}
+
+/// Adds an imported module to the Flatpak manifest, replacing the module from a previous
+/// import if any. Returns the module with its merged sources, and the change writing it.
+fn add_imported_module(
+    flatpak_manifest_path: &str,
+    manifest_path: &str,
+    import_result: importers::ImportResult,
+    output_path: Option<&String>,
+    use_module_file_name: bool,
+    flatpak_application: &mut FlatpakApplication,
+    imported_modules: &[FlatpakModule],
+) -> (FlatpakModule, FileChange) {
+    let mut module = import_result.module;
+
+    let module_name = module.name.to_string();
+    let (generated_module_path, generated_module_include) = match get_generated_module_path(
+        flatpak_manifest_path,
+        output_path,
+        if use_module_file_name {
+            Some(&module_name)
+        } else {
+            None
+        },
+    ) {
+        Ok(p) => p,
+        Err(e) => panic!("{}", e),
+    };
+
+    // Merging the sources with the ones of a previous import of the same module, and making
+    // sure they do not conflict with the sources of the other modules.
+    let flatpak_manifest_dir = path::Path::new(flatpak_manifest_path)
+        .parent()
+        .unwrap_or(path::Path::new("./"));
+    // Only the modules written by a previous import are merged with, the modules written
+    // by hand are never modified.
+    let (previous_imports, other_modules): (Vec<FlatpakModuleItem>, Vec<FlatpakModuleItem>) =
+        flatpak_application
+            .modules
+            .iter()
+            .cloned()
+            .partition(|m| match m {
+                FlatpakModuleItem::Path(p) => {
+                    is_previous_import(p, &generated_module_include, &module_name, flatpak_manifest_dir)
+                }
+                FlatpakModuleItem::Description(_) => false,
+            });
+    let mut existing_module: Option<FlatpakModule> =
+        utils::get_all_modules(&previous_imports, flatpak_manifest_dir)
+            .into_iter()
+            .map(|m| m.module)
+            .find(|m| m.name == module_name);
+    if existing_module.is_none() && path::Path::new(&generated_module_path).is_file() {
+        existing_module = FlatpakModule::load_from_file(generated_module_path.to_string())
+            .ok()
+            .filter(|m| m.name == module_name);
+    }
+    // The modules imported previously are not written yet.
+    let mut other_sources: Vec<FlatpakSourceItem> = vec![];
+    for imported_module in imported_modules {
+        other_sources.extend(imported_module.sources.iter().cloned());
+    }
+    for other_module in utils::get_all_modules(&other_modules, flatpak_manifest_dir) {
+        other_sources.extend(other_module.module.sources);
+    }
+    let existing_sources = match &existing_module {
+        Some(m) => m.sources.clone(),
+        None => vec![],
+    };
+    // The checksums can legitimately change between two imports of the same module, for
+    // example when an archive generated by a forge is regenerated.
+    for url in importers::merge::get_checksum_conflicts(&module.sources, &existing_sources) {
+        log::warn!("The checksum of {} changed since the previous import.", url);
+    }
+    let conflicts = importers::merge::get_checksum_conflicts(&module.sources, &other_sources);
+    if !conflicts.is_empty() {
+        panic!(
+            "Could not import {}, other modules download the same files with different checksums:\n{}",
+            manifest_path,
+            conflicts.join("\n")
+        );
+    }
+    // The sources shared with other modules are not removed from the imported module, since
+    // each module is built in its own directory and needs all of its sources. flatpak-builder
+    // only downloads them once, since its downloads are identified by their checksum.
+    let shared_sources = importers::merge::get_shared_sources(&module.sources, &other_sources);
+    if !shared_sources.is_empty() {
+        println!(
+            "{} source(s) are also used by other modules, and will only be downloaded once.",
+            shared_sources.len()
+        );
+        log::debug!("Shared sources: {}", shared_sources.join(", "));
+    }
+    let sources_merge = importers::merge::merge_sources(&existing_sources, module.sources);
+    module.sources = sources_merge.sources;
+    if existing_module.is_some() {
+        for added_source in &sources_merge.added {
+            println!("+ {}", added_source);
+        }
+        for removed_source in &sources_merge.removed {
+            println!("- {}", removed_source);
+        }
+        println!(
+            "{} source(s) added, {} removed and {} kept.",
+            sources_merge.added.len(),
+            sources_merge.removed.len(),
+            sources_merge.kept.len()
+        );
+    } else {
+        println!("{} source(s) added.", sources_merge.added.len());
+    }
+
+    module.format = match FlatpakManifestFormat::from_path(&generated_module_path) {
+        Some(f) => f,
+        None => panic!(
+            "Could not determine the format of the generated module from {}.",
+            generated_module_path
+        ),
+    };
+    let module_change = FileChange::new(&generated_module_path, module.dump().unwrap());
+
+    // Replacing the modules from a previous import, if any, even when they were
+    // written to another generated file. The modules written by hand are never replaced.
+    let modules_count = flatpak_application.modules.len();
+    flatpak_application.modules.retain(|m| match m {
+        FlatpakModuleItem::Path(p) => {
+            !is_previous_import(p, &generated_module_include, &module_name, flatpak_manifest_dir)
+        }
+        FlatpakModuleItem::Description(_) => true,
+    });
+    if flatpak_application.modules.len() != modules_count {
+        log::info!("Replacing the modules from a previous import.");
+    }
+    flatpak_application
+        .modules
+        .push(FlatpakModuleItem::Path(generated_module_include));
+
+    (module, module_change)
+}
+
+/// Whether a module included in a Flatpak manifest was written by a previous import of a
+/// module. Only the generated files are considered, so that the modules written by hand
+/// are never modified, even when they have the same name as the imported module.
+fn is_previous_import(
+    module_include: &str,
+    generated_module_include: &str,
+    module_name: &str,
+    flatpak_manifest_dir: &path::Path,
+) -> bool {
+    if module_include == generated_module_include {
+        return true;
+    }
+    let is_generated_file = path::Path::new(module_include)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .is_some_and(|f| f.starts_with(GENERATED_MODULE_FILE_NAME));
+    if !is_generated_file {
+        return false;
+    }
+    let included_module_path = flatpak_manifest_dir.join(module_include);
+    match FlatpakModule::load_from_file(included_module_path.to_str().unwrap_or("").to_string()) {
+        Ok(included_module) => included_module.name == module_name,
+        Err(_) => false,
+    }
+}
+
+/// Gets the path of the generated module, and the path to use to include it in the Flatpak
+/// manifest. When a module name is given, it is used in the default file name, so that
+/// multiple modules can be imported side by side.
+fn get_generated_module_path(
+    flatpak_manifest_path: &str,
+    output_path: Option<&String>,
+    module_name: Option<&String>,
+) -> Result<(String, String), String> {
+    // The parent of a bare file name is an empty path.
+    let flatpak_manifest_dir = match path::Path::new(flatpak_manifest_path).parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => path::PathBuf::from("./"),
+    };
+
+    let output_path = match output_path {
+        Some(p) => path::PathBuf::from(p),
+        None => {
+            let extension = match FlatpakManifestFormat::from_path(flatpak_manifest_path) {
+                Some(FlatpakManifestFormat::JSON) => "json",
+                _ => "yaml",
+            };
+            let file_name = match module_name {
+                Some(n) => format!(
+                    "{}-{}",
+                    GENERATED_MODULE_FILE_NAME,
+                    n.replace(
+                        |c: char| !c.is_alphanumeric() && c != '-' && c != '_' && c != '.',
+                        "-"
+                    )
+                ),
+                None => GENERATED_MODULE_FILE_NAME.to_string(),
+            };
+            flatpak_manifest_dir.join(format!("{}.{}", file_name, extension))
+        }
+    };
+
+    let output_file_name = match output_path.file_name() {
+        Some(f) => f.to_owned(),
+        None => return Err(format!("Invalid output path {}.", output_path.display())),
+    };
+    let output_dir = match output_path.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d.to_path_buf(),
+        _ => path::PathBuf::from("./"),
+    };
+    // The directories must exist to be canonicalized.
+    let (canonical_manifest_dir, canonical_output_dir) =
+        match (flatpak_manifest_dir.canonicalize(), output_dir.canonicalize()) {
+            (Ok(m), Ok(o)) => (m, o),
+            _ => return Err(format!("Invalid output path {}.", output_path.display())),
+        };
+    let relative_output_dir = match canonical_output_dir.strip_prefix(&canonical_manifest_dir) {
+        Ok(d) => d.to_path_buf(),
+        Err(_) => {
+            return Err(format!(
+                "The generated module must be in the same directory as the Flatpak manifest {}, or in a sub-directory.",
+                flatpak_manifest_path
+            ))
+        }
+    };
+
+    Ok((
+        output_path.to_str().unwrap_or("").to_string(),
+        relative_output_dir
+            .join(output_file_name)
+            .to_str()
+            .unwrap_or("")
+            .to_string(),
+    ))
+}
+
 pub fn get_manifest_file_path(path_arg: Option<&String>) -> Option<String> {
     if let Some(manifest_file_path) = path_arg {
-        if manifest_file_path.trim().len() != 0 {
+        if !manifest_file_path.trim().is_empty() {
             return Some(manifest_file_path.to_string());
         }
     };
@@ -456,5 +1109,62 @@ pub fn get_manifest_file_path(path_arg: Option<&String>) -> Option<String> {
             }
         }
     };
-    return Some(manifest_path);
+    Some(manifest_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_get_generated_module_path() {
+        assert_eq!(
+            get_generated_module_path("org.example.App.yaml", None, None).unwrap(),
+            (
+                "./generated-sources.yaml".to_string(),
+                "generated-sources.yaml".to_string()
+            )
+        );
+        assert_eq!(
+            get_generated_module_path("./org.example.App.json", None, Some(&"app".to_string())).unwrap(),
+            (
+                "./generated-sources-app.json".to_string(),
+                "generated-sources-app.json".to_string()
+            )
+        );
+        let output_path = "src/generated-sources.yaml".to_string();
+        assert_eq!(
+            get_generated_module_path("org.example.App.yaml", Some(&output_path), None).unwrap(),
+            (output_path.to_string(), output_path.to_string())
+        );
+        assert!(get_generated_module_path(
+            "src/org.example.App.yaml",
+            Some(&"generated.yaml".to_string()),
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    pub fn test_is_previous_import() {
+        let manifest_dir =
+            std::env::temp_dir().join(format!("fpm-test-previous-import-{}", std::process::id()));
+        fs::create_dir_all(&manifest_dir).unwrap();
+        let module_content = "name: app\nsources:\n  - type: dir\n    path: ./\n";
+        fs::write(manifest_dir.join("generated-sources-app.yaml"), module_content).unwrap();
+        fs::write(manifest_dir.join("app.yaml"), module_content).unwrap();
+
+        let is_generated_include = is_previous_import("deps.yaml", "deps.yaml", "app", &manifest_dir);
+        let is_other_generated_file =
+            is_previous_import("generated-sources-app.yaml", "deps.yaml", "app", &manifest_dir);
+        let is_other_module =
+            is_previous_import("generated-sources-app.yaml", "deps.yaml", "lib", &manifest_dir);
+        let is_hand_written = is_previous_import("app.yaml", "deps.yaml", "app", &manifest_dir);
+        fs::remove_dir_all(&manifest_dir).unwrap();
+
+        assert!(is_generated_include);
+        assert!(is_other_generated_file);
+        assert!(!is_other_module);
+        assert!(!is_hand_written);
+    }
 }
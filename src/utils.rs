@@ -1,4 +1,11 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use flatpak_rs::application::FlatpakApplication;
+use flatpak_rs::module::{FlatpakModule, FlatpakModuleItem};
+use sha2::{Digest, Sha256};
 
 pub fn get_candidate_flatpak_manifests(dir_path: &str) -> Result<Vec<String>, String> {
     let mut response: Vec<String> = vec![];
@@ -26,5 +33,459 @@ pub fn get_candidate_flatpak_manifests(dir_path: &str) -> Result<Vec<String>, St
         log::debug!("Found candidate Flatpak manifest {}", file_path);
         response.push(file_path.to_string());
     }
-    return Ok(response);
+    Ok(response)
+}
+
+/// Computes the hex-encoded sha256 checksum of a file.
+pub fn get_file_sha256(file_path: &Path) -> Result<String, String> {
+    let file_content = match fs::read(file_path) {
+        Ok(c) => c,
+        Err(e) => {
+            return Err(format!(
+                "Could not read file {}: {}",
+                file_path.to_str().unwrap_or(""),
+                e
+            ))
+        }
+    };
+    Ok(format!("{:x}", Sha256::digest(&file_content)))
+}
+
+/// Fetches a remote document.
+pub fn fetch(url: &str) -> Result<String, String> {
+    let output = match Command::new("curl").arg("-sSfL").arg(url).output() {
+        Ok(o) => o,
+        Err(e) => return Err(format!("Could not run curl: {}", e)),
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "Could not fetch {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    match String::from_utf8(output.stdout) {
+        Ok(c) => Ok(c),
+        Err(e) => Err(format!("Invalid document at {}: {}", url, e)),
+    }
+}
+
+/// Fetches and parses a JSON document, for example the metadata of a package from a registry.
+pub fn fetch_json(url: &str) -> Result<serde_json::Value, String> {
+    let content = fetch(url)?;
+    match serde_json::from_str(&content) {
+        Ok(v) => Ok(v),
+        Err(e) => Err(format!("Invalid JSON document at {}: {}", url, e)),
+    }
+}
+
+/// Computes a checksum of a Flatpak manifest, including the content of the module files
+/// it includes, to detect when the manifest changed.
+pub fn get_manifest_sha256(manifest_path: &str) -> Result<String, String> {
+    let manifest_content = match fs::read(manifest_path) {
+        Ok(c) => c,
+        Err(e) => return Err(format!("Could not read file {}: {}", manifest_path, e)),
+    };
+    let flatpak_application = FlatpakApplication::load_from_file(manifest_path.to_string())?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&manifest_content);
+    let manifest_dir = Path::new(manifest_path).parent().unwrap_or(Path::new("./"));
+    hash_included_modules(&mut hasher, &flatpak_application.modules, manifest_dir);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn hash_included_modules(hasher: &mut Sha256, module_items: &[FlatpakModuleItem], base_dir: &Path) {
+    for manifest_module in get_all_modules(module_items, base_dir) {
+        let module_path = match manifest_module.path {
+            Some(p) => p,
+            None => continue,
+        };
+        let relative_path = module_path.strip_prefix(base_dir).unwrap_or(&module_path);
+        hasher.update(relative_path.to_string_lossy().as_bytes());
+        if let Ok(module_content) = fs::read(&module_path) {
+            hasher.update(&module_content);
+        }
+    }
+}
+
+/// A module of a manifest, found by walking the nested modules and the modules included
+/// from other files.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct ManifestModule {
+    pub module: FlatpakModule,
+    /// The file the module was loaded from, when it is included by path.
+    pub path: Option<PathBuf>,
+    /// Whether the module, or one of the modules containing it, is disabled.
+    pub disabled: bool,
+}
+
+/// Gets all the modules of a manifest, in the order in which flatpak-builder builds them, the
+/// nested modules coming before the module containing them. The included paths are relative
+/// to the file including them, and the files which cannot be loaded are skipped.
+pub fn get_all_modules(module_items: &[FlatpakModuleItem], base_dir: &Path) -> Vec<ManifestModule> {
+    let mut modules: Vec<ManifestModule> = vec![];
+    add_modules(&mut modules, module_items, base_dir, false);
+    modules
+}
+
+fn add_modules(
+    modules: &mut Vec<ManifestModule>,
+    module_items: &[FlatpakModuleItem],
+    base_dir: &Path,
+    parent_disabled: bool,
+) {
+    for module_item in module_items {
+        let (module, module_path) = match module_item {
+            FlatpakModuleItem::Description(m) => (m.clone(), None),
+            FlatpakModuleItem::Path(p) => {
+                let module_path = base_dir.join(p);
+                match FlatpakModule::load_from_file(module_path.to_str().unwrap_or("").to_string()) {
+                    Ok(m) => (m, Some(module_path)),
+                    Err(e) => {
+                        log::debug!("Could not load module {}: {}", module_path.display(), e);
+                        continue;
+                    }
+                }
+            }
+        };
+        let module_dir = match &module_path {
+            Some(p) => p.parent().unwrap_or(base_dir).to_path_buf(),
+            None => base_dir.to_path_buf(),
+        };
+        let disabled = parent_disabled || module.disabled.unwrap_or(false);
+        add_modules(modules, &module.modules, &module_dir, disabled);
+        modules.push(ManifestModule {
+            module,
+            path: module_path,
+            disabled,
+        });
+    }
+}
+
+/// Gets the names of the modules of a manifest in the order in which flatpak-builder builds
+/// them. The disabled modules are skipped.
+pub fn get_module_names_in_build_order(module_items: &[FlatpakModuleItem], base_dir: &Path) -> Vec<String> {
+    get_all_modules(module_items, base_dir)
+        .into_iter()
+        .filter(|m| !m.disabled)
+        .map(|m| m.module.name)
+        .collect()
+}
+
+/// Completes a module name from the names of the modules of a manifest. A module name
+/// which is the prefix of a single module is completed to the name of that module.
+pub fn complete_module_name(module_names: &[String], module_name: &str) -> Result<String, String> {
+    if module_names.iter().any(|m| m == module_name) {
+        return Ok(module_name.to_string());
+    }
+
+    let candidates: Vec<&String> = module_names
+        .iter()
+        .filter(|m| m.starts_with(module_name))
+        .collect();
+    match candidates.len() {
+        0 => Err(format!(
+            "Module {} not found. Available modules are {}.",
+            module_name,
+            module_names.join(", ")
+        )),
+        1 => Ok(candidates[0].to_string()),
+        _ => Err(format!(
+            "Module name {} is ambiguous. Matching modules are {}.",
+            module_name,
+            candidates
+                .iter()
+                .map(|m| m.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// Gets the commit checked out in the current git repository, if any.
+pub fn get_git_head() -> Option<String> {
+    let output = Command::new("git").arg("rev-parse").arg("HEAD").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Lists the references of a remote git repository with the commits they point to.
+/// Annotated tags are resolved to the commit they point to.
+pub fn get_git_remote_refs(url: &str) -> Result<BTreeMap<String, String>, String> {
+    let output = match Command::new("git").arg("ls-remote").arg(url).output() {
+        Ok(o) => o,
+        Err(e) => return Err(format!("Could not run git: {}", e)),
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "Could not list the references of {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    let mut refs: BTreeMap<String, String> = BTreeMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let (commit, ref_name) = match line.split_once('\t') {
+            Some(p) => p,
+            None => continue,
+        };
+        match ref_name.strip_suffix("^{}") {
+            Some(tag_name) => {
+                refs.insert(tag_name.to_string(), commit.to_string());
+            }
+            None => {
+                refs.entry(ref_name.to_string()).or_insert(commit.to_string());
+            }
+        }
+    }
+    Ok(refs)
+}
+
+/// Formats a Unix timestamp as a UTC date and time.
+/// See http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds_of_day = timestamp % 86400;
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Formats a duration in a human-readable way, like `1m 12s`.
+pub fn format_duration(duration_ms: u64) -> String {
+    let seconds = duration_ms / 1000;
+    if seconds < 60 {
+        return format!("{}.{}s", seconds, duration_ms % 1000 / 100);
+    }
+    if seconds < 3600 {
+        return format!("{}m {}s", seconds / 60, seconds % 60);
+    }
+    format!("{}h {}m", seconds / 3600, seconds % 3600 / 60)
+}
+
+/// A pending change to a file, which can be reviewed before it is written.
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct FileChange {
+    pub path: String,
+    pub content: String,
+}
+impl FileChange {
+    pub fn new(path: &str, content: String) -> FileChange {
+        FileChange {
+            path: path.to_string(),
+            content,
+        }
+    }
+
+    /// Gets the unified diff between the current content of the file and its new content,
+    /// or an empty string if the content did not change.
+    pub fn get_diff(&self) -> String {
+        let (previous_content, previous_path) = match fs::read_to_string(&self.path) {
+            Ok(c) => (c, self.get_diff_path("a")),
+            Err(_) => ("".to_string(), "/dev/null".to_string()),
+        };
+        if previous_content == self.content {
+            return "".to_string();
+        }
+        similar::TextDiff::from_lines(&previous_content, &self.content)
+            .unified_diff()
+            .header(&previous_path, &self.get_diff_path("b"))
+            .to_string()
+    }
+
+    /// Gets the path of the file in the diff headers, relative to the current directory
+    /// when possible, like git does.
+    fn get_diff_path(&self, prefix: &str) -> String {
+        let file_path = Path::new(&self.path);
+        let relative_path = match std::env::current_dir() {
+            Ok(current_dir) => file_path.strip_prefix(current_dir).unwrap_or(file_path),
+            Err(_) => file_path,
+        };
+        if relative_path.is_absolute() {
+            return relative_path.display().to_string();
+        }
+        format!(
+            "{}/{}",
+            prefix,
+            relative_path.display().to_string().trim_start_matches("./")
+        )
+    }
+
+    pub fn apply(&self) -> Result<(), String> {
+        match fs::write(Path::new(&self.path), &self.content) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write file {}: {}.", self.path, e)),
+        }
+    }
+}
+
+/// Prints the diff of the changes and asks for confirmation before writing them. Nothing
+/// is written for a dry run, and nothing is asked when the changes are accepted in advance.
+/// Returns whether the changes were written.
+pub fn apply_file_changes(
+    file_changes: &[FileChange],
+    dry_run: bool,
+    assume_yes: bool,
+) -> Result<bool, String> {
+    let file_changes: Vec<(&FileChange, String)> = file_changes
+        .iter()
+        .map(|c| (c, c.get_diff()))
+        .filter(|(_, diff)| !diff.is_empty())
+        .collect();
+    if file_changes.is_empty() {
+        println!("No changes to apply.");
+        return Ok(false);
+    }
+
+    if dry_run || !assume_yes {
+        for (_, diff) in &file_changes {
+            print!("{}", diff);
+        }
+    }
+    if dry_run {
+        return Ok(false);
+    }
+    if !assume_yes && !fpm_core::utils::ask_yes_no_question("Apply these changes".to_string()) {
+        return Ok(false);
+    }
+    for (file_change, _) in &file_changes {
+        file_change.apply()?;
+        println!("Wrote {}.", file_change.path);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flatpak_rs::format::FlatpakManifestFormat;
+
+    #[test]
+    pub fn test_get_module_names_in_build_order() {
+        let flatpak_application = FlatpakApplication::parse(
+            FlatpakManifestFormat::YAML,
+            r#"
+            app-id: org.example.App
+            runtime: org.gnome.Platform
+            runtime-version: "44"
+            sdk: org.gnome.Sdk
+            modules:
+              - name: libfoo
+                modules:
+                  - name: libbar
+                  - name: libbaz
+                    disabled: true
+              - name: app
+            "#,
+        )
+        .unwrap();
+        let module_names = get_module_names_in_build_order(&flatpak_application.modules, Path::new("./"));
+        assert_eq!(module_names, vec!["libbar", "libfoo", "app"]);
+    }
+
+    #[test]
+    pub fn test_get_all_modules() {
+        let manifest_dir = std::env::temp_dir().join(format!("fpm-test-all-modules-{}", std::process::id()));
+        fs::create_dir_all(manifest_dir.join("modules")).unwrap();
+        fs::write(
+            manifest_dir.join("modules/libfoo.yaml"),
+            "name: libfoo\ndisabled: true\nsources:\n  - type: dir\n    path: .\nmodules:\n  - libbar.yaml\n",
+        )
+        .unwrap();
+        fs::write(
+            manifest_dir.join("modules/libbar.yaml"),
+            "name: libbar\nsources:\n  - type: dir\n    path: .\n",
+        )
+        .unwrap();
+
+        let module_items = vec![
+            FlatpakModuleItem::Path("modules/libfoo.yaml".to_string()),
+            FlatpakModuleItem::Path("modules/missing.yaml".to_string()),
+            FlatpakModuleItem::Description(FlatpakModule {
+                name: "app".to_string(),
+                ..Default::default()
+            }),
+        ];
+        let modules = get_all_modules(&module_items, &manifest_dir);
+        let module_names = get_module_names_in_build_order(&module_items, &manifest_dir);
+        let _ = fs::remove_dir_all(&manifest_dir);
+
+        assert_eq!(modules.len(), 3);
+        assert_eq!(modules[0].module.name, "libbar");
+        assert_eq!(modules[0].path, Some(manifest_dir.join("modules/libbar.yaml")));
+        // The modules contained in a disabled module are not built either.
+        assert!(modules[0].disabled);
+        assert_eq!(modules[1].module.name, "libfoo");
+        assert_eq!(modules[2].path, None);
+        assert_eq!(module_names, vec!["app"]);
+    }
+
+    #[test]
+    pub fn test_complete_module_name() {
+        let module_names: Vec<String> = vec!["libbar".to_string(), "libfoo".to_string(), "app".to_string()];
+        assert_eq!(complete_module_name(&module_names, "libfoo").unwrap(), "libfoo");
+        assert_eq!(complete_module_name(&module_names, "a").unwrap(), "app");
+        assert!(complete_module_name(&module_names, "lib")
+            .unwrap_err()
+            .contains("libbar, libfoo"));
+        assert!(complete_module_name(&module_names, "libqux").is_err());
+    }
+
+    #[test]
+    pub fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_timestamp(951782400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_timestamp(1700000000), "2023-11-14 22:13:20 UTC");
+    }
+
+    #[test]
+    pub fn test_format_duration() {
+        assert_eq!(format_duration(1250), "1.2s");
+        assert_eq!(format_duration(72000), "1m 12s");
+        assert_eq!(format_duration(7260000), "2h 1m");
+    }
+
+    #[test]
+    pub fn test_file_change_diff() {
+        let file_path = std::env::temp_dir().join(format!("fpm-test-file-change-{}.yaml", std::process::id()));
+        let file_path = file_path.to_str().unwrap();
+
+        let file_change = FileChange::new(file_path, "modules:\n  - foo.yaml\n".to_string());
+        assert!(file_change.get_diff().starts_with("--- /dev/null\n"));
+
+        fs::write(file_path, "modules:\n  - foo.yaml\n").unwrap();
+        assert_eq!(file_change.get_diff(), "");
+
+        let file_change = FileChange::new(file_path, "modules:\n  - bar.yaml\n".to_string());
+        let diff = file_change.get_diff();
+        fs::remove_file(file_path).unwrap();
+        assert!(diff.contains("\n-  - foo.yaml\n+  - bar.yaml\n"));
+    }
 }
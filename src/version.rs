@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 
 // See https://github.com/rust-lang/rfcs/blob/master/text/1105-api-evolution.md
 // and https://doc.rust-lang.org/cargo/reference/manifest.html#the-version-field
@@ -16,7 +17,7 @@ impl SemanticVersion {
     //                  | <version core> "-" <pre-release>
     //                  | <version core> "+" <build>
     //                  | <version core> "-" <pre-release> "+" <build>
-    pub fn parse(version: &String) -> Option<SemanticVersion> {
+    pub fn parse(version: &str) -> Option<SemanticVersion> {
         let mut pre_release = "".to_string();
         let mut build = "".to_string();
 
@@ -25,7 +26,8 @@ impl SemanticVersion {
             build = parts[1].to_string();
         }
 
-        let parts: Vec<&str> = parts[0].split('-').collect();
+        // The pre-release identifiers can contain hyphens.
+        let parts: Vec<&str> = parts[0].splitn(2, '-').collect();
         if parts.len() == 2 {
             pre_release = parts[1].to_string();
         }
@@ -33,40 +35,76 @@ impl SemanticVersion {
         let version_core = parts[0].trim().to_string();
         let version_parts: Vec<&str> = version_core.split('.').collect();
         if version_parts.len() != 3 {
-            eprintln!("Invalid semantic version {}.", version_core);
+            log::debug!("Invalid semantic version {}.", version_core);
             return None;
         }
 
         let major: i32 = match version_parts[0].parse() {
             Ok(v) => v,
             Err(_) => {
-                eprintln!("Invalid major version {}", version_parts[0]);
+                log::debug!("Invalid major version {}", version_parts[0]);
                 return None;
             }
         };
         let minor: i32 = match version_parts[1].parse() {
             Ok(v) => v,
             Err(_) => {
-                eprintln!("Invalid minor version {}", version_parts[1]);
+                log::debug!("Invalid minor version {}", version_parts[1]);
                 return None;
             }
         };
         let patch: i32 = match version_parts[2].parse() {
             Ok(v) => v,
             Err(_) => {
-                eprintln!("Invalid patch version {}", version_parts[2]);
+                log::debug!("Invalid patch version {}", version_parts[2]);
                 return None;
             }
         };
 
         Some(SemanticVersion {
-            major: major,
-            minor: minor,
-            patch: patch,
-            pre_release: pre_release,
-            build: build,
+            major,
+            minor,
+            patch,
+            pre_release,
+            build,
         })
     }
+
+    /// Compares two versions with the precedence rules of semver. The build metadata is ignored.
+    pub fn compare(&self, other: &SemanticVersion) -> Ordering {
+        let ordering = (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+        // A pre-release version has a lower precedence than the associated normal version.
+        match (self.pre_release.is_empty(), other.pre_release.is_empty()) {
+            (true, true) => return Ordering::Equal,
+            (true, false) => return Ordering::Greater,
+            (false, true) => return Ordering::Less,
+            (false, false) => {}
+        }
+        let mut other_identifiers = other.pre_release.split('.');
+        for identifier in self.pre_release.split('.') {
+            let other_identifier = match other_identifiers.next() {
+                Some(i) => i,
+                None => return Ordering::Greater,
+            };
+            // The numeric identifiers have a lower precedence than the alphanumeric ones.
+            let ordering = match (identifier.parse::<u64>(), other_identifier.parse::<u64>()) {
+                (Ok(n), Ok(other_n)) => n.cmp(&other_n),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => identifier.cmp(other_identifier),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        match other_identifiers.next() {
+            Some(_) => Ordering::Less,
+            None => Ordering::Equal,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -75,15 +113,15 @@ mod tests {
 
     #[test]
     pub fn test_parse_invalid_strings() {
-        assert!(SemanticVersion::parse(&"not a version".to_string()).is_none());
-        assert!(SemanticVersion::parse(&"fdsfsdfd.2.3".to_string()).is_none());
-        assert!(SemanticVersion::parse(&"1.fsdfsd.3".to_string()).is_none());
-        assert!(SemanticVersion::parse(&"1.2.fsdfsd".to_string()).is_none());
+        assert!(SemanticVersion::parse("not a version").is_none());
+        assert!(SemanticVersion::parse("fdsfsdfd.2.3").is_none());
+        assert!(SemanticVersion::parse("1.fsdfsd.3").is_none());
+        assert!(SemanticVersion::parse("1.2.fsdfsd").is_none());
     }
 
     #[test]
     pub fn test_parse_simple_version() {
-        let mut sem_ver = SemanticVersion::parse(&"1.2.3".to_string());
+        let sem_ver = SemanticVersion::parse("1.2.3");
         assert!(sem_ver.is_some());
         let sem_ver = sem_ver.unwrap();
         assert_eq!(sem_ver.major, 1);
@@ -93,7 +131,7 @@ mod tests {
 
     #[test]
     pub fn test_parse_version_with_release() {
-        let mut sem_ver = SemanticVersion::parse(&"1.2.3-alpha".to_string());
+        let sem_ver = SemanticVersion::parse("1.2.3-alpha");
         assert!(sem_ver.is_some());
         let sem_ver = sem_ver.unwrap();
         assert_eq!(sem_ver.major, 1);
@@ -104,7 +142,7 @@ mod tests {
 
     #[test]
     pub fn test_parse_version_with_build() {
-        let mut sem_ver = SemanticVersion::parse(&"1.2.3+build".to_string());
+        let sem_ver = SemanticVersion::parse("1.2.3+build");
         assert!(sem_ver.is_some());
         let sem_ver = sem_ver.unwrap();
         assert_eq!(sem_ver.major, 1);
@@ -115,7 +153,7 @@ mod tests {
 
     #[test]
     pub fn test_parse_version_with_build_and_release() {
-        let mut sem_ver = SemanticVersion::parse(&"1.2.3-alpha+build".to_string());
+        let sem_ver = SemanticVersion::parse("1.2.3-alpha+build");
         assert!(sem_ver.is_some());
         let sem_ver = sem_ver.unwrap();
         assert_eq!(sem_ver.major, 1);
@@ -124,4 +162,29 @@ mod tests {
         assert_eq!(sem_ver.build, "build".to_string());
         assert_eq!(sem_ver.pre_release, "alpha".to_string());
     }
+
+    #[test]
+    pub fn test_compare_versions() {
+        let parse = |version: &str| SemanticVersion::parse(version).unwrap();
+        // From the precedence example of https://semver.org/
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.0.1",
+            "1.1.0",
+            "2.0.0",
+        ];
+        for pair in versions.windows(2) {
+            assert_eq!(parse(pair[0]).compare(&parse(pair[1])), Ordering::Less);
+            assert_eq!(parse(pair[1]).compare(&parse(pair[0])), Ordering::Greater);
+        }
+        assert_eq!(parse("1.0.0+build").compare(&parse("1.0.0")), Ordering::Equal);
+        assert_eq!(parse("1.0.0-rc-1").pre_release, "rc-1".to_string());
+    }
 }